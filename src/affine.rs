@@ -0,0 +1,341 @@
+//! This module contains utilities for working with the spatial
+//! transformations declared in a NIfTI-1 header. Affine matrices are
+//! represented as 4x4 row-major arrays of `f64`, mapping voxel index
+//! coordinates `(i, j, k, 1)` to world coordinates `(x, y, z, 1)`.
+
+/// A 4x4 affine transformation matrix, in row-major order.
+pub type Affine = [[f64; 4]; 4];
+
+/// The parameters of a quaternion-based transformation, as stored in
+/// the `quatern_*`, `pixdim` and `qfac` fields of a NIfTI-1 header.
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct Quaternion {
+    /// Quaternion b parameter
+    pub b: f64,
+    /// Quaternion c parameter
+    pub c: f64,
+    /// Quaternion d parameter
+    pub d: f64,
+    /// Translation over x (`quatern_x`)
+    pub x: f64,
+    /// Translation over y (`quatern_y`)
+    pub y: f64,
+    /// Translation over z (`quatern_z`)
+    pub z: f64,
+    /// Voxel spacing (`pixdim[1..4]`)
+    pub spacing: [f64; 3],
+    /// Handedness of the transformation (`pixdim[0]`), either 1 or -1
+    pub qfac: f64,
+}
+
+/// Obtain the 4x4 identity matrix.
+pub fn identity() -> Affine {
+    [
+        [1., 0., 0., 0.],
+        [0., 1., 0., 0.],
+        [0., 0., 1., 0.],
+        [0., 0., 0., 1.],
+    ]
+}
+
+/// Multiply two affine matrices, yielding `a * b`.
+pub fn multiply(a: &Affine, b: &Affine) -> Affine {
+    let mut out = [[0.; 4]; 4];
+    for (r, row) in out.iter_mut().enumerate() {
+        for (c, v) in row.iter_mut().enumerate() {
+            *v = (0..4).map(|k| a[r][k] * b[k][c]).sum();
+        }
+    }
+    out
+}
+
+/// Invert an affine matrix. Returns `None` if the linear part of the
+/// transformation is singular.
+pub fn invert(a: &Affine) -> Option<Affine> {
+    let m = [
+        [a[0][0], a[0][1], a[0][2]],
+        [a[1][0], a[1][1], a[1][2]],
+        [a[2][0], a[2][1], a[2][2]],
+    ];
+    let det = determinant3(&m);
+    if det == 0. || !det.is_finite() {
+        return None;
+    }
+
+    let mut inv = [[0.; 3]; 3];
+    for (r, row) in inv.iter_mut().enumerate() {
+        for (c, v) in row.iter_mut().enumerate() {
+            // adjugate: transposed cofactor matrix
+            let (r1, r2) = ((c + 1) % 3, (c + 2) % 3);
+            let (c1, c2) = ((r + 1) % 3, (r + 2) % 3);
+            *v = (m[r1][c1] * m[r2][c2] - m[r1][c2] * m[r2][c1]) / det;
+        }
+    }
+
+    let t = [a[0][3], a[1][3], a[2][3]];
+    let mut out = identity();
+    for r in 0..3 {
+        out[r][..3].copy_from_slice(&inv[r]);
+        out[r][3] = -(0..3).map(|k| inv[r][k] * t[k]).sum::<f64>();
+    }
+    Some(out)
+}
+
+/// Calculate the determinant of the linear part of an affine matrix. A
+/// negative determinant means that the transformation flips handedness.
+pub fn determinant(a: &Affine) -> f64 {
+    determinant3(&[
+        [a[0][0], a[0][1], a[0][2]],
+        [a[1][0], a[1][1], a[1][2]],
+        [a[2][0], a[2][1], a[2][2]],
+    ])
+}
+
+/// Apply an affine transformation to a point.
+pub fn transform_point(a: &Affine, p: [f64; 3]) -> [f64; 3] {
+    let mut out = [0.; 3];
+    for (r, v) in out.iter_mut().enumerate() {
+        *v = a[r][0] * p[0] + a[r][1] * p[1] + a[r][2] * p[2] + a[r][3];
+    }
+    out
+}
+
+/// Calculate the obliquity of an affine transformation, in degrees. This is
+/// the largest angle between a voxel axis and its closest world axis, as
+/// reported by AFNI's `3dinfo -obliquity`. A cardinal-aligned affine has an
+/// obliquity of 0.
+pub fn obliquity(a: &Affine) -> f64 {
+    let merit = (0..3)
+        .map(|c| {
+            let norm = (0..3).map(|r| a[r][c] * a[r][c]).sum::<f64>().sqrt();
+            if norm == 0. {
+                return 1.;
+            }
+            (0..3).map(|r| a[r][c].abs() / norm).fold(0., f64::max)
+        })
+        .fold(1., f64::min);
+    merit.min(1.).acos().to_degrees()
+}
+
+/// Calculate a cardinal-aligned version of the given affine transformation.
+/// Each voxel axis is snapped to its closest world axis (keeping the original
+/// direction and voxel spacing), while the translation is kept intact.
+pub fn cardinal(a: &Affine) -> Affine {
+    let mut taken = [false; 3];
+    let mut out = identity();
+    out[0][0] = 0.;
+    out[1][1] = 0.;
+    out[2][2] = 0.;
+
+    // assign voxel axes to world axes, the least ambiguous ones first
+    let mut order: Vec<(usize, f64)> = (0..3)
+        .map(|c| {
+            let norm = column_norm(a, c);
+            let best = (0..3).map(|r| a[r][c].abs()).fold(0., f64::max);
+            (c, if norm == 0. { 0. } else { best / norm })
+        })
+        .collect();
+    order.sort_by(|x, y| y.1.partial_cmp(&x.1).unwrap_or(::std::cmp::Ordering::Equal));
+
+    for (c, _) in order {
+        let norm = column_norm(a, c);
+        let r = (0..3)
+            .filter(|r| !taken[*r])
+            .max_by(|x, y| {
+                a[*x][c]
+                    .abs()
+                    .partial_cmp(&a[*y][c].abs())
+                    .unwrap_or(::std::cmp::Ordering::Equal)
+            })
+            .unwrap();
+        taken[r] = true;
+        out[r][c] = if a[r][c] < 0. { -norm } else { norm };
+    }
+
+    for (r, row) in out.iter_mut().enumerate().take(3) {
+        row[3] = a[r][3];
+    }
+    out
+}
+
+/// Build the affine transformation described by quaternion parameters,
+/// following the NIfTI-1 qform method (method 2).
+pub fn from_quaternion(q: &Quaternion) -> Affine {
+    let (b, c, d) = (q.b, q.c, q.d);
+    let a = (1. - (b * b + c * c + d * d)).max(0.).sqrt();
+    let qfac = if q.qfac < 0. { -1. } else { 1. };
+    let [dx, dy, dz] = q.spacing;
+    let dz = dz * qfac;
+
+    [
+        [
+            (a * a + b * b - c * c - d * d) * dx,
+            2. * (b * c - a * d) * dy,
+            2. * (b * d + a * c) * dz,
+            q.x,
+        ],
+        [
+            2. * (b * c + a * d) * dx,
+            (a * a + c * c - b * b - d * d) * dy,
+            2. * (c * d - a * b) * dz,
+            q.y,
+        ],
+        [
+            2. * (b * d - a * c) * dx,
+            2. * (c * d + a * b) * dy,
+            (a * a + d * d - c * c - b * b) * dz,
+            q.z,
+        ],
+        [0., 0., 0., 1.],
+    ]
+}
+
+/// Decompose an affine transformation into quaternion parameters, as done
+/// by `nifti_mat44_to_quatern` in the reference implementation. Any shearing
+/// in the affine is lost in the process.
+pub fn to_quaternion(m: &Affine) -> Quaternion {
+    let mut r = [
+        [m[0][0], m[0][1], m[0][2]],
+        [m[1][0], m[1][1], m[1][2]],
+        [m[2][0], m[2][1], m[2][2]],
+    ];
+
+    // column lengths are the voxel spacings
+    let mut spacing = [0.; 3];
+    for (c, s) in spacing.iter_mut().enumerate() {
+        let norm = (0..3).map(|k| r[k][c] * r[k][c]).sum::<f64>().sqrt();
+        *s = if norm == 0. { 1. } else { norm };
+        for (k, row) in r.iter_mut().enumerate() {
+            if norm == 0. {
+                // degenerate axis, fall back to the respective cardinal axis
+                row[c] = if k == c { 1. } else { 0. };
+            } else {
+                row[c] /= norm;
+            }
+        }
+    }
+
+    // the rotation must be proper, otherwise the third axis is flipped
+    let qfac = if determinant3(&r) < 0. {
+        for row in r.iter_mut() {
+            row[2] = -row[2];
+        }
+        -1.
+    } else {
+        1.
+    };
+
+    let trace = r[0][0] + r[1][1] + r[2][2] + 1.;
+    let (b, c, d) = if trace > 0.5 {
+        let a = 0.5 * trace.sqrt();
+        (
+            0.25 * (r[2][1] - r[1][2]) / a,
+            0.25 * (r[0][2] - r[2][0]) / a,
+            0.25 * (r[1][0] - r[0][1]) / a,
+        )
+    } else {
+        let xd = 1. + r[0][0] - (r[1][1] + r[2][2]);
+        let yd = 1. + r[1][1] - (r[0][0] + r[2][2]);
+        let zd = 1. + r[2][2] - (r[0][0] + r[1][1]);
+        let (a, b, c, d) = if xd > 1. {
+            let b = 0.5 * xd.sqrt();
+            (
+                0.25 * (r[2][1] - r[1][2]) / b,
+                b,
+                0.25 * (r[0][1] + r[1][0]) / b,
+                0.25 * (r[0][2] + r[2][0]) / b,
+            )
+        } else if yd > 1. {
+            let c = 0.5 * yd.sqrt();
+            (
+                0.25 * (r[0][2] - r[2][0]) / c,
+                0.25 * (r[0][1] + r[1][0]) / c,
+                c,
+                0.25 * (r[1][2] + r[2][1]) / c,
+            )
+        } else {
+            let d = 0.5 * zd.sqrt();
+            (
+                0.25 * (r[1][0] - r[0][1]) / d,
+                0.25 * (r[0][2] + r[2][0]) / d,
+                0.25 * (r[1][2] + r[2][1]) / d,
+                d,
+            )
+        };
+        // keep the real part (a) non-negative
+        if a < 0. {
+            (-b, -c, -d)
+        } else {
+            (b, c, d)
+        }
+    };
+
+    Quaternion {
+        b,
+        c,
+        d,
+        x: m[0][3],
+        y: m[1][3],
+        z: m[2][3],
+        spacing,
+        qfac,
+    }
+}
+
+fn column_norm(a: &Affine, c: usize) -> f64 {
+    (0..3).map(|r| a[r][c] * a[r][c]).sum::<f64>().sqrt()
+}
+
+fn determinant3(m: &[[f64; 3]; 3]) -> f64 {
+    m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+        - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_affine_eq(a: &Affine, b: &Affine) {
+        for r in 0..4 {
+            for c in 0..4 {
+                assert!(
+                    (a[r][c] - b[r][c]).abs() < 1e-6,
+                    "mismatch at ({}, {}): {:?} vs {:?}",
+                    r,
+                    c,
+                    a,
+                    b
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn invert_affine() {
+        let a = [
+            [2., 0., 0., 10.],
+            [0., 0., 3., -4.],
+            [0., -1., 0., 7.],
+            [0., 0., 0., 1.],
+        ];
+        let inv = invert(&a).unwrap();
+        assert_affine_eq(&multiply(&a, &inv), &identity());
+        assert!(invert(&[[0.; 4]; 4]).is_none());
+    }
+
+    #[test]
+    fn quaternion_round_trip() {
+        let angle = 15f64.to_radians();
+        let a = [
+            [-2. * angle.cos(), 0., 2.5 * angle.sin(), 90.],
+            [0., 2., 0., -126.],
+            [2. * angle.sin(), 0., 2.5 * angle.cos(), -72.],
+            [0., 0., 0., 1.],
+        ];
+        let q = to_quaternion(&a);
+        assert_affine_eq(&from_quaternion(&q), &a);
+        assert!((obliquity(&a) - 15.).abs() < 1e-6);
+        assert_eq!(obliquity(&cardinal(&a)), 0.);
+    }
+}
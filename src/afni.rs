@@ -0,0 +1,276 @@
+//! This module provides access to the dataset attributes which AFNI stores
+//! in NIfTI extensions with the AFNI code (4), such as the history note,
+//! the sub-brick labels and the statistical parameters of each sub-brick.
+//! Requires the `afni` Cargo feature.
+//!
+//! The extension holds an `AFNI_attributes` XML element, with one
+//! `AFNI_atr` element per attribute. Each attribute is either a string or
+//! an array of floating point or integer numbers.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use nifti::afni::AfniAttributes;
+//! use nifti::{InMemNiftiObject, NiftiObject};
+//! # use nifti::Result;
+//!
+//! # fn run() -> Result<()> {
+//! let mut obj = InMemNiftiObject::from_file("stats.nii.gz")?;
+//! if let Some(attributes) = AfniAttributes::from_extensions(obj.extensions()) {
+//!     let mut attributes = attributes?;
+//!     println!("{:?}", attributes.brick_labels());
+//!     attributes.set_brick_labels(&["Coef", "Tstat"]);
+//!     attributes.store(obj.extensions_mut());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use dom::Element;
+use error::{NiftiError, Result};
+use extension::{Extension, ExtensionSequence};
+use std::fmt::Write;
+use std::str::FromStr;
+use typedef::ExtensionCode;
+
+/// The name of the attribute holding the dataset's history.
+pub const HISTORY_NOTE: &str = "HISTORY_NOTE";
+/// The name of the attribute holding the sub-brick labels.
+pub const BRICK_LABS: &str = "BRICK_LABS";
+
+/// The value of an AFNI attribute.
+#[derive(Debug, PartialEq, Clone)]
+pub enum AfniValue {
+    /// A string (`ni_type="String"`)
+    String(String),
+    /// An array of floating point numbers (`ni_type="float"`)
+    Float(Vec<f32>),
+    /// An array of integers (`ni_type="int"`)
+    Int(Vec<i32>),
+}
+
+impl AfniValue {
+    /// Obtain the string, if this is a string value.
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            AfniValue::String(ref s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Obtain the numbers, if this is a floating point value.
+    pub fn as_floats(&self) -> Option<&[f32]> {
+        match *self {
+            AfniValue::Float(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Obtain the numbers, if this is an integer value.
+    pub fn as_ints(&self) -> Option<&[i32]> {
+        match *self {
+            AfniValue::Int(ref v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+/// The dataset attributes held by an AFNI extension, in document order.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct AfniAttributes {
+    /// XML attributes of the `AFNI_attributes` element, such as
+    /// `self_idcode` and `NIfTI_nums`
+    group: Vec<(String, String)>,
+    attributes: Vec<(String, AfniValue)>,
+}
+
+impl AfniAttributes {
+    /// Create an empty set of attributes.
+    pub fn new() -> Self {
+        AfniAttributes::default()
+    }
+
+    /// Parse the `AFNI_attributes` XML document of an AFNI extension.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::InvalidAfniAttributes` if the document is not valid
+    ///   XML, or if an attribute's value does not match its type.
+    pub fn from_xml(xml: &str) -> Result<Self> {
+        let root = Element::parse(xml.trim_end_matches('\0').as_bytes())
+            .map_err(|e| invalid(e.to_string()))?;
+        if root.name != "AFNI_attributes" {
+            return Err(invalid(format!("unexpected root element `{}`", root.name)));
+        }
+        let attributes = root
+            .children_named("AFNI_atr")
+            .map(|atr| {
+                let name = atr
+                    .attr("atr_name")
+                    .ok_or_else(|| invalid("missing attribute name"))?;
+                let value = match atr.attr("ni_type").unwrap_or("String") {
+                    "String" => AfniValue::String(unquote(&atr.text)),
+                    "float" => AfniValue::Float(parse_numbers(name, &atr.text)?),
+                    "int" => AfniValue::Int(parse_numbers(name, &atr.text)?),
+                    t => return Err(invalid(format!("unknown type `{}` of `{}`", t, name))),
+                };
+                Ok((name.to_string(), value))
+            })
+            .collect::<Result<_>>()?;
+        Ok(AfniAttributes {
+            group: root.attributes,
+            attributes,
+        })
+    }
+
+    /// Parse the attributes held by an extension.
+    ///
+    /// # Errors
+    ///
+    /// - See `from_xml`.
+    pub fn from_extension(extension: &Extension) -> Result<Self> {
+        AfniAttributes::from_xml(&extension.text())
+    }
+
+    /// Parse the attributes held by the first AFNI extension, if any.
+    pub fn from_extensions(extensions: &ExtensionSequence) -> Option<Result<Self>> {
+        extensions
+            .afni_xml()
+            .map(|xml| AfniAttributes::from_xml(&xml))
+    }
+
+    /// Serialize the attributes into an `AFNI_attributes` XML document.
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::from("<?xml version='1.0' ?>\n<AFNI_attributes");
+        for (name, value) in &self.group {
+            let _ = write!(xml, "\n  {}=\"{}\"", name, escape(value));
+        }
+        xml.push_str(" >\n");
+        for (name, value) in &self.attributes {
+            let (ni_type, dimen, text) = match *value {
+                AfniValue::String(ref s) => ("String", 1, format!("\"{}\"", escape(s))),
+                AfniValue::Float(ref v) => ("float", v.len(), join(v)),
+                AfniValue::Int(ref v) => ("int", v.len(), join(v)),
+            };
+            let _ = write!(
+                xml,
+                "<AFNI_atr\n  ni_type=\"{}\"\n  ni_dimen=\"{}\"\n  atr_name=\"{}\" >\n {}\n</AFNI_atr>\n",
+                ni_type,
+                dimen,
+                escape(name),
+                text
+            );
+        }
+        xml.push_str("</AFNI_attributes>\n");
+        xml
+    }
+
+    /// Serialize the attributes into an extension with the AFNI code.
+    pub fn to_extension(&self) -> Extension {
+        Extension::from_data(ExtensionCode::Afni as i32, self.to_xml().into_bytes())
+    }
+
+    /// Replace the AFNI extensions of a sequence with these attributes.
+    pub fn store(&self, extensions: &mut ExtensionSequence) {
+        extensions.retain(|e| e.code() != ExtensionCode::Afni as i32);
+        extensions.push(self.to_extension());
+    }
+
+    /// Get the value of an attribute.
+    pub fn get(&self, name: &str) -> Option<&AfniValue> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v)
+    }
+
+    /// Set the value of an attribute, returning its previous value, if any.
+    /// New attributes are added at the end.
+    pub fn set<S: Into<String>>(&mut self, name: S, value: AfniValue) -> Option<AfniValue> {
+        let name = name.into();
+        match self.attributes.iter_mut().find(|(n, _)| *n == name) {
+            Some(&mut (_, ref mut v)) => Some(::std::mem::replace(v, value)),
+            None => {
+                self.attributes.push((name, value));
+                None
+            }
+        }
+    }
+
+    /// Remove an attribute, returning its value, if any.
+    pub fn remove(&mut self, name: &str) -> Option<AfniValue> {
+        let index = self.attributes.iter().position(|(n, _)| n == name)?;
+        Some(self.attributes.remove(index).1)
+    }
+
+    /// Obtain an iterator to the attributes' names and values.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &AfniValue)> {
+        self.attributes.iter().map(|(n, v)| (n.as_str(), v))
+    }
+
+    /// Whether there are no attributes.
+    pub fn is_empty(&self) -> bool {
+        self.attributes.is_empty()
+    }
+
+    /// Obtain the number of attributes.
+    pub fn len(&self) -> usize {
+        self.attributes.len()
+    }
+
+    /// Get the dataset's history (`HISTORY_NOTE`).
+    pub fn history(&self) -> Option<&str> {
+        self.get(HISTORY_NOTE).and_then(AfniValue::as_str)
+    }
+
+    /// Get the label of each sub-brick (`BRICK_LABS`), which AFNI stores
+    /// separated by `~`.
+    pub fn brick_labels(&self) -> Option<Vec<&str>> {
+        self.get(BRICK_LABS)
+            .and_then(AfniValue::as_str)
+            .map(|s| s.split('~').collect())
+    }
+
+    /// Set the label of each sub-brick (`BRICK_LABS`).
+    pub fn set_brick_labels<S: AsRef<str>>(&mut self, labels: &[S]) {
+        let labels: Vec<&str> = labels.iter().map(AsRef::as_ref).collect();
+        let _ = self.set(BRICK_LABS, AfniValue::String(labels.join("~")));
+    }
+}
+
+fn invalid<S: Into<String>>(reason: S) -> NiftiError {
+    NiftiError::InvalidAfniAttributes(reason.into())
+}
+
+fn parse_numbers<T: FromStr>(name: &str, text: &str) -> Result<Vec<T>> {
+    text.split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|v| !v.is_empty())
+        .map(|v| {
+            v.parse()
+                .map_err(|_| invalid(format!("invalid value `{}` in `{}`", v, name)))
+        })
+        .collect()
+}
+
+/// Remove the white space and quotes around a string value.
+fn unquote(text: &str) -> String {
+    let text = text.trim();
+    let text = text.strip_prefix('"').unwrap_or(text);
+    let text = text.strip_suffix('"').unwrap_or(text);
+    text.to_string()
+}
+
+fn join<T: ToString>(values: &[T]) -> String {
+    values
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
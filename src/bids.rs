@@ -0,0 +1,332 @@
+//! This module provides support for the JSON sidecar files of BIDS
+//! datasets, which hold the acquisition metadata of each image (such as
+//! `RepetitionTime` and `SliceTiming`). Requires the `bids` Cargo feature.
+//!
+//! Following the inheritance principle of BIDS, the metadata of an image
+//! is gathered from all JSON files which apply to it, from the dataset's
+//! root directory down to the image's directory. A JSON file applies to an
+//! image if it has the same suffix (e.g. `bold`) and all of its entities
+//! (e.g. `task-rest`) are also in the image's file name. Fields in deeper
+//! files override those in files closer to the root.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use nifti::bids::Sidecar;
+//! use nifti::{InMemNiftiObject, NiftiObject};
+//! # use nifti::Result;
+//!
+//! # fn run() -> Result<()> {
+//! let path = "ds000001/sub-01/func/sub-01_task-rest_bold.nii.gz";
+//! let (obj, sidecar) = InMemNiftiObject::from_file_with_sidecar(path)?;
+//! if let Some(sidecar) = sidecar {
+//!     for inconsistency in sidecar.reconcile(obj.header()) {
+//!         println!("{:?}", inconsistency);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use error::{NiftiError, Result};
+use header::NiftiHeader;
+use serde_json::{self, Map, Value};
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+/// Tolerance for comparing times, in seconds.
+const TIME_TOLERANCE: f64 = 1e-4;
+
+/// A disagreement between a sidecar and a NIfTI header.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Inconsistency {
+    /// `RepetitionTime` does not match `pixdim[4]` (both in seconds).
+    RepetitionTime {
+        /// The repetition time in the sidecar
+        sidecar: f64,
+        /// The repetition time in the header
+        header: f64,
+    },
+    /// `SliceTiming` does not have one entry per slice.
+    SliceCount {
+        /// The number of entries in `SliceTiming`
+        sidecar: usize,
+        /// The number of slices in the header
+        header: usize,
+    },
+    /// The sidecar has `SliceTiming`, but the header does not describe the
+    /// slice timing (the slice axis or `slice_code` is not specified).
+    MissingSliceTiming,
+    /// `SliceTiming` does not match the slice timing described by the
+    /// header (`slice_code`, `slice_duration`, `slice_start` and
+    /// `slice_end`), in seconds, in slice index order.
+    SliceTiming {
+        /// The slice times in the sidecar
+        sidecar: Vec<f64>,
+        /// The slice times in the header
+        header: Vec<Option<f64>>,
+    },
+}
+
+/// The metadata of an image, gathered from its BIDS sidecar files.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Sidecar {
+    fields: Map<String, Value>,
+    sources: Vec<PathBuf>,
+}
+
+impl Sidecar {
+    /// Read a single JSON sidecar file.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::InvalidSidecar` if the file is not a JSON object.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut sidecar = Sidecar::default();
+        sidecar.merge(path.as_ref())?;
+        Ok(sidecar)
+    }
+
+    /// Gather the metadata of an image from all the sidecar files which
+    /// apply to it, up to the dataset's root directory (the first one
+    /// holding a `dataset_description.json` file). If no directory above
+    /// the image is a dataset root, only the sidecar files next to the
+    /// image are used. Returns `None` if no sidecar file applies to the
+    /// image.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::InvalidSidecar` if a sidecar file is not a JSON
+    ///   object.
+    pub fn discover<P: AsRef<Path>>(image: P) -> Result<Option<Self>> {
+        let image = image.as_ref();
+        let (entities, suffix) = match image
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(parse_name)
+        {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+
+        let start = match image.parent() {
+            Some(d) if !d.as_os_str().is_empty() => fs::canonicalize(d)?,
+            _ => fs::canonicalize(".")?,
+        };
+
+        // applicable files in each directory, from the image's up to the root
+        let mut levels = Vec::new();
+        let mut dir = Some(start.as_path());
+        let mut found_root = false;
+        while let Some(d) = dir {
+            let mut files = Vec::new();
+            // directories above the image's may not be readable
+            for entry in fs::read_dir(d).into_iter().flatten() {
+                let path = entry?.path();
+                let applies = path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .filter(|n| n.ends_with(".json"))
+                    .and_then(|n| parse_name(&n[..n.len() - ".json".len()]))
+                    .map(|(e, s)| s == suffix && e.iter().all(|kv| entities.contains(kv)))
+                    .unwrap_or(false);
+                if applies {
+                    files.push(path);
+                }
+            }
+            // the least specific file first
+            files.sort_by_key(|p| (entity_count(p), p.clone()));
+            levels.push(files);
+
+            if d.join("dataset_description.json").is_file() {
+                found_root = true;
+                break;
+            }
+            dir = d.parent();
+        }
+        // outside of a dataset, files in unrelated ancestors do not apply
+        if !found_root {
+            levels.truncate(1);
+        }
+
+        let mut sidecar = Sidecar::default();
+        for path in levels.into_iter().rev().flatten() {
+            sidecar.merge(&path)?;
+        }
+        Ok(if sidecar.sources.is_empty() {
+            None
+        } else {
+            Some(sidecar)
+        })
+    }
+
+    /// Obtain the paths of the files which the metadata was read from, in
+    /// the order in which they were applied.
+    pub fn sources(&self) -> &[PathBuf] {
+        &self.sources
+    }
+
+    /// Obtain all metadata fields.
+    pub fn fields(&self) -> &Map<String, Value> {
+        &self.fields
+    }
+
+    /// Get a metadata field.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.fields.get(key)
+    }
+
+    /// Get the repetition time (`RepetitionTime`), in seconds.
+    pub fn repetition_time(&self) -> Option<f64> {
+        self.get("RepetitionTime").and_then(Value::as_f64)
+    }
+
+    /// Get the acquisition time of each slice (`SliceTiming`), in seconds,
+    /// in the order of `SliceEncodingDirection`.
+    pub fn slice_timing(&self) -> Option<Vec<f64>> {
+        self.get("SliceTiming")?
+            .as_array()?
+            .iter()
+            .map(Value::as_f64)
+            .collect()
+    }
+
+    /// Get the slice encoding direction (`SliceEncodingDirection`), as a
+    /// zero-based axis and whether it is reversed (e.g. `k-`).
+    pub fn slice_encoding_direction(&self) -> Option<(u16, bool)> {
+        let direction = self.get("SliceEncodingDirection")?.as_str()?;
+        let axis = match direction.chars().next()? {
+            'i' => 0,
+            'j' => 1,
+            'k' => 2,
+            _ => return None,
+        };
+        Some((axis, direction.ends_with('-')))
+    }
+
+    /// Compare the sidecar's `RepetitionTime` and `SliceTiming` with the
+    /// header's `pixdim[4]` and slice timing information, reporting the
+    /// fields which disagree. Fields which are absent from the sidecar are
+    /// not compared.
+    pub fn reconcile(&self, header: &NiftiHeader) -> Vec<Inconsistency> {
+        let mut inconsistencies = Vec::new();
+
+        if let (Some(sidecar), Ok(tr)) = (self.repetition_time(), header.time_step_sec()) {
+            if header.dim[0] >= 4 && (sidecar - tr).abs() > TIME_TOLERANCE {
+                inconsistencies.push(Inconsistency::RepetitionTime {
+                    sidecar,
+                    header: tr,
+                });
+            }
+        }
+
+        if let Some(times) = self.slice_times_by_index() {
+            let header_times = header
+                .slice_info()
+                .and_then(|info| Ok((info.num_slices, header.slice_times()?)));
+            match header_times {
+                Ok((n, _)) if times.len() != n as usize => {
+                    inconsistencies.push(Inconsistency::SliceCount {
+                        sidecar: times.len(),
+                        header: n as usize,
+                    });
+                }
+                Ok((_, header_times)) => {
+                    let matches = times.iter().zip(&header_times).all(|(s, h)| match *h {
+                        Some(h) => (s - h).abs() <= TIME_TOLERANCE,
+                        None => false,
+                    });
+                    if !matches {
+                        inconsistencies.push(Inconsistency::SliceTiming {
+                            sidecar: times,
+                            header: header_times,
+                        });
+                    }
+                }
+                Err(_) => inconsistencies.push(Inconsistency::MissingSliceTiming),
+            }
+        }
+
+        inconsistencies
+    }
+
+    /// Update the header with the sidecar's `RepetitionTime` (into
+    /// `pixdim[4]`, in the header's time unit) and `SliceTiming` (into the
+    /// slice timing fields, see `NiftiHeader::set_slice_times`). If the
+    /// header does not specify the slice axis, it is taken from
+    /// `SliceEncodingDirection` (the third axis by default).
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::IncompatibleUnit` if the time unit is spectral.
+    /// - See `NiftiHeader::set_slice_times`.
+    pub fn apply(&self, header: &mut NiftiHeader) -> Result<()> {
+        if let Some(tr) = self.repetition_time() {
+            let unit = header.xyzt_to_time()?;
+            let factor = unit.seconds().ok_or(NiftiError::IncompatibleUnit(unit))?;
+            header.pixdim[4] = (tr / factor) as f32;
+        }
+        if self.slice_timing().is_some() {
+            if header.slice_dim().is_none() {
+                let axis = self.slice_encoding_direction().map(|d| d.0).unwrap_or(2);
+                header.set_slice_dim(Some(axis))?;
+            }
+            if let Some(times) = self.slice_times_by_index() {
+                header.set_slice_times(&times)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The slice times in slice index order.
+    fn slice_times_by_index(&self) -> Option<Vec<f64>> {
+        let mut times = self.slice_timing()?;
+        if let Some((_, true)) = self.slice_encoding_direction() {
+            times.reverse();
+        }
+        Some(times)
+    }
+
+    fn merge(&mut self, path: &Path) -> Result<()> {
+        let invalid =
+            |reason: String| NiftiError::InvalidSidecar(format!("{}: {}", path.display(), reason));
+        let value: Value = serde_json::from_reader(BufReader::new(File::open(path)?))
+            .map_err(|e| invalid(e.to_string()))?;
+        match value {
+            Value::Object(fields) => self.fields.extend(fields),
+            _ => return Err(invalid("not a JSON object".to_string())),
+        }
+        self.sources.push(path.to_path_buf());
+        Ok(())
+    }
+}
+
+/// Split a BIDS file name (without the extension) into its entities
+/// (key-value pairs) and its suffix.
+fn parse_name(name: &str) -> Option<(Vec<(&str, &str)>, &str)> {
+    let stem = name.split('.').next()?;
+    let mut parts: Vec<&str> = stem.split('_').collect();
+    let suffix = parts.pop()?;
+    if suffix.is_empty() || suffix.contains('-') {
+        return None;
+    }
+    let entities = parts
+        .into_iter()
+        .map(|p| {
+            let mut kv = p.splitn(2, '-');
+            match (kv.next(), kv.next()) {
+                (Some(k), Some(v)) if !k.is_empty() => Some((k, v)),
+                _ => None,
+            }
+        })
+        .collect::<Option<_>>()?;
+    Some((entities, suffix))
+}
+
+fn entity_count(path: &Path) -> usize {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .map(|n| n.matches('_').count())
+        .unwrap_or(0)
+}
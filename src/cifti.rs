@@ -0,0 +1,817 @@
+//! This module provides support for CIFTI-2 files, such as the HCP
+//! grayordinate files (`.dtseries.nii`, `.dscalar.nii`, `.dlabel.nii`,
+//! `.ptseries.nii`, ...). Requires the `cifti` Cargo feature.
+//!
+//! A CIFTI-2 file is a NIfTI-2 file holding a data matrix along dimensions
+//! 5 and above (`dim[5]` for the first CIFTI dimension, `dim[6]` for the
+//! second one, ...), with an XML document in an extension with the CIFTI
+//! code (32). The document describes what each index along each dimension
+//! stands for: a grayordinate (a surface vertex or a voxel), a parcel, a
+//! time point, or a named map.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use nifti::cifti::{CiftiObject, Grayordinate};
+//! # use nifti::Result;
+//!
+//! # fn run() -> Result<()> {
+//! let obj = CiftiObject::from_file("rfMRI_REST1_LR.dtseries.nii")?;
+//! let models = obj.cifti().map(1).expect("missing brain models");
+//! match models.grayordinate(0) {
+//!     Some(Grayordinate::Vertex { structure, vertex }) => {
+//!         println!("{} vertex {}", structure, vertex)
+//!     }
+//!     Some(Grayordinate::Voxel { structure, ijk }) => println!("{} voxel {:?}", structure, ijk),
+//!     None => {}
+//! }
+//! let first_frame = obj.slice(0, 0)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use affine::Affine;
+use byteorder::{BigEndian, LittleEndian};
+use dom::Element;
+use error::{NiftiError, Result};
+use extension::{Extender, ExtensionSequence};
+use label::{Label, LabelTable};
+use num_traits::FromPrimitive;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+use std::str::FromStr;
+use typedef::NiftiType;
+use util::{decompress, Endianness};
+
+/// Size of the NIfTI-2 header, in bytes.
+const NIFTI2_HEADER_SIZE: usize = 540;
+/// Magic code of single-file NIfTI-2 objects.
+const MAGIC_CODE_NIP2: &[u8; 8] = b"n+2\0\r\n\x1a\n";
+
+/// The type of a brain model.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum ModelType {
+    /// A set of surface vertices (`CIFTI_MODEL_TYPE_SURFACE`)
+    Surface,
+    /// A set of voxels (`CIFTI_MODEL_TYPE_VOXELS`)
+    Voxels,
+}
+
+/// The unit of a series dimension.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum SeriesUnit {
+    /// Seconds (`SECOND`)
+    Second,
+    /// Hertz (`HERTZ`)
+    Hertz,
+    /// Meters (`METER`)
+    Meter,
+    /// Radians (`RADIAN`)
+    Radian,
+}
+
+/// The voxel grid of the volume-based brain models or parcels.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CiftiVolume {
+    /// The dimensions of the voxel grid
+    pub dimensions: [usize; 3],
+    /// The transformation from voxel indices to world coordinates, in
+    /// millimeters
+    pub transform: Affine,
+}
+
+/// A set of grayordinates of a single brain structure.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BrainModel {
+    /// The index of the first grayordinate of the model along the dimension
+    pub index_offset: usize,
+    /// The number of grayordinates in the model
+    pub index_count: usize,
+    /// Whether the model holds vertices or voxels
+    pub model_type: ModelType,
+    /// The brain structure, such as `CIFTI_STRUCTURE_CORTEX_LEFT`
+    pub brain_structure: String,
+    /// The number of vertices of the surface, for surface models
+    pub surface_number_of_vertices: Option<usize>,
+    /// The surface vertex of each grayordinate, for surface models
+    pub vertex_indices: Vec<usize>,
+    /// The voxel of each grayordinate, for voxel models
+    pub voxel_indices: Vec<[usize; 3]>,
+}
+
+/// A surface which parcels may hold vertices of.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct ParcelSurface {
+    /// The brain structure of the surface
+    pub brain_structure: String,
+    /// The number of vertices of the surface
+    pub number_of_vertices: usize,
+}
+
+/// A parcel: a named set of surface vertices and voxels.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Parcel {
+    /// The parcel's name
+    pub name: String,
+    /// The vertices of the parcel, as pairs of a brain structure and the
+    /// vertex indices in its surface
+    pub vertices: Vec<(String, Vec<usize>)>,
+    /// The voxels of the parcel
+    pub voxel_indices: Vec<[usize; 3]>,
+}
+
+/// A series of regularly sampled points, such as time points.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Series {
+    /// The number of points in the series
+    pub number_of_points: usize,
+    /// The power of ten by which the start and step are multiplied
+    pub exponent: i32,
+    /// The value of the first point
+    pub start: f64,
+    /// The distance between consecutive points
+    pub step: f64,
+    /// The unit of the points
+    pub unit: SeriesUnit,
+}
+
+impl Series {
+    /// Obtain the value of the point at the given index, in the series'
+    /// unit (with the exponent applied).
+    pub fn value(&self, index: usize) -> f64 {
+        (self.start + self.step * index as f64) * 10f64.powi(self.exponent)
+    }
+}
+
+/// A named map, such as a scalar map or a label map.
+#[derive(Debug, PartialEq, Clone)]
+pub struct NamedMap {
+    /// The map's name
+    pub name: String,
+    /// The map's metadata, as name-value pairs
+    pub metadata: Vec<(String, String)>,
+    /// The label table, for label maps
+    pub label_table: Option<LabelTable>,
+}
+
+/// What the indices along a dimension stand for.
+#[derive(Debug, PartialEq, Clone)]
+pub enum IndicesMap {
+    /// Grayordinates (`CIFTI_INDEX_TYPE_BRAIN_MODELS`)
+    BrainModels {
+        /// The voxel grid of the voxel models, if any
+        volume: Option<CiftiVolume>,
+        /// The brain models, by increasing index offset
+        models: Vec<BrainModel>,
+    },
+    /// Parcels (`CIFTI_INDEX_TYPE_PARCELS`)
+    Parcels {
+        /// The voxel grid of the parcels' voxels, if any
+        volume: Option<CiftiVolume>,
+        /// The surfaces of the parcels' vertices
+        surfaces: Vec<ParcelSurface>,
+        /// The parcels
+        parcels: Vec<Parcel>,
+    },
+    /// A series of points (`CIFTI_INDEX_TYPE_SERIES`)
+    Series(Series),
+    /// Scalar maps (`CIFTI_INDEX_TYPE_SCALARS`)
+    Scalars(Vec<NamedMap>),
+    /// Label maps (`CIFTI_INDEX_TYPE_LABELS`)
+    Labels(Vec<NamedMap>),
+}
+
+/// The entity found at an index of a brain models dimension.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Grayordinate<'a> {
+    /// A surface vertex
+    Vertex {
+        /// The brain structure of the surface
+        structure: &'a str,
+        /// The vertex index in the surface
+        vertex: usize,
+    },
+    /// A voxel
+    Voxel {
+        /// The brain structure of the voxel
+        structure: &'a str,
+        /// The voxel indices in the volume
+        ijk: [usize; 3],
+    },
+}
+
+/// The description of one or more dimensions of the data matrix.
+#[derive(Debug, PartialEq, Clone)]
+pub struct MatrixIndicesMap {
+    /// The (zero-based) CIFTI dimensions which this map applies to
+    pub applies_to: Vec<usize>,
+    /// What the indices along these dimensions stand for
+    pub map: IndicesMap,
+}
+
+impl MatrixIndicesMap {
+    /// Obtain the number of indices described by the map.
+    pub fn len(&self) -> usize {
+        match self.map {
+            IndicesMap::BrainModels { ref models, .. } => models
+                .iter()
+                .map(|m| m.index_offset + m.index_count)
+                .max()
+                .unwrap_or(0),
+            IndicesMap::Parcels { ref parcels, .. } => parcels.len(),
+            IndicesMap::Series(ref series) => series.number_of_points,
+            IndicesMap::Scalars(ref maps) | IndicesMap::Labels(ref maps) => maps.len(),
+        }
+    }
+
+    /// Whether the map describes no indices.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Obtain the grayordinate at the given index of a brain models map.
+    /// Returns `None` if the index is out of bounds, or if this is not a
+    /// brain models map.
+    pub fn grayordinate(&self, index: usize) -> Option<Grayordinate<'_>> {
+        let models = match self.map {
+            IndicesMap::BrainModels { ref models, .. } => models,
+            _ => return None,
+        };
+        let model = models
+            .iter()
+            .find(|m| index >= m.index_offset && index < m.index_offset + m.index_count)?;
+        let i = index - model.index_offset;
+        let structure = model.brain_structure.as_str();
+        match model.model_type {
+            ModelType::Surface => model
+                .vertex_indices
+                .get(i)
+                .map(|&vertex| Grayordinate::Vertex { structure, vertex }),
+            ModelType::Voxels => model
+                .voxel_indices
+                .get(i)
+                .map(|&ijk| Grayordinate::Voxel { structure, ijk }),
+        }
+    }
+
+    /// Find the index of the given surface vertex in a brain models map.
+    pub fn find_vertex(&self, structure: &str, vertex: usize) -> Option<usize> {
+        self.brain_models()
+            .filter(|m| m.model_type == ModelType::Surface && m.brain_structure == structure)
+            .filter_map(|m| {
+                m.vertex_indices
+                    .iter()
+                    .position(|&v| v == vertex)
+                    .map(|i| m.index_offset + i)
+            })
+            .next()
+    }
+
+    /// Find the index of the given voxel in a brain models map.
+    pub fn find_voxel(&self, ijk: [usize; 3]) -> Option<usize> {
+        self.brain_models()
+            .filter(|m| m.model_type == ModelType::Voxels)
+            .filter_map(|m| {
+                m.voxel_indices
+                    .iter()
+                    .position(|&v| v == ijk)
+                    .map(|i| m.index_offset + i)
+            })
+            .next()
+    }
+
+    /// Obtain the parcel at the given index of a parcels map.
+    pub fn parcel(&self, index: usize) -> Option<&Parcel> {
+        match self.map {
+            IndicesMap::Parcels { ref parcels, .. } => parcels.get(index),
+            _ => None,
+        }
+    }
+
+    /// Obtain the named map at the given index of a scalars or labels map.
+    pub fn named_map(&self, index: usize) -> Option<&NamedMap> {
+        match self.map {
+            IndicesMap::Scalars(ref maps) | IndicesMap::Labels(ref maps) => maps.get(index),
+            _ => None,
+        }
+    }
+
+    fn brain_models(&self) -> ::std::slice::Iter<'_, BrainModel> {
+        match self.map {
+            IndicesMap::BrainModels { ref models, .. } => models.iter(),
+            _ => [].iter(),
+        }
+    }
+}
+
+/// The contents of a CIFTI-2 XML document.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Cifti {
+    /// The CIFTI version, usually `"2"`
+    pub version: String,
+    /// The matrix' metadata, as name-value pairs
+    pub metadata: Vec<(String, String)>,
+    /// The descriptions of the matrix' dimensions
+    pub maps: Vec<MatrixIndicesMap>,
+}
+
+impl Cifti {
+    /// Parse a CIFTI-2 XML document.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::InvalidCifti` if the document is not valid XML, or if
+    ///   it does not follow the CIFTI-2 schema.
+    pub fn from_xml(xml: &str) -> Result<Self> {
+        let root = Element::parse(xml.trim_end_matches('\0').as_bytes())
+            .map_err(|e| invalid(e.to_string()))?;
+        if root.name != "CIFTI" {
+            return Err(invalid(format!("unexpected root element `{}`", root.name)));
+        }
+        let matrix = root
+            .child("Matrix")
+            .ok_or_else(|| invalid("missing element `Matrix`"))?;
+        let maps = matrix
+            .children_named("MatrixIndicesMap")
+            .map(parse_indices_map)
+            .collect::<Result<_>>()?;
+        Ok(Cifti {
+            version: root.attr("Version").unwrap_or_default().to_string(),
+            metadata: parse_metadata(matrix),
+            maps,
+        })
+    }
+
+    /// Parse the CIFTI-2 XML document held by the first extension with
+    /// the CIFTI code, if any.
+    pub fn from_extensions(extensions: &ExtensionSequence) -> Option<Result<Self>> {
+        extensions.cifti_xml().map(|xml| Cifti::from_xml(&xml))
+    }
+
+    /// Obtain the description of the given (zero-based) CIFTI dimension.
+    pub fn map(&self, dimension: usize) -> Option<&MatrixIndicesMap> {
+        self.maps.iter().find(|m| m.applies_to.contains(&dimension))
+    }
+}
+
+/// A CIFTI-2 file fully contained in memory: the XML document and the data
+/// matrix. The matrix has one dimension per CIFTI dimension (`dim[5]`
+/// onwards), and is kept as stored in the file, with its data type, byte
+/// order and scaling parameters. Values are scaled and converted to `f64`
+/// when fetched.
+///
+/// Only single-file NIfTI-2 objects (`.nii` or `.nii.gz`) are supported.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CiftiObject {
+    cifti: Cifti,
+    extensions: ExtensionSequence,
+    dim: Vec<usize>,
+    datatype: NiftiType,
+    endianness: Endianness,
+    scl_slope: f32,
+    scl_inter: f32,
+    raw_data: Vec<u8>,
+}
+
+impl CiftiObject {
+    /// Read a CIFTI-2 file. Compressed files are detected from their first
+    /// bytes and automatically decoded, regardless of their extension.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::InvalidFormat` if the file is not a single-file
+    ///   NIfTI-2 object, or if its header is corrupt.
+    /// - `NiftiError::InvalidCifti` if the file has no CIFTI extension, or
+    ///   if its dimensions do not describe a CIFTI matrix.
+    /// - `NiftiError::InvalidCode` if the data type is invalid.
+    /// - `NiftiError::UnsupportedDataType` if values of the data type
+    ///   cannot be read.
+    /// - `NiftiError::UnsupportedCompression` if the file is compressed
+    ///   with a format whose Cargo feature is not enabled.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = BufReader::new(File::open(path)?);
+        CiftiObject::from_stream(decompress(file)?)
+    }
+
+    /// Read a CIFTI-2 object from a stream of data, starting at the NIfTI-2
+    /// header.
+    ///
+    /// # Errors
+    ///
+    /// - See `from_file`.
+    pub fn from_stream<R: Read>(mut source: R) -> Result<Self> {
+        let mut raw_header = [0u8; NIFTI2_HEADER_SIZE];
+        source.read_exact(&mut raw_header)?;
+        let endianness = if Endianness::LE.read_i32(&raw_header[..4])? == NIFTI2_HEADER_SIZE as i32
+        {
+            Endianness::LE
+        } else if Endianness::BE.read_i32(&raw_header[..4])? == NIFTI2_HEADER_SIZE as i32 {
+            Endianness::BE
+        } else {
+            return Err(NiftiError::InvalidFormat);
+        };
+        if &raw_header[4..12] != MAGIC_CODE_NIP2 {
+            return Err(NiftiError::InvalidFormat);
+        }
+
+        let datatype_code = endianness.read_i16(&raw_header[12..14])?;
+        let datatype = NiftiType::from_i16(datatype_code)
+            .ok_or(NiftiError::InvalidCode("datatype", datatype_code))?;
+        let mut dims = [0i64; 8];
+        for (i, d) in dims.iter_mut().enumerate() {
+            *d = endianness.read_i64(&raw_header[16 + 8 * i..])?;
+        }
+        let vox_offset = endianness.read_i64(&raw_header[168..176])?;
+        let scl_slope = endianness.read_f64(&raw_header[176..184])? as f32;
+        let scl_inter = endianness.read_f64(&raw_header[184..192])? as f32;
+
+        let ndim = dims[0];
+        if !(5..=7).contains(&ndim) || dims[1..5].iter().any(|&d| d != 1) {
+            return Err(invalid("dimensions 1 to 4 must be of size 1"));
+        }
+        if dims[5..=ndim as usize].iter().any(|&d| d < 1) {
+            return Err(invalid("invalid matrix dimensions"));
+        }
+        let dim: Vec<usize> = dims[5..=ndim as usize]
+            .iter()
+            .map(|&d| d as usize)
+            .collect();
+        let nbytes = dim
+            .iter()
+            .try_fold(datatype.size_of(), |n, &d| n.checked_mul(d))
+            .ok_or(NiftiError::InvalidFormat)?;
+
+        let extender = Extender::from_stream(&mut source)?;
+        let len = (vox_offset.max(0) as usize).saturating_sub(NIFTI2_HEADER_SIZE + 4);
+        let extensions = match endianness {
+            Endianness::LE => {
+                ExtensionSequence::from_stream::<LittleEndian, _>(extender, &mut source, len)
+            }
+            Endianness::BE => {
+                ExtensionSequence::from_stream::<BigEndian, _>(extender, &mut source, len)
+            }
+        }?;
+        let cifti = Cifti::from_extensions(&extensions)
+            .unwrap_or_else(|| Err(invalid("missing CIFTI extension")))?;
+
+        // skip any padding between the extensions and the data
+        let read =
+            NIFTI2_HEADER_SIZE + 4 + extensions.iter().map(|e| e.size() as usize).sum::<usize>();
+        if vox_offset < read as i64 {
+            return Err(NiftiError::InvalidFormat);
+        }
+        let _ = io::copy(
+            &mut (&mut source).take(vox_offset as u64 - read as u64),
+            &mut io::sink(),
+        )?;
+
+        // read up to the expected size, so that a corrupt header does not
+        // make us allocate more than the file holds
+        let mut raw_data = Vec::new();
+        let _ = source.take(nbytes as u64).read_to_end(&mut raw_data)?;
+        if raw_data.len() != nbytes {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+
+        let obj = CiftiObject {
+            cifti,
+            extensions,
+            dim,
+            datatype,
+            endianness,
+            scl_slope,
+            scl_inter,
+            raw_data,
+        };
+        // the matrix holds at least one value, which fails to be read if
+        // the data type is not supported
+        let _ = obj.value(0)?;
+        Ok(obj)
+    }
+
+    /// Obtain a reference to the CIFTI-2 XML document.
+    pub fn cifti(&self) -> &Cifti {
+        &self.cifti
+    }
+
+    /// Obtain a reference to the object's extensions.
+    pub fn extensions(&self) -> &ExtensionSequence {
+        &self.extensions
+    }
+
+    /// Obtain the dimensions of the data matrix (`dim[5]` onwards).
+    pub fn dim(&self) -> &[usize] {
+        &self.dim
+    }
+
+    /// Obtain the data type of the matrix values.
+    pub fn data_type(&self) -> NiftiType {
+        self.datatype
+    }
+
+    /// Obtain the raw bytes of the data matrix, in column-major order (the
+    /// first CIFTI dimension changing fastest) and in the byte order of the
+    /// file, without scaling.
+    pub fn raw_data(&self) -> &[u8] {
+        &self.raw_data
+    }
+
+    /// Fetch the value at the given matrix indices, one per CIFTI
+    /// dimension. Returns `None` if the indices are out of bounds.
+    pub fn get(&self, indices: &[usize]) -> Option<f64> {
+        if indices.len() != self.dim.len() || indices.iter().zip(&self.dim).any(|(i, d)| i >= d) {
+            return None;
+        }
+        let mut offset = 0;
+        for (i, d) in indices.iter().zip(&self.dim).rev() {
+            offset = offset * d + i;
+        }
+        self.value(offset).ok()
+    }
+
+    /// Fetch all values with the given index along the given dimension,
+    /// in column-major order. For example, `slice(0, t)` on a dense time
+    /// series (time along dimension 0) yields the values of all
+    /// grayordinates at time point `t`.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::AxisOutOfBounds` if the dimension does not exist.
+    /// - `NiftiError::InvalidCifti` if the index is out of bounds.
+    pub fn slice(&self, dimension: usize, index: usize) -> Result<Vec<f64>> {
+        if dimension >= self.dim.len() {
+            return Err(NiftiError::AxisOutOfBounds(dimension as u16));
+        }
+        let size = self.dim[dimension];
+        if index >= size {
+            return Err(invalid(format!("index {} out of bounds", index)));
+        }
+
+        // only visit the values with the given index: `stride` consecutive
+        // values out of every `stride * size`
+        let stride: usize = self.dim[..dimension].iter().product();
+        let outer: usize = self.dim[dimension + 1..].iter().product();
+        let mut values = Vec::with_capacity(stride * outer);
+        for o in 0..outer {
+            let start = (o * size + index) * stride;
+            for offset in start..start + stride {
+                values.push(self.value(offset)?);
+            }
+        }
+        Ok(values)
+    }
+
+    /// Consume the object, returning the XML document and the raw bytes of
+    /// the data matrix (see `raw_data`).
+    pub fn into_parts(self) -> (Cifti, Vec<u8>) {
+        (self.cifti, self.raw_data)
+    }
+
+    /// Read the scaled value at the given position in the matrix.
+    fn value(&self, offset: usize) -> Result<f64> {
+        let size = self.datatype.size_of();
+        let bytes = &self.raw_data[offset * size..(offset + 1) * size];
+        self.datatype
+            .read_primitive_value(bytes, self.endianness, self.scl_slope, self.scl_inter)
+    }
+}
+
+fn invalid<S: Into<String>>(reason: S) -> NiftiError {
+    NiftiError::InvalidCifti(reason.into())
+}
+
+fn attr<'a>(element: &'a Element, name: &str) -> Result<&'a str> {
+    element.attr(name).ok_or_else(|| {
+        invalid(format!(
+            "missing attribute `{}` in element `{}`",
+            name, element.name
+        ))
+    })
+}
+
+fn parse_attr<T: FromStr>(element: &Element, name: &str) -> Result<T> {
+    let value = attr(element, name)?;
+    value.trim().parse().map_err(|_| {
+        invalid(format!(
+            "invalid value `{}` for attribute `{}`",
+            value, name
+        ))
+    })
+}
+
+fn parse_list<T: FromStr>(element: &Element) -> Result<Vec<T>> {
+    element
+        .text
+        .split_whitespace()
+        .map(|v| {
+            v.parse()
+                .map_err(|_| invalid(format!("invalid value `{}` in `{}`", v, element.name)))
+        })
+        .collect()
+}
+
+fn parse_voxels(element: Option<&Element>) -> Result<Vec<[usize; 3]>> {
+    let values: Vec<usize> = match element {
+        Some(e) => parse_list(e)?,
+        None => return Ok(Vec::new()),
+    };
+    if !values.len().is_multiple_of(3) {
+        return Err(invalid("voxel indices must come in triplets"));
+    }
+    Ok(values.chunks(3).map(|v| [v[0], v[1], v[2]]).collect())
+}
+
+fn parse_metadata(element: &Element) -> Vec<(String, String)> {
+    element
+        .child("MetaData")
+        .map(|md| {
+            md.children_named("MD")
+                .map(|e| {
+                    let text = |name| e.child(name).map(|c| c.text.trim().to_string());
+                    (
+                        text("Name").unwrap_or_default(),
+                        text("Value").unwrap_or_default(),
+                    )
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn parse_volume(element: &Element) -> Result<Option<CiftiVolume>> {
+    let volume = match element.child("Volume") {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+    let dims: Vec<usize> = attr(volume, "VolumeDimensions")?
+        .split(',')
+        .map(|d| d.trim().parse())
+        .collect::<::std::result::Result<_, _>>()
+        .map_err(|_| invalid("invalid volume dimensions"))?;
+    if dims.len() != 3 {
+        return Err(invalid("invalid volume dimensions"));
+    }
+    let matrix = volume
+        .child("TransformationMatrixVoxelIndicesIJKtoXYZ")
+        .ok_or_else(|| invalid("missing volume transformation matrix"))?;
+    let exponent: i32 = parse_attr(matrix, "MeterExponent")?;
+    let values: Vec<f64> = parse_list(matrix)?;
+    if values.len() != 16 {
+        return Err(invalid("the transformation matrix must have 16 elements"));
+    }
+    // convert to millimeters
+    let scale = 10f64.powi(exponent + 3);
+    let mut transform = [[0.; 4]; 4];
+    for (r, row) in transform.iter_mut().enumerate() {
+        for (c, v) in row.iter_mut().enumerate() {
+            *v = values[r * 4 + c] * if r < 3 { scale } else { 1. };
+        }
+    }
+    Ok(Some(CiftiVolume {
+        dimensions: [dims[0], dims[1], dims[2]],
+        transform,
+    }))
+}
+
+fn parse_named_map(element: &Element, labels: bool) -> Result<NamedMap> {
+    let name = element
+        .child("MapName")
+        .map(|e| e.text.trim().to_string())
+        .unwrap_or_default();
+    let label_table = if labels {
+        let mut table = LabelTable::new();
+        if let Some(lt) = element.child("LabelTable") {
+            for label in lt.children_named("Label") {
+                let mut color = [0u8; 4];
+                for (c, name) in color.iter_mut().zip(&["Red", "Green", "Blue", "Alpha"]) {
+                    let v: f64 = parse_attr(label, name)?;
+                    *c = (v.clamp(0., 1.) * 255.).round() as u8;
+                }
+                let _ = table.insert(Label {
+                    value: parse_attr(label, "Key")?,
+                    name: label.text.trim().to_string(),
+                    color,
+                });
+            }
+        }
+        Some(table)
+    } else {
+        None
+    };
+    Ok(NamedMap {
+        name,
+        metadata: parse_metadata(element),
+        label_table,
+    })
+}
+
+fn parse_indices_map(element: &Element) -> Result<MatrixIndicesMap> {
+    let applies_to = attr(element, "AppliesToMatrixDimension")?
+        .split(',')
+        .map(|d| d.trim().parse())
+        .collect::<::std::result::Result<_, _>>()
+        .map_err(|_| invalid("invalid matrix dimensions in indices map"))?;
+
+    let map = match attr(element, "IndicesMapToDataType")? {
+        "CIFTI_INDEX_TYPE_BRAIN_MODELS" => {
+            let mut models = element
+                .children_named("BrainModel")
+                .map(parse_brain_model)
+                .collect::<Result<Vec<_>>>()?;
+            models.sort_by_key(|m| m.index_offset);
+            IndicesMap::BrainModels {
+                volume: parse_volume(element)?,
+                models,
+            }
+        }
+        "CIFTI_INDEX_TYPE_PARCELS" => IndicesMap::Parcels {
+            volume: parse_volume(element)?,
+            surfaces: element
+                .children_named("Surface")
+                .map(|s| {
+                    Ok(ParcelSurface {
+                        brain_structure: attr(s, "BrainStructure")?.to_string(),
+                        number_of_vertices: parse_attr(s, "SurfaceNumberOfVertices")?,
+                    })
+                })
+                .collect::<Result<_>>()?,
+            parcels: element
+                .children_named("Parcel")
+                .map(|p| {
+                    Ok(Parcel {
+                        name: attr(p, "Name")?.to_string(),
+                        vertices: p
+                            .children_named("Vertices")
+                            .map(|v| Ok((attr(v, "BrainStructure")?.to_string(), parse_list(v)?)))
+                            .collect::<Result<_>>()?,
+                        voxel_indices: parse_voxels(p.child("VoxelIndicesIJK"))?,
+                    })
+                })
+                .collect::<Result<_>>()?,
+        },
+        "CIFTI_INDEX_TYPE_SERIES" => IndicesMap::Series(Series {
+            number_of_points: parse_attr(element, "NumberOfSeriesPoints")?,
+            exponent: parse_attr(element, "SeriesExponent")?,
+            start: parse_attr(element, "SeriesStart")?,
+            step: parse_attr(element, "SeriesStep")?,
+            unit: match attr(element, "SeriesUnit")? {
+                "SECOND" => SeriesUnit::Second,
+                "HERTZ" => SeriesUnit::Hertz,
+                "METER" => SeriesUnit::Meter,
+                "RADIAN" => SeriesUnit::Radian,
+                u => return Err(invalid(format!("unknown series unit `{}`", u))),
+            },
+        }),
+        "CIFTI_INDEX_TYPE_SCALARS" => IndicesMap::Scalars(
+            element
+                .children_named("NamedMap")
+                .map(|e| parse_named_map(e, false))
+                .collect::<Result<_>>()?,
+        ),
+        "CIFTI_INDEX_TYPE_LABELS" => IndicesMap::Labels(
+            element
+                .children_named("NamedMap")
+                .map(|e| parse_named_map(e, true))
+                .collect::<Result<_>>()?,
+        ),
+        t => return Err(invalid(format!("unknown indices map type `{}`", t))),
+    };
+
+    Ok(MatrixIndicesMap { applies_to, map })
+}
+
+fn parse_brain_model(element: &Element) -> Result<BrainModel> {
+    let model_type = match attr(element, "ModelType")? {
+        "CIFTI_MODEL_TYPE_SURFACE" => ModelType::Surface,
+        "CIFTI_MODEL_TYPE_VOXELS" => ModelType::Voxels,
+        t => return Err(invalid(format!("unknown model type `{}`", t))),
+    };
+    let index_count: usize = parse_attr(element, "IndexCount")?;
+    let (vertex_indices, voxel_indices) = match model_type {
+        ModelType::Surface => {
+            let vertices = match element.child("VertexIndices") {
+                Some(e) => parse_list(e)?,
+                // all vertices of the surface, in order
+                None => (0..index_count).collect(),
+            };
+            (vertices, Vec::new())
+        }
+        ModelType::Voxels => (Vec::new(), parse_voxels(element.child("VoxelIndicesIJK"))?),
+    };
+    if vertex_indices.len() + voxel_indices.len() != index_count {
+        return Err(invalid("the number of indices does not match `IndexCount`"));
+    }
+    Ok(BrainModel {
+        index_offset: parse_attr(element, "IndexOffset")?,
+        index_count,
+        model_type,
+        brain_structure: attr(element, "BrainStructure")?.to_string(),
+        surface_number_of_vertices: match element.attr("SurfaceNumberOfVertices") {
+            Some(_) => Some(parse_attr(element, "SurfaceNumberOfVertices")?),
+            None => None,
+        },
+        vertex_indices,
+        voxel_indices,
+    })
+}
@@ -0,0 +1,452 @@
+//! This module provides a minimal parser for DICOM data sets embedded in
+//! NIfTI extensions with the DICOM code (2), as written by some converters
+//! to keep the original acquisition header next to the image.
+//!
+//! Data sets in explicit VR little endian and implicit VR little endian
+//! transfer syntaxes are supported, with or without the 128-byte preamble
+//! and the `DICM` prefix of DICOM files. Element values are kept as raw
+//! bytes, and can be interpreted as text or numbers according to their
+//! value representation (VR). Accessors are available for a few commonly
+//! used attributes.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use nifti::dicom::DicomDataSet;
+//! use nifti::{InMemNiftiObject, NiftiObject};
+//! # use nifti::Result;
+//!
+//! # fn run() -> Result<()> {
+//! let obj = InMemNiftiObject::from_file("converted.nii.gz")?;
+//! if let Some(dicom) = DicomDataSet::from_extensions(obj.extensions()) {
+//!     let dicom = dicom?;
+//!     println!("TR = {:?} ms, TE = {:?} ms", dicom.repetition_time(), dicom.echo_time());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use byteorder::{ByteOrder, LittleEndian};
+use error::{NiftiError, Result};
+use extension::{Extension, ExtensionSequence};
+use std::collections::btree_map::{BTreeMap, Values};
+use std::fmt;
+use typedef::ExtensionCode;
+
+/// A DICOM attribute tag, made of a group and an element number.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub struct DicomTag(pub u16, pub u16);
+
+impl fmt::Display for DicomTag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({:04X},{:04X})", self.0, self.1)
+    }
+}
+
+/// Series Description (LO)
+pub const SERIES_DESCRIPTION: DicomTag = DicomTag(0x0008, 0x103E);
+/// Repetition Time, in milliseconds (DS)
+pub const REPETITION_TIME: DicomTag = DicomTag(0x0018, 0x0080);
+/// Echo Time, in milliseconds (DS)
+pub const ECHO_TIME: DicomTag = DicomTag(0x0018, 0x0081);
+/// Image Orientation (Patient), as the direction cosines of the first
+/// row and of the first column (DS)
+pub const IMAGE_ORIENTATION_PATIENT: DicomTag = DicomTag(0x0020, 0x0037);
+/// Transfer Syntax UID (UI)
+pub const TRANSFER_SYNTAX_UID: DicomTag = DicomTag(0x0002, 0x0010);
+
+const ITEM: DicomTag = DicomTag(0xFFFE, 0xE000);
+const ITEM_DELIMITATION: DicomTag = DicomTag(0xFFFE, 0xE00D);
+const SEQUENCE_DELIMITATION: DicomTag = DicomTag(0xFFFE, 0xE0DD);
+const UNDEFINED_LENGTH: u32 = 0xFFFF_FFFF;
+const IMPLICIT_VR_LITTLE_ENDIAN: &str = "1.2.840.10008.1.2";
+const EXPLICIT_VR_BIG_ENDIAN: &str = "1.2.840.10008.1.2.2";
+/// The maximum nesting level of sequences.
+const MAX_SEQUENCE_DEPTH: usize = 64;
+
+/// Value representations with a 4-byte length in explicit VR encoding.
+const LONG_VRS: &[&[u8; 2]] = &[
+    b"OB", b"OD", b"OF", b"OL", b"OV", b"OW", b"SQ", b"SV", b"UC", b"UN", b"UR", b"UT", b"UV",
+];
+/// Value representations with a 2-byte length in explicit VR encoding.
+const SHORT_VRS: &[&[u8; 2]] = &[
+    b"AE", b"AS", b"AT", b"CS", b"DA", b"DS", b"DT", b"FD", b"FL", b"IS", b"LO", b"LT", b"PN",
+    b"SH", b"SL", b"SS", b"ST", b"TM", b"UI", b"UL", b"US",
+];
+
+/// The value of a data element.
+#[derive(Debug, PartialEq, Clone)]
+pub enum DicomValue {
+    /// The raw bytes of the value
+    Bytes(Vec<u8>),
+    /// The items of a sequence (`SQ`)
+    Sequence(Vec<DicomDataSet>),
+}
+
+/// A DICOM data element.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DicomElement {
+    /// The attribute tag
+    pub tag: DicomTag,
+    /// The value representation, such as `b"DS"`. In implicit VR data sets,
+    /// this is looked up from a small dictionary of known attributes, being
+    /// `b"UN"` for all others.
+    pub vr: [u8; 2],
+    /// The value
+    pub value: DicomValue,
+}
+
+impl DicomElement {
+    /// Obtain the raw bytes of the value, if it is not a sequence.
+    pub fn bytes(&self) -> Option<&[u8]> {
+        match self.value {
+            DicomValue::Bytes(ref b) => Some(b),
+            DicomValue::Sequence(_) => None,
+        }
+    }
+
+    /// Interpret the value as text, with the padding removed. Multiple
+    /// values remain separated by backslashes.
+    pub fn as_str(&self) -> Option<String> {
+        let bytes = self.bytes()?;
+        let text = String::from_utf8_lossy(bytes);
+        Some(text.trim_end_matches([' ', '\0']).trim_start().to_string())
+    }
+
+    /// Interpret the value as a list of numbers. Decimal and integer
+    /// strings (`DS`, `IS`) are parsed from text, and binary numbers
+    /// (`FD`, `FL`, `SL`, `SS`, `UL`, `US`) are decoded in little endian.
+    /// Returns `None` for other value representations, or if the value is
+    /// not a valid list of numbers.
+    pub fn as_f64s(&self) -> Option<Vec<f64>> {
+        let bytes = self.bytes()?;
+        let decode = |size: usize, f: &dyn Fn(&[u8]) -> f64| -> Option<Vec<f64>> {
+            if bytes.len() % size != 0 {
+                return None;
+            }
+            Some(bytes.chunks(size).map(f).collect())
+        };
+        match &self.vr {
+            b"DS" | b"IS" => self
+                .as_str()?
+                .split('\\')
+                .map(|v| v.trim().parse().ok())
+                .collect(),
+            b"FD" => decode(8, &LittleEndian::read_f64),
+            b"FL" => decode(4, &|b| f64::from(LittleEndian::read_f32(b))),
+            b"SL" => decode(4, &|b| f64::from(LittleEndian::read_i32(b))),
+            b"UL" => decode(4, &|b| f64::from(LittleEndian::read_u32(b))),
+            b"SS" => decode(2, &|b| f64::from(LittleEndian::read_i16(b))),
+            b"US" => decode(2, &|b| f64::from(LittleEndian::read_u16(b))),
+            _ => None,
+        }
+    }
+
+    /// Interpret the value as a single number (the first one, if the value
+    /// holds several).
+    pub fn as_f64(&self) -> Option<f64> {
+        self.as_f64s().and_then(|v| v.first().cloned())
+    }
+}
+
+/// A DICOM data set: a collection of data elements, indexed by tag.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct DicomDataSet {
+    elements: BTreeMap<DicomTag, DicomElement>,
+}
+
+impl DicomDataSet {
+    /// Parse a DICOM data set from its encoded bytes, in explicit or
+    /// implicit VR little endian. The transfer syntax is taken from the
+    /// file meta information (group `0002`) if present, or detected from
+    /// the first element otherwise.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::InvalidDicom` if the data is truncated or malformed,
+    ///   or if it is encoded in big endian.
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let data = if data.len() >= 132 && &data[128..132] == b"DICM" {
+            &data[132..]
+        } else if data.starts_with(b"DICM") {
+            &data[4..]
+        } else {
+            data
+        };
+        let mut parser = Parser {
+            data,
+            pos: 0,
+            explicit: None,
+            depth: 0,
+        };
+        parser.data_set(data.len())
+    }
+
+    /// Parse the data set held by an extension.
+    ///
+    /// # Errors
+    ///
+    /// - See `from_bytes`.
+    pub fn from_extension(extension: &Extension) -> Result<Self> {
+        DicomDataSet::from_bytes(extension.data())
+    }
+
+    /// Parse the data set held by the first DICOM extension, if any.
+    pub fn from_extensions(extensions: &ExtensionSequence) -> Option<Result<Self>> {
+        extensions
+            .find(ExtensionCode::Dicom)
+            .map(DicomDataSet::from_extension)
+    }
+
+    /// Get the element with the given tag.
+    pub fn get(&self, tag: DicomTag) -> Option<&DicomElement> {
+        self.elements.get(&tag)
+    }
+
+    /// Obtain an iterator to the elements, by increasing tag.
+    pub fn iter(&self) -> Values<'_, DicomTag, DicomElement> {
+        self.elements.values()
+    }
+
+    /// Whether the data set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    /// Obtain the number of elements in the data set.
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    /// Get the value of an element as text (see `DicomElement::as_str`).
+    pub fn string(&self, tag: DicomTag) -> Option<String> {
+        self.get(tag).and_then(DicomElement::as_str)
+    }
+
+    /// Get the value of an element as a single number (see
+    /// `DicomElement::as_f64`).
+    pub fn number(&self, tag: DicomTag) -> Option<f64> {
+        self.get(tag).and_then(DicomElement::as_f64)
+    }
+
+    /// Get the echo time, in milliseconds.
+    pub fn echo_time(&self) -> Option<f64> {
+        self.number(ECHO_TIME)
+    }
+
+    /// Get the repetition time, in milliseconds.
+    pub fn repetition_time(&self) -> Option<f64> {
+        self.number(REPETITION_TIME)
+    }
+
+    /// Get the direction cosines of the first row and of the first column
+    /// of the image, in the patient's LPS+ coordinate system.
+    pub fn image_orientation_patient(&self) -> Option<[f64; 6]> {
+        let v = self
+            .get(IMAGE_ORIENTATION_PATIENT)
+            .and_then(DicomElement::as_f64s)?;
+        if v.len() != 6 {
+            return None;
+        }
+        let mut orientation = [0.; 6];
+        orientation.copy_from_slice(&v);
+        Some(orientation)
+    }
+
+    /// Get the series description.
+    pub fn series_description(&self) -> Option<String> {
+        self.string(SERIES_DESCRIPTION)
+    }
+}
+
+/// The VR of the known attributes, for implicit VR data sets.
+fn implicit_vr(tag: DicomTag) -> [u8; 2] {
+    let vr = match tag {
+        SERIES_DESCRIPTION => b"LO",
+        REPETITION_TIME | ECHO_TIME | IMAGE_ORIENTATION_PATIENT => b"DS",
+        DicomTag(0x0018, 0x0050) | DicomTag(0x0018, 0x0088) | DicomTag(0x0018, 0x1314) => b"DS",
+        DicomTag(0x0020, 0x0032) | DicomTag(0x0028, 0x0030) => b"DS",
+        DicomTag(0x0008, 0x0060) | DicomTag(0x0018, 0x0020) => b"CS",
+        DicomTag(0x0008, 0x0070) | DicomTag(0x0018, 0x1030) => b"LO",
+        DicomTag(0x0018, 0x0087) => b"DS",
+        DicomTag(0x0020, 0x0011) | DicomTag(0x0020, 0x0013) => b"IS",
+        DicomTag(0x0028, 0x0010) | DicomTag(0x0028, 0x0011) => b"US",
+        DicomTag(_, 0x0000) => b"UL",
+        _ => b"UN",
+    };
+    *vr
+}
+
+fn invalid<S: Into<String>>(reason: S) -> NiftiError {
+    NiftiError::InvalidDicom(reason.into())
+}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+    /// Whether the data set (after the file meta information) is in
+    /// explicit VR, if already known
+    explicit: Option<bool>,
+    /// The number of sequences containing the current position
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if len > self.data.len() - self.pos {
+            return Err(invalid("unexpected end of data"));
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        self.take(2).map(LittleEndian::read_u16)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        self.take(4).map(LittleEndian::read_u32)
+    }
+
+    fn tag(&mut self) -> Result<DicomTag> {
+        Ok(DicomTag(self.u16()?, self.u16()?))
+    }
+
+    /// Read data elements until `end`, or until an item delimitation.
+    fn data_set(&mut self, end: usize) -> Result<DicomDataSet> {
+        let mut data_set = DicomDataSet::default();
+        while self.pos < end {
+            // the extension's data may be padded with zeros, which would
+            // otherwise read as tag (0000,0000)
+            let next_tag = &self.data[self.pos..end.min(self.pos + 4)];
+            if next_tag.iter().all(|&b| b == 0) {
+                if self.data[self.pos..end].iter().any(|&b| b != 0) {
+                    return Err(invalid("unexpected data after padding"));
+                }
+                self.pos = end;
+                break;
+            }
+            let tag = self.tag()?;
+            if tag == ITEM_DELIMITATION {
+                let _ = self.u32()?;
+                break;
+            }
+            let element = self.element(tag, &data_set)?;
+            let _ = data_set.elements.insert(tag, element);
+        }
+        Ok(data_set)
+    }
+
+    fn element(&mut self, tag: DicomTag, data_set: &DicomDataSet) -> Result<DicomElement> {
+        let explicit = if tag.0 == 0x0002 {
+            true
+        } else {
+            match self.explicit {
+                Some(explicit) => explicit,
+                None => {
+                    let explicit = match data_set.string(TRANSFER_SYNTAX_UID) {
+                        Some(ref uid) if uid == EXPLICIT_VR_BIG_ENDIAN => {
+                            return Err(invalid("big endian data sets are not supported"))
+                        }
+                        Some(ref uid) if uid == IMPLICIT_VR_LITTLE_ENDIAN => false,
+                        Some(_) => true,
+                        None => self.looks_explicit(),
+                    };
+                    self.explicit = Some(explicit);
+                    explicit
+                }
+            }
+        };
+
+        let (vr, len) = if explicit {
+            let vr = self.take(2)?;
+            let vr = [vr[0], vr[1]];
+            if LONG_VRS.contains(&&vr) {
+                let _ = self.take(2)?;
+                (vr, self.u32()?)
+            } else if SHORT_VRS.contains(&&vr) {
+                (vr, u32::from(self.u16()?))
+            } else {
+                return Err(invalid(format!("unknown VR in element {}", tag)));
+            }
+        } else {
+            (implicit_vr(tag), self.u32()?)
+        };
+
+        let value = if &vr == b"SQ" || (len == UNDEFINED_LENGTH && &vr == b"UN") {
+            DicomValue::Sequence(self.sequence(len)?)
+        } else if len == UNDEFINED_LENGTH {
+            DicomValue::Bytes(self.fragments()?)
+        } else {
+            DicomValue::Bytes(self.take(len as usize)?.to_vec())
+        };
+        Ok(DicomElement { tag, vr, value })
+    }
+
+    /// Whether the element at the current position (past its tag) seems to
+    /// be encoded in explicit VR.
+    fn looks_explicit(&self) -> bool {
+        self.data
+            .get(self.pos..self.pos + 2)
+            .map(|vr| LONG_VRS.iter().chain(SHORT_VRS).any(|v| &v[..] == vr))
+            .unwrap_or(false)
+    }
+
+    fn sequence(&mut self, len: u32) -> Result<Vec<DicomDataSet>> {
+        if self.depth == MAX_SEQUENCE_DEPTH {
+            return Err(invalid("too many nested sequences"));
+        }
+        self.depth += 1;
+        let items = self.items(len);
+        self.depth -= 1;
+        items
+    }
+
+    fn items(&mut self, len: u32) -> Result<Vec<DicomDataSet>> {
+        let end = if len == UNDEFINED_LENGTH {
+            self.data.len()
+        } else {
+            self.pos + len as usize
+        };
+        let mut items = Vec::new();
+        while self.pos < end {
+            let tag = self.tag()?;
+            let item_len = self.u32()?;
+            if tag == SEQUENCE_DELIMITATION {
+                break;
+            }
+            if tag != ITEM {
+                return Err(invalid(format!("unexpected element {} in sequence", tag)));
+            }
+            if item_len == UNDEFINED_LENGTH {
+                items.push(self.data_set(self.data.len())?);
+            } else {
+                let item_end = self.pos + item_len as usize;
+                if item_end > self.data.len() {
+                    return Err(invalid("unexpected end of data"));
+                }
+                items.push(self.data_set(item_end)?);
+                self.pos = item_end;
+            }
+        }
+        Ok(items)
+    }
+
+    /// Read the fragments of an encapsulated value of undefined length,
+    /// concatenating them.
+    fn fragments(&mut self) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        loop {
+            let tag = self.tag()?;
+            let len = self.u32()?;
+            if tag == SEQUENCE_DELIMITATION {
+                return Ok(bytes);
+            }
+            if tag != ITEM {
+                return Err(invalid(format!("unexpected element {} in fragments", tag)));
+            }
+            bytes.extend_from_slice(self.take(len as usize)?);
+        }
+    }
+}
@@ -0,0 +1,71 @@
+//! Private module with a minimal XML element tree, for the formats which
+//! embed XML documents (such as CIFTI-2).
+use std::io::Read;
+use xml::reader::{EventReader, XmlEvent};
+
+pub use xml::reader::Error as XmlError;
+
+/// An XML element, with its attributes, child elements and text content.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Element>,
+    pub text: String,
+}
+
+impl Element {
+    /// Parse an XML document, returning its root element.
+    pub fn parse<R: Read>(source: R) -> Result<Element, XmlError> {
+        let mut stack: Vec<Element> = vec![Element::default()];
+        for event in EventReader::new(source) {
+            match event? {
+                XmlEvent::StartElement {
+                    name, attributes, ..
+                } => stack.push(Element {
+                    name: name.local_name,
+                    attributes: attributes
+                        .into_iter()
+                        .map(|a| (a.name.local_name, a.value))
+                        .collect(),
+                    children: Vec::new(),
+                    text: String::new(),
+                }),
+                XmlEvent::EndElement { .. } => {
+                    let element = stack.pop().expect("unbalanced XML events");
+                    stack
+                        .last_mut()
+                        .expect("unbalanced XML events")
+                        .children
+                        .push(element);
+                }
+                XmlEvent::Characters(text) | XmlEvent::CData(text) => {
+                    if let Some(e) = stack.last_mut() {
+                        e.text.push_str(&text);
+                    }
+                }
+                _ => {}
+            }
+        }
+        let mut document = stack.pop().expect("unbalanced XML events");
+        Ok(document.children.pop().unwrap_or_default())
+    }
+
+    /// Get the value of an attribute.
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Get the first child element with the given name.
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|e| e.name == name)
+    }
+
+    /// Iterate over the child elements with the given name.
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |e| e.name == name)
+    }
+}
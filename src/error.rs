@@ -1,7 +1,7 @@
 //! Types for error handling go here.
 
 use std::io::Error as IOError;
-use typedef::NiftiType;
+use typedef::{Intent, NiftiType, Unit};
 
 quick_error! {
     /// Error type for all error variants originated by this crate.
@@ -56,6 +56,108 @@ quick_error! {
             description("invalid code")
             display("invalid code `{}` for {}", code, typename)
         }
+        /// The operation cannot be performed with the given measurement unit.
+        IncompatibleUnit(unit: Unit) {
+            description("Incompatible unit")
+            display("incompatible unit {:?}", unit)
+        }
+        /// The header does not specify the slice acquisition axis.
+        MissingSliceInfo {
+            description("Slice acquisition axis not specified")
+        }
+        /// The given slice acquisition times do not follow a known slice order.
+        UnsupportedSliceTiming {
+            description("Slice timing does not match a known slice order")
+        }
+        /// This intent is not supported by the requested operation.
+        UnsupportedIntent(intent: Intent) {
+            description("Unsupported intent")
+            display("unsupported intent {:?}", intent)
+        }
+        /// The intent parameters (`intent_p1`, `intent_p2` and `intent_p3`)
+        /// are not valid for the given intent.
+        InvalidIntentParameters(intent: Intent) {
+            description("Invalid intent parameters")
+            display("invalid intent parameters for {:?}", intent)
+        }
+        /// The affine transformation of a volume is degenerate (e.g. it cannot
+        /// be inverted).
+        InvalidAffine {
+            description("Invalid affine transformation")
+        }
+        /// A displacement field does not hold 3 components per voxel, along
+        /// `dim[4]` (`[x, y, z, 3]`) or `dim[5]` (`[x, y, z, 1, 3]`).
+        InvalidDisplacementField(dim: Vec<u16>) {
+            description("Invalid displacement field")
+            display("invalid displacement field of shape {:?}, 3 components are required \
+                     along dim[4] or dim[5]", dim)
+        }
+        /// A label table could not be parsed, at the given (one-based) line.
+        InvalidLabelTable(line: usize) {
+            description("Invalid label table")
+            display("invalid label table entry at line {}", line)
+        }
+        /// No label with the given name exists in the label table.
+        UnknownLabel(name: String) {
+            description("Unknown label")
+            display("unknown label `{}`", name)
+        }
+        /// A CIFTI-2 file or its XML document is not valid.
+        InvalidCifti(reason: String) {
+            description("Invalid CIFTI-2 file")
+            display("invalid CIFTI-2 file: {}", reason)
+        }
+        /// The attributes of an AFNI extension could not be parsed.
+        InvalidAfniAttributes(reason: String) {
+            description("Invalid AFNI attributes")
+            display("invalid AFNI attributes: {}", reason)
+        }
+        /// An embedded DICOM data set could not be parsed.
+        InvalidDicom(reason: String) {
+            description("Invalid DICOM data set")
+            display("invalid DICOM data set: {}", reason)
+        }
+        /// A BIDS sidecar file could not be parsed.
+        InvalidSidecar(reason: String) {
+            description("Invalid BIDS sidecar")
+            display("invalid BIDS sidecar {}", reason)
+        }
+        /// A FreeSurfer MGH file is not valid, or a volume cannot be
+        /// stored in one.
+        InvalidMgh(reason: String) {
+            description("Invalid MGH file")
+            display("invalid MGH file: {}", reason)
+        }
+        /// A FreeSurfer MGH volume has a dimension larger than 65535, which
+        /// does not fit in a volume, such as the surface overlays of the
+        /// `fsaverage` subject (163842 vertices along the first dimension).
+        UnsupportedMghDimensions(dim: [usize; 4]) {
+            description("Unsupported MGH dimensions")
+            display("unsupported MGH dimensions {:?}: each dimension must not be larger \
+                     than 65535", dim)
+        }
+        /// A NRRD file is not valid, or uses unsupported features.
+        InvalidNrrd(reason: String) {
+            description("Invalid NRRD file")
+            display("invalid NRRD file: {}", reason)
+        }
+        /// A NumPy ".npy" file is not valid, or holds an array which
+        /// cannot be read as a volume.
+        InvalidNpy(reason: String) {
+            description("Invalid NumPy file")
+            display("invalid NumPy file: {}", reason)
+        }
+        /// A GIFTI document is not valid, or uses unsupported features.
+        InvalidGifti(reason: String) {
+            description("Invalid GIFTI file")
+            display("invalid GIFTI file: {}", reason)
+        }
+        /// The data is compressed with a format whose Cargo feature is not
+        /// enabled.
+        UnsupportedCompression(feature: &'static str) {
+            description("Unsupported compression format")
+            display("unsupported compression format, requires the `{}` feature", feature)
+        }
     }
 }
 
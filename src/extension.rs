@@ -4,9 +4,12 @@
 //! end of the NIFTI-1 header, with the first byte set to something
 //! other than 0.
 
+use std::borrow::Cow;
 use std::io::{ErrorKind as IoErrorKind, Read};
 use error::{NiftiError, Result};
 use byteorder::{ByteOrder, ReadBytesExt};
+use num_traits::FromPrimitive;
+use typedef::ExtensionCode;
 
 /// Data type for the extender code.
 #[derive(Debug, Default, PartialEq, Clone, Copy)]
@@ -84,6 +87,16 @@ impl Extension {
         }
     }
 
+    /// Create an extension out of its code and data. The data is padded
+    /// with zeros so that the full size of the extension (`esize`, which
+    /// includes the 8 bytes of `esize` and `ecode`) is a multiple of 16, as
+    /// required by the standard.
+    pub fn from_data(ecode: i32, mut edata: Vec<u8>) -> Self {
+        let esize = (edata.len() + 8).div_ceil(16) * 16;
+        edata.resize(esize - 8, 0);
+        Extension::new(esize as i32, ecode, edata)
+    }
+
     /// Obtain the claimed extension raw size (`esize` field).
     pub fn size(&self) -> i32 {
         self.esize
@@ -94,6 +107,24 @@ impl Extension {
         self.ecode
     }
 
+    /// Obtain the extension's code as a registered extension code, or
+    /// `None` if the code is not registered.
+    pub fn extension_code(&self) -> Option<ExtensionCode> {
+        FromPrimitive::from_i32(self.ecode)
+    }
+
+    /// Obtain the extension's data as text, with the trailing NUL padding
+    /// removed. Invalid UTF-8 sequences are replaced with
+    /// `U+FFFD REPLACEMENT CHARACTER`.
+    pub fn text(&self) -> Cow<'_, str> {
+        let len = self
+            .edata
+            .iter()
+            .rposition(|c| *c != 0)
+            .map_or(0, |i| i + 1);
+        String::from_utf8_lossy(&self.edata[..len])
+    }
+
     /// Obtain the extension's data (`edata` field).
     pub fn data(&self) -> &Vec<u8> {
         &self.edata
@@ -106,8 +137,9 @@ impl Extension {
 }
 
 /// Data type for aggregating the extender code and
-/// all extensions.
-#[derive(Debug, PartialEq, Clone)]
+/// all extensions. When modifying the sequence, the extender code is kept
+/// consistent with the presence of extensions.
+#[derive(Debug, Default, PartialEq, Clone)]
 pub struct ExtensionSequence {
     extender: Extender,
     extensions: Vec<Extension>,
@@ -132,6 +164,11 @@ impl<'a> IntoIterator for &'a ExtensionSequence {
 }
 
 impl ExtensionSequence {
+    /// Create an empty sequence of extensions.
+    pub fn new() -> Self {
+        ExtensionSequence::default()
+    }
+
     /// Read a sequence of extensions from a source, up until `len` bytes.
     pub fn from_stream<B: ByteOrder, S: Read>(
         extender: Extender,
@@ -173,8 +210,84 @@ impl ExtensionSequence {
         self.extensions.len()
     }
 
+    /// Obtain an iterator to the extensions with the given code.
+    pub fn with_code(&self, code: ExtensionCode) -> impl Iterator<Item = &Extension> + '_ {
+        self.extensions
+            .iter()
+            .filter(move |e| e.code() == code as i32)
+    }
+
+    /// Obtain the first extension with the given code, if any.
+    pub fn find(&self, code: ExtensionCode) -> Option<&Extension> {
+        self.with_code(code).next()
+    }
+
+    /// Obtain the text of all comment extensions (`ExtensionCode::Comment`).
+    pub fn comments(&self) -> Vec<Cow<'_, str>> {
+        self.with_code(ExtensionCode::Comment)
+            .map(Extension::text)
+            .collect()
+    }
+
+    /// Obtain the XML text of the first AFNI extension
+    /// (`ExtensionCode::Afni`), if any.
+    pub fn afni_xml(&self) -> Option<Cow<'_, str>> {
+        self.find(ExtensionCode::Afni).map(Extension::text)
+    }
+
+    /// Obtain the XML text of the first CIFTI extension
+    /// (`ExtensionCode::Cifti`), if any.
+    pub fn cifti_xml(&self) -> Option<Cow<'_, str>> {
+        self.find(ExtensionCode::Cifti).map(Extension::text)
+    }
+
+    /// Obtain the JSON text of the first NIfTI-MRS header extension
+    /// (`ExtensionCode::Mrs`), if any.
+    pub fn json(&self) -> Option<Cow<'_, str>> {
+        self.find(ExtensionCode::Mrs).map(Extension::text)
+    }
+
+    /// Append an extension to the end of the sequence. Its data is padded
+    /// with zeros and its size recomputed (see `Extension::from_data`), so
+    /// that the full size is always a multiple of 16.
+    pub fn push(&mut self, extension: Extension) {
+        let ecode = extension.code();
+        self.extensions
+            .push(Extension::from_data(ecode, extension.into_data()));
+        self.update_extender();
+    }
+
+    /// Remove and return the extension at the given position.
+    ///
+    /// # Panics
+    ///
+    /// If `index` is out of bounds.
+    pub fn remove(&mut self, index: usize) -> Extension {
+        let extension = self.extensions.remove(index);
+        self.update_extender();
+        extension
+    }
+
+    /// Retain only the extensions for which the given predicate returns
+    /// `true`, keeping their order.
+    pub fn retain<F>(&mut self, f: F)
+    where
+        F: FnMut(&Extension) -> bool,
+    {
+        self.extensions.retain(f);
+        self.update_extender();
+    }
+
     /// Get the extender code from this extension sequence.
     pub fn extender(&self) -> Extender {
         self.extender
     }
+
+    fn update_extender(&mut self) {
+        self.extender = if self.extensions.is_empty() {
+            Extender::default()
+        } else {
+            Extender::from([1, 0, 0, 0])
+        };
+    }
 }
@@ -0,0 +1,571 @@
+//! This module provides support for GIFTI surface files (`.gii`), such as
+//! surface meshes (`.surf.gii`), shape maps (`.shape.gii`) and label maps
+//! (`.label.gii`). Requires the `gifti` Cargo feature.
+//!
+//! A GIFTI file is an XML document holding a list of data arrays. The
+//! intent of each array tells what it stands for (`Intent::Pointset` for
+//! the vertex coordinates of a mesh, `Intent::Triangle` for its faces,
+//! `Intent::Shape` for a scalar per vertex, ...). Arrays encoded as
+//! `ASCII`, `Base64Binary` and `GZipBase64Binary` are supported, while
+//! arrays stored in external files are not.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use nifti::gifti::Gifti;
+//! # use nifti::Result;
+//!
+//! # fn run() -> Result<()> {
+//! let gifti = Gifti::from_file("lh.pial.surf.gii")?;
+//! let vertices = gifti.pointset().expect("missing vertices");
+//! let triangles = gifti.triangles().expect("missing triangles");
+//! println!("{} vertices, {} triangles", vertices.len(), triangles.len());
+//! # Ok(())
+//! # }
+//! ```
+
+use affine::Affine;
+use base64;
+use dom::Element;
+use error::{NiftiError, Result};
+use flate2::bufread::GzDecoder;
+use flate2::read::ZlibDecoder;
+use label::{Label, LabelTable};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::str::FromStr;
+use typedef::{Intent, NiftiType, XForm};
+use util::{decompress, Endianness};
+
+/// The names of the intent codes, as written in GIFTI documents.
+const INTENT_NAMES: &[(&str, Intent)] = &[
+    ("NIFTI_INTENT_NONE", Intent::None),
+    ("NIFTI_INTENT_CORREL", Intent::Correl),
+    ("NIFTI_INTENT_TTEST", Intent::Ttest),
+    ("NIFTI_INTENT_FTEST", Intent::Ftest),
+    ("NIFTI_INTENT_ZSCORE", Intent::Zscore),
+    ("NIFTI_INTENT_CHISQ", Intent::Chisq),
+    ("NIFTI_INTENT_BETA", Intent::Beta),
+    ("NIFTI_INTENT_BINOM", Intent::Binom),
+    ("NIFTI_INTENT_GAMMA", Intent::Gamma),
+    ("NIFTI_INTENT_POISSON", Intent::Poisson),
+    ("NIFTI_INTENT_NORMAL", Intent::Normal),
+    ("NIFTI_INTENT_FTEST_NONC", Intent::FtestNonc),
+    ("NIFTI_INTENT_CHISQ_NONC", Intent::ChisqNonc),
+    ("NIFTI_INTENT_LOGISTIC", Intent::Logistic),
+    ("NIFTI_INTENT_LAPLACE", Intent::Laplace),
+    ("NIFTI_INTENT_UNIFORM", Intent::Uniform),
+    ("NIFTI_INTENT_TTEST_NONC", Intent::TtestNonc),
+    ("NIFTI_INTENT_WEIBULL", Intent::Weibull),
+    ("NIFTI_INTENT_CHI", Intent::Chi),
+    ("NIFTI_INTENT_INVGAUSS", Intent::Invgauss),
+    ("NIFTI_INTENT_EXTVAL", Intent::Extval),
+    ("NIFTI_INTENT_PVAL", Intent::Pval),
+    ("NIFTI_INTENT_LOGPVAL", Intent::Logpval),
+    ("NIFTI_INTENT_LOG10PVAL", Intent::Log10pval),
+    ("NIFTI_INTENT_ESTIMATE", Intent::Estimate),
+    ("NIFTI_INTENT_LABEL", Intent::Label),
+    ("NIFTI_INTENT_NEURONAME", Intent::Neuroname),
+    ("NIFTI_INTENT_GENMATRIX", Intent::Genmatrix),
+    ("NIFTI_INTENT_SYMMATRIX", Intent::Symmatrix),
+    ("NIFTI_INTENT_DISPVECT", Intent::Dispvect),
+    ("NIFTI_INTENT_VECTOR", Intent::Vector),
+    ("NIFTI_INTENT_POINTSET", Intent::Pointset),
+    ("NIFTI_INTENT_TRIANGLE", Intent::Triangle),
+    ("NIFTI_INTENT_QUATERNION", Intent::Quaternion),
+    ("NIFTI_INTENT_DIMLESS", Intent::Dimless),
+    ("NIFTI_INTENT_TIME_SERIES", Intent::TimeSeries),
+    ("NIFTI_INTENT_NODE_INDEX", Intent::NodeIndex),
+    ("NIFTI_INTENT_RGB_VECTOR", Intent::RgbVector),
+    ("NIFTI_INTENT_RGBA_VECTOR", Intent::RgbaVector),
+    ("NIFTI_INTENT_SHAPE", Intent::Shape),
+];
+
+/// The names of the data types, as written in GIFTI documents.
+const TYPE_NAMES: &[(&str, NiftiType)] = &[
+    ("NIFTI_TYPE_UINT8", NiftiType::Uint8),
+    ("NIFTI_TYPE_INT16", NiftiType::Int16),
+    ("NIFTI_TYPE_INT32", NiftiType::Int32),
+    ("NIFTI_TYPE_FLOAT32", NiftiType::Float32),
+    ("NIFTI_TYPE_COMPLEX64", NiftiType::Complex64),
+    ("NIFTI_TYPE_FLOAT64", NiftiType::Float64),
+    ("NIFTI_TYPE_RGB24", NiftiType::Rgb24),
+    ("NIFTI_TYPE_INT8", NiftiType::Int8),
+    ("NIFTI_TYPE_UINT16", NiftiType::Uint16),
+    ("NIFTI_TYPE_UINT32", NiftiType::Uint32),
+    ("NIFTI_TYPE_INT64", NiftiType::Int64),
+    ("NIFTI_TYPE_UINT64", NiftiType::Uint64),
+    ("NIFTI_TYPE_FLOAT128", NiftiType::Float128),
+    ("NIFTI_TYPE_COMPLEX128", NiftiType::Complex128),
+    ("NIFTI_TYPE_COMPLEX256", NiftiType::Complex256),
+    ("NIFTI_TYPE_RGBA32", NiftiType::Rgba32),
+];
+
+/// The names of the coordinate spaces, as written in GIFTI documents.
+const XFORM_NAMES: &[(&str, XForm)] = &[
+    ("NIFTI_XFORM_UNKNOWN", XForm::Unknown),
+    ("NIFTI_XFORM_SCANNER_ANAT", XForm::ScannerAnat),
+    ("NIFTI_XFORM_ALIGNED_ANAT", XForm::AlignedAnat),
+    ("NIFTI_XFORM_TALAIRACH", XForm::Talairach),
+    ("NIFTI_XFORM_MNI_152", XForm::Mni152),
+];
+
+/// The values of a GIFTI data array. The GIFTI format only allows
+/// unsigned bytes, 32-bit integers and 32-bit floats.
+#[derive(Debug, PartialEq, Clone)]
+pub enum GiftiData {
+    /// `NIFTI_TYPE_UINT8` values
+    Uint8(Vec<u8>),
+    /// `NIFTI_TYPE_INT32` values
+    Int32(Vec<i32>),
+    /// `NIFTI_TYPE_FLOAT32` values
+    Float32(Vec<f32>),
+}
+
+impl GiftiData {
+    /// Obtain the data type of the values.
+    pub fn datatype(&self) -> NiftiType {
+        match *self {
+            GiftiData::Uint8(_) => NiftiType::Uint8,
+            GiftiData::Int32(_) => NiftiType::Int32,
+            GiftiData::Float32(_) => NiftiType::Float32,
+        }
+    }
+
+    /// Obtain the number of values.
+    pub fn len(&self) -> usize {
+        match *self {
+            GiftiData::Uint8(ref v) => v.len(),
+            GiftiData::Int32(ref v) => v.len(),
+            GiftiData::Float32(ref v) => v.len(),
+        }
+    }
+
+    /// Whether there are no values.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Obtain the value at the given (linear) position, as a `f64`.
+    pub fn get_f64(&self, index: usize) -> Option<f64> {
+        match *self {
+            GiftiData::Uint8(ref v) => v.get(index).map(|&x| f64::from(x)),
+            GiftiData::Int32(ref v) => v.get(index).map(|&x| f64::from(x)),
+            GiftiData::Float32(ref v) => v.get(index).map(|&x| f64::from(x)),
+        }
+    }
+
+    /// Convert all values to `f64`, in storage order.
+    pub fn to_f64(&self) -> Vec<f64> {
+        (0..self.len()).filter_map(|i| self.get_f64(i)).collect()
+    }
+
+    /// Obtain the values if they are unsigned bytes.
+    pub fn as_u8(&self) -> Option<&[u8]> {
+        match *self {
+            GiftiData::Uint8(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Obtain the values if they are 32-bit integers.
+    pub fn as_i32(&self) -> Option<&[i32]> {
+        match *self {
+            GiftiData::Int32(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Obtain the values if they are 32-bit floats.
+    pub fn as_f32(&self) -> Option<&[f32]> {
+        match *self {
+            GiftiData::Float32(ref v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+/// The order in which the values of a multi-dimensional array are stored.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum ArrayIndexingOrder {
+    /// The last index varies fastest (`RowMajorOrder`)
+    RowMajor,
+    /// The first index varies fastest (`ColumnMajorOrder`)
+    ColumnMajor,
+}
+
+/// A transform from the space of a data array to another space.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct CoordinateSystem {
+    /// The space of the array's coordinates
+    pub data_space: XForm,
+    /// The space which the matrix transforms the coordinates to
+    pub transformed_space: XForm,
+    /// The transform matrix
+    pub matrix: Affine,
+}
+
+/// A data array of a GIFTI file.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DataArray {
+    /// What the array stands for
+    pub intent: Intent,
+    /// The array's dimensions
+    pub dim: Vec<usize>,
+    /// The order in which the values are stored
+    pub order: ArrayIndexingOrder,
+    /// The array's metadata, as name-value pairs
+    pub metadata: Vec<(String, String)>,
+    /// The transforms of the array's coordinates, for point sets
+    pub transforms: Vec<CoordinateSystem>,
+    /// The array's values
+    pub data: GiftiData,
+}
+
+impl DataArray {
+    /// Obtain the value at the given position, as a `f64`. Returns `None`
+    /// if the number of indices does not match the array's dimensionality,
+    /// or if an index is out of bounds.
+    pub fn get(&self, idx: &[usize]) -> Option<f64> {
+        if idx.len() != self.dim.len() || idx.iter().zip(&self.dim).any(|(i, d)| i >= d) {
+            return None;
+        }
+        let mut index = 0;
+        match self.order {
+            ArrayIndexingOrder::RowMajor => {
+                for (i, d) in idx.iter().zip(&self.dim) {
+                    index = index * d + i;
+                }
+            }
+            ArrayIndexingOrder::ColumnMajor => {
+                for (i, d) in idx.iter().zip(&self.dim).rev() {
+                    index = index * d + i;
+                }
+            }
+        }
+        self.data.get_f64(index)
+    }
+
+    /// Obtain the vertex coordinates, if this is a `N x 3` point set array
+    /// of floats.
+    pub fn points(&self) -> Option<Vec<[f32; 3]>> {
+        if self.intent != Intent::Pointset || self.data.as_f32().is_none() {
+            return None;
+        }
+        self.rows().map(|rows| {
+            rows.map(|[x, y, z]| [x as f32, y as f32, z as f32])
+                .collect()
+        })
+    }
+
+    /// Obtain the vertex indices of the faces, if this is a `N x 3`
+    /// triangle array of integers.
+    pub fn triangles(&self) -> Option<Vec<[i32; 3]>> {
+        if self.intent != Intent::Triangle || self.data.as_i32().is_none() {
+            return None;
+        }
+        self.rows().map(|rows| {
+            rows.map(|[a, b, c]| [a as i32, b as i32, c as i32])
+                .collect()
+        })
+    }
+
+    fn rows<'a>(&'a self) -> Option<impl Iterator<Item = [f64; 3]> + 'a> {
+        if self.dim.len() != 2 || self.dim[1] != 3 {
+            return None;
+        }
+        Some((0..self.dim[0]).map(move |i| {
+            let v = |j| self.get(&[i, j]).unwrap_or_default();
+            [v(0), v(1), v(2)]
+        }))
+    }
+}
+
+/// The contents of a GIFTI file.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Gifti {
+    /// The GIFTI version, usually `"1.0"`
+    pub version: String,
+    /// The file's metadata, as name-value pairs
+    pub metadata: Vec<(String, String)>,
+    /// The label table, for label maps
+    pub label_table: Option<LabelTable>,
+    /// The data arrays
+    pub arrays: Vec<DataArray>,
+}
+
+impl Gifti {
+    /// Read a GIFTI file. Compressed files are detected from their first
+    /// bytes and automatically decoded, regardless of their extension.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::InvalidGifti` if the document is not valid XML, if
+    ///   it does not follow the GIFTI schema, or if a data array uses an
+    ///   unsupported encoding or data type.
+    /// - `NiftiError::UnsupportedCompression` if the file is compressed
+    ///   with a format whose Cargo feature is not enabled.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = BufReader::new(File::open(path)?);
+        Gifti::from_reader(decompress(file)?)
+    }
+
+    /// Read a GIFTI document from a stream of data.
+    ///
+    /// # Errors
+    ///
+    /// - See `from_file`.
+    pub fn from_reader<R: Read>(source: R) -> Result<Self> {
+        let root = Element::parse(source).map_err(|e| invalid(e.to_string()))?;
+        Gifti::from_element(&root)
+    }
+
+    /// Parse a GIFTI document.
+    ///
+    /// # Errors
+    ///
+    /// - See `from_file`.
+    pub fn from_xml(xml: &str) -> Result<Self> {
+        Gifti::from_reader(xml.as_bytes())
+    }
+
+    fn from_element(root: &Element) -> Result<Self> {
+        if root.name != "GIFTI" {
+            return Err(invalid(format!("unexpected root element `{}`", root.name)));
+        }
+        let arrays = root
+            .children_named("DataArray")
+            .map(parse_data_array)
+            .collect::<Result<_>>()?;
+        Ok(Gifti {
+            version: root.attr("Version").unwrap_or_default().to_string(),
+            metadata: parse_metadata(root),
+            label_table: root
+                .child("LabelTable")
+                .map(parse_label_table)
+                .transpose()?,
+            arrays,
+        })
+    }
+
+    /// Iterate over the data arrays with the given intent.
+    pub fn arrays_with_intent(&self, intent: Intent) -> impl Iterator<Item = &DataArray> {
+        self.arrays.iter().filter(move |a| a.intent == intent)
+    }
+
+    /// Obtain the vertex coordinates of the first point set array, if any.
+    pub fn pointset(&self) -> Option<Vec<[f32; 3]>> {
+        self.arrays_with_intent(Intent::Pointset)
+            .next()
+            .and_then(DataArray::points)
+    }
+
+    /// Obtain the faces of the first triangle array, if any.
+    pub fn triangles(&self) -> Option<Vec<[i32; 3]>> {
+        self.arrays_with_intent(Intent::Triangle)
+            .next()
+            .and_then(DataArray::triangles)
+    }
+}
+
+fn invalid<S: Into<String>>(reason: S) -> NiftiError {
+    NiftiError::InvalidGifti(reason.into())
+}
+
+fn attr<'a>(element: &'a Element, name: &str) -> Result<&'a str> {
+    element.attr(name).ok_or_else(|| {
+        invalid(format!(
+            "missing attribute `{}` in element `{}`",
+            name, element.name
+        ))
+    })
+}
+
+fn parse_attr<T: FromStr>(element: &Element, name: &str) -> Result<T> {
+    let value = attr(element, name)?;
+    value.trim().parse().map_err(|_| {
+        invalid(format!(
+            "invalid value `{}` for attribute `{}`",
+            value, name
+        ))
+    })
+}
+
+fn lookup<T: Copy>(table: &[(&str, T)], name: &str) -> Result<T> {
+    let name = name.trim();
+    table
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|&(_, v)| v)
+        .ok_or_else(|| invalid(format!("unknown name `{}`", name)))
+}
+
+fn parse_metadata(element: &Element) -> Vec<(String, String)> {
+    element
+        .child("MetaData")
+        .map(|md| {
+            md.children_named("MD")
+                .map(|e| {
+                    let text = |name| e.child(name).map(|c| c.text.trim().to_string());
+                    (
+                        text("Name").unwrap_or_default(),
+                        text("Value").unwrap_or_default(),
+                    )
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn parse_label_table(element: &Element) -> Result<LabelTable> {
+    let mut table = LabelTable::new();
+    for label in element.children_named("Label") {
+        let mut color = [0u8; 4];
+        for (c, name) in color.iter_mut().zip(&["Red", "Green", "Blue", "Alpha"]) {
+            let v: f64 = match label.attr(name) {
+                Some(_) => parse_attr(label, name)?,
+                None => 1.,
+            };
+            *c = (v.clamp(0., 1.) * 255.).round() as u8;
+        }
+        let _ = table.insert(Label {
+            value: parse_attr(label, "Key")?,
+            name: label.text.trim().to_string(),
+            color,
+        });
+    }
+    Ok(table)
+}
+
+fn parse_transform(element: &Element) -> Result<CoordinateSystem> {
+    let space = |name| -> Result<XForm> {
+        let text = element
+            .child(name)
+            .map(|e| e.text.trim())
+            .ok_or_else(|| invalid(format!("missing element `{}`", name)))?;
+        // other spaces are in use, but have no NIfTI code
+        Ok(lookup(XFORM_NAMES, text).unwrap_or(XForm::Unknown))
+    };
+    let values: Vec<f64> = match element.child("MatrixData") {
+        Some(e) => parse_values(&e.text)?,
+        None => return Err(invalid("missing element `MatrixData`")),
+    };
+    if values.len() != 16 {
+        return Err(invalid("a transform matrix must hold 16 values"));
+    }
+    let mut matrix = [[0.; 4]; 4];
+    for (row, chunk) in matrix.iter_mut().zip(values.chunks(4)) {
+        row.copy_from_slice(chunk);
+    }
+    Ok(CoordinateSystem {
+        data_space: space("DataSpace")?,
+        transformed_space: space("TransformedSpace")?,
+        matrix,
+    })
+}
+
+fn parse_values<T: FromStr>(text: &str) -> Result<Vec<T>> {
+    text.split_whitespace()
+        .map(|v| {
+            v.parse()
+                .map_err(|_| invalid(format!("invalid value `{}` in data array", v)))
+        })
+        .collect()
+}
+
+fn parse_data_array(element: &Element) -> Result<DataArray> {
+    let intent = lookup(INTENT_NAMES, attr(element, "Intent")?)?;
+    let datatype = lookup(TYPE_NAMES, attr(element, "DataType")?)?;
+    let order = match attr(element, "ArrayIndexingOrder")?.trim() {
+        "RowMajorOrder" => ArrayIndexingOrder::RowMajor,
+        "ColumnMajorOrder" => ArrayIndexingOrder::ColumnMajor,
+        o => return Err(invalid(format!("unknown array indexing order `{}`", o))),
+    };
+    let dimensionality: usize = parse_attr(element, "Dimensionality")?;
+    if !(1..=6).contains(&dimensionality) {
+        return Err(invalid(format!(
+            "invalid dimensionality {}",
+            dimensionality
+        )));
+    }
+    let dim = (0..dimensionality)
+        .map(|i| parse_attr(element, &format!("Dim{}", i)))
+        .collect::<Result<Vec<usize>>>()?;
+    let endianness = match element.attr("Endian").map(str::trim) {
+        Some("BigEndian") => Endianness::BE,
+        Some("LittleEndian") | None => Endianness::LE,
+        Some(e) => return Err(invalid(format!("unknown endianness `{}`", e))),
+    };
+
+    let text = element.child("Data").map(|e| e.text.as_str()).unwrap_or("");
+    let data = match attr(element, "Encoding")?.trim() {
+        "ASCII" => match datatype {
+            NiftiType::Uint8 => GiftiData::Uint8(parse_values(text)?),
+            NiftiType::Int32 => GiftiData::Int32(parse_values(text)?),
+            NiftiType::Float32 => GiftiData::Float32(parse_values(text)?),
+            t => return Err(invalid(format!("unsupported data type {:?}", t))),
+        },
+        "Base64Binary" => decode_binary(datatype, decode_base64(text)?, endianness)?,
+        "GZipBase64Binary" => {
+            let compressed = decode_base64(text)?;
+            let mut bytes = Vec::new();
+            // the format asks for zlib streams, but some writers use gzip
+            if compressed.starts_with(&[0x1f, 0x8b]) {
+                let _ = GzDecoder::new(&compressed[..]).read_to_end(&mut bytes)?;
+            } else {
+                let _ = ZlibDecoder::new(&compressed[..]).read_to_end(&mut bytes)?;
+            }
+            decode_binary(datatype, bytes, endianness)?
+        }
+        "ExternalFileBinary" => return Err(invalid("external data files are not supported")),
+        e => return Err(invalid(format!("unknown encoding `{}`", e))),
+    };
+    if data.len() != dim.iter().product::<usize>() {
+        return Err(invalid(format!(
+            "expected {} values in data array, found {}",
+            dim.iter().product::<usize>(),
+            data.len()
+        )));
+    }
+
+    Ok(DataArray {
+        intent,
+        dim,
+        order,
+        metadata: parse_metadata(element),
+        transforms: element
+            .children_named("CoordinateSystemTransformMatrix")
+            .map(parse_transform)
+            .collect::<Result<_>>()?,
+        data,
+    })
+}
+
+fn decode_base64(text: &str) -> Result<Vec<u8>> {
+    let text: String = text.split_whitespace().collect();
+    base64::decode(&text).map_err(|e| invalid(format!("invalid base64 data: {}", e)))
+}
+
+fn decode_binary(datatype: NiftiType, bytes: Vec<u8>, endianness: Endianness) -> Result<GiftiData> {
+    let size = datatype.size_of();
+    if !bytes.len().is_multiple_of(size) {
+        return Err(invalid("the data size is not a multiple of the value size"));
+    }
+    Ok(match datatype {
+        NiftiType::Uint8 => GiftiData::Uint8(bytes),
+        NiftiType::Int32 => GiftiData::Int32(
+            bytes
+                .chunks(size)
+                .map(|c| endianness.read_i32(c))
+                .collect::<::std::io::Result<_>>()?,
+        ),
+        NiftiType::Float32 => GiftiData::Float32(
+            bytes
+                .chunks(size)
+                .map(|c| endianness.read_f32(c))
+                .collect::<::std::io::Result<_>>()?,
+        ),
+        t => return Err(invalid(format!("unsupported data type {:?}", t))),
+    })
+}
@@ -1,21 +1,26 @@
 //! This module defines the `NiftiHeader` struct, which is used
 //! to provide important information about NIFTI-1 volumes.
 
+use affine::{self, Affine, Quaternion};
 use byteorder::{ByteOrder, NativeEndian, ReadBytesExt};
 use error::{NiftiError, Result};
-use flate2::bufread::GzDecoder;
+use intent::IntentSpec;
 use num_traits::FromPrimitive;
 use std::fs::File;
 use std::io::{BufReader, Read};
 use std::path::Path;
 use typedef::*;
-use util::{is_gz_file, Endianness, OppositeNativeEndian};
+use util::{decompress, Endianness, OppositeNativeEndian};
 
 /// Magic code for NIFTI-1 header files (extention ".hdr[.gz]").
 pub const MAGIC_CODE_NI1: &'static [u8; 4] = b"ni1\0";
 /// Magic code for full NIFTI-1 files (extention ".nii[.gz]").
 pub const MAGIC_CODE_NIP1: &'static [u8; 4] = b"n+1\0";
 
+/// Obliquity angle (in degrees) above which a header is considered oblique.
+/// This is the same threshold used by AFNI.
+pub const OBLIQUITY_THRESHOLD: f64 = 0.01;
+
 /// The NIFTI-1 header data type.
 /// All fields are public and named after the specification's header file.
 /// The type of each field was adjusted according to their use and
@@ -212,22 +217,61 @@ impl Default for NiftiHeader {
 
 impl NiftiHeader {
     /// Retrieve a NIFTI header, along with its byte order, from a file in the file system.
-    /// If the file's name ends with ".gz", the file is assumed to need GZip decoding.
+    /// Compressed files are detected from their first bytes and decoded, regardless
+    /// of the file's name. GZip is always supported, while Zstandard, Bzip2 and XZ
+    /// require the `zstd`, `bzip2` and `xz2` features respectively.
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<NiftiHeader> {
-        let gz = is_gz_file(&path);
-        let file = BufReader::new(File::open(path)?);
-        if gz {
-            NiftiHeader::from_stream(GzDecoder::new(file))
-        } else {
-            NiftiHeader::from_stream(file)
-        }
+        NiftiHeader::from_stream(decompress(BufReader::new(File::open(path)?))?)
     }
 
     /// Read a NIfTI-1 header, along with its byte order, from the given byte stream.
     /// It is assumed that the input is currently at the start of the
     /// NIFTI header.
     pub fn from_stream<S: Read>(input: S) -> Result<NiftiHeader> {
-        parse_header_1(input)
+        let h = parse_header_1(input)?;
+        if &h.magic != MAGIC_CODE_NI1 && &h.magic != MAGIC_CODE_NIP1 {
+            Err(NiftiError::InvalidFormat)
+        } else {
+            Ok(h)
+        }
+    }
+
+    /// Retrieve a header from a file in the file system, accepting legacy
+    /// Analyze 7.5 headers as well as NIfTI-1 headers (see
+    /// `from_analyze_stream`). As in `from_file`, compressed files are
+    /// detected from their first bytes.
+    pub fn from_analyze_file<P: AsRef<Path>>(path: P) -> Result<NiftiHeader> {
+        NiftiHeader::from_analyze_stream(decompress(BufReader::new(File::open(path)?))?)
+    }
+
+    /// Read a header from the given byte stream, accepting legacy Analyze
+    /// 7.5 headers as well as NIfTI-1 headers. NIfTI-1 headers are returned
+    /// as is. An Analyze header (one without the NIfTI magic code) is
+    /// converted into a NIfTI-1 header for a file pair (`ni1`):
+    ///
+    /// - fields which have a different meaning in Analyze 7.5 are cleared;
+    /// - the SPM scale factor (`funused1`) becomes `scl_slope`;
+    /// - the spatial unit is millimetres and the temporal unit is seconds;
+    /// - the qform and the sform are set to a best-effort affine
+    ///   transformation, built from the voxel spacing, the image
+    ///   orientation (`orient`) and the SPM origin (`originator`, in
+    ///   one-based voxel coordinates). Without an origin, the center of
+    ///   the volume is used.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::InvalidFormat` if `sizeof_hdr` is not 348.
+    pub fn from_analyze_stream<S: Read>(mut input: S) -> Result<NiftiHeader> {
+        let mut raw = [0u8; 348];
+        input.read_exact(&mut raw)?;
+        let h = parse_header_1(&raw[..])?;
+        if h.sizeof_hdr != 348 {
+            Err(NiftiError::InvalidFormat)
+        } else if &h.magic == MAGIC_CODE_NI1 || &h.magic == MAGIC_CODE_NIP1 {
+            Ok(h)
+        } else {
+            analyze_to_nifti(&h, &raw)
+        }
     }
 
     /// Get the data type as a validated enum.
@@ -255,18 +299,321 @@ impl NiftiHeader {
         Ok((self.xyzt_to_space()?, self.xyzt_to_time()?))
     }
 
+    /// Set the space and time units of the header, converting all
+    /// measurements to the new units. This includes the voxel spacing in
+    /// `pixdim`, the translations of the qform and sform, the sform's scaling
+    /// factors, `slice_duration` and `toffset`.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::InvalidCode` if either of the current units is invalid.
+    /// - `NiftiError::IncompatibleUnit` if `space` is not a unit of space or
+    ///   `time` is not a unit of time, or if conversion between the current
+    ///   and new units is not possible (e.g. spectral units).
+    pub fn set_xyzt_units(&mut self, space: Unit, time: Unit) -> Result<()> {
+        if !space.is_space() {
+            return Err(NiftiError::IncompatibleUnit(space));
+        }
+        if !time.is_time() {
+            return Err(NiftiError::IncompatibleUnit(time));
+        }
+        let (old_space, old_time) = self.xyzt_units()?;
+
+        let space_factor = if old_space == space {
+            1.
+        } else {
+            let from = old_space
+                .millimeters()
+                .ok_or(NiftiError::IncompatibleUnit(old_space))?;
+            let to = space.millimeters().ok_or(NiftiError::IncompatibleUnit(space))?;
+            from / to
+        };
+        let time_factor = if old_time == time {
+            1.
+        } else {
+            let from = old_time
+                .seconds()
+                .ok_or(NiftiError::IncompatibleUnit(old_time))?;
+            let to = time.seconds().ok_or(NiftiError::IncompatibleUnit(time))?;
+            from / to
+        };
+
+        let scale = |v: &mut f32, factor: f64| *v = (f64::from(*v) * factor) as f32;
+        for v in &mut self.pixdim[1..4] {
+            scale(v, space_factor);
+        }
+        for v in self
+            .srow_x
+            .iter_mut()
+            .chain(self.srow_y.iter_mut())
+            .chain(self.srow_z.iter_mut())
+        {
+            scale(v, space_factor);
+        }
+        scale(&mut self.quatern_x, space_factor);
+        scale(&mut self.quatern_y, space_factor);
+        scale(&mut self.quatern_z, space_factor);
+        scale(&mut self.pixdim[4], time_factor);
+        scale(&mut self.slice_duration, time_factor);
+        scale(&mut self.toffset, time_factor);
+
+        self.xyzt_units = space as u8 | time as u8;
+        Ok(())
+    }
+
+    /// Get the size of a voxel in each of the three spatial axes, in
+    /// millimeters. If the space unit is unknown, `pixdim` is assumed to be
+    /// in millimeters.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::InvalidCode` if the space unit is invalid.
+    pub fn voxel_size_mm(&self) -> Result<[f64; 3]> {
+        let unit = self.xyzt_to_space()?;
+        let factor = unit.millimeters().ok_or(NiftiError::IncompatibleUnit(unit))?;
+        Ok([
+            f64::from(self.pixdim[1]).abs() * factor,
+            f64::from(self.pixdim[2]).abs() * factor,
+            f64::from(self.pixdim[3]).abs() * factor,
+        ])
+    }
+
+    /// Get the volume of a single voxel, in cubic millimeters.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::InvalidCode` if the space unit is invalid.
+    pub fn voxel_volume_mm3(&self) -> Result<f64> {
+        Ok(self.voxel_size_mm()?.iter().product())
+    }
+
+    /// Get the extent of the volume in each of the three spatial axes
+    /// (number of voxels times voxel size), in millimeters. Axes beyond the
+    /// volume's dimensionality count as a single voxel.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::InvalidCode` if the space unit is invalid.
+    pub fn field_of_view_mm(&self) -> Result<[f64; 3]> {
+        let mut fov = self.voxel_size_mm()?;
+        for (i, v) in fov.iter_mut().enumerate() {
+            if i < self.dim[0] as usize {
+                *v *= f64::from(self.dim[i + 1]);
+            }
+        }
+        Ok(fov)
+    }
+
+    /// Get the time step between consecutive volumes (`pixdim[4]`), in
+    /// seconds. If the time unit is unknown, `pixdim[4]` is assumed to be
+    /// in seconds.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::InvalidCode` if the time unit is invalid.
+    /// - `NiftiError::IncompatibleUnit` if the time unit is spectral.
+    pub fn time_step_sec(&self) -> Result<f64> {
+        let unit = self.xyzt_to_time()?;
+        let factor = unit.seconds().ok_or(NiftiError::IncompatibleUnit(unit))?;
+        Ok(f64::from(self.pixdim[4]) * factor)
+    }
+
     /// Get the slice order as a validated enum.
     pub fn slice_order(&self) -> Result<SliceOrder> {
         FromPrimitive::from_u8(self.slice_code)
             .ok_or_else(|| NiftiError::InvalidCode("slice order", self.slice_code as i16))
     }
 
+    /// Get the axis of the frequency encoding direction, as encoded in
+    /// `dim_info`. Axes are zero-based (0 for x, 1 for y and 2 for z).
+    /// Returns `None` if the axis is not specified.
+    pub fn freq_dim(&self) -> Option<u16> {
+        dim_info_axis(self.dim_info)
+    }
+
+    /// Get the axis of the phase encoding direction, as encoded in
+    /// `dim_info`. Axes are zero-based (0 for x, 1 for y and 2 for z).
+    /// Returns `None` if the axis is not specified.
+    pub fn phase_dim(&self) -> Option<u16> {
+        dim_info_axis(self.dim_info >> 2)
+    }
+
+    /// Get the slice acquisition axis, as encoded in `dim_info`. Axes are
+    /// zero-based (0 for x, 1 for y and 2 for z). Returns `None` if the axis
+    /// is not specified.
+    pub fn slice_dim(&self) -> Option<u16> {
+        dim_info_axis(self.dim_info >> 4)
+    }
+
+    /// Set the axis of the frequency encoding direction in `dim_info`.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::AxisOutOfBounds` if the axis is not one of the three
+    ///   spatial axes.
+    pub fn set_freq_dim(&mut self, axis: Option<u16>) -> Result<()> {
+        self.dim_info = (self.dim_info & !0b11) | dim_info_code(axis)?;
+        Ok(())
+    }
+
+    /// Set the axis of the phase encoding direction in `dim_info`.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::AxisOutOfBounds` if the axis is not one of the three
+    ///   spatial axes.
+    pub fn set_phase_dim(&mut self, axis: Option<u16>) -> Result<()> {
+        self.dim_info = (self.dim_info & !0b1100) | (dim_info_code(axis)? << 2);
+        Ok(())
+    }
+
+    /// Set the slice acquisition axis in `dim_info`.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::AxisOutOfBounds` if the axis is not one of the three
+    ///   spatial axes.
+    pub fn set_slice_dim(&mut self, axis: Option<u16>) -> Result<()> {
+        self.dim_info = (self.dim_info & !0b11_0000) | (dim_info_code(axis)? << 4);
+        Ok(())
+    }
+
+    /// Get the acquisition time of each slice along the slice axis, in
+    /// seconds (see `SliceInfo::slice_times`). The repetition time is
+    /// taken from `pixdim[4]`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the slice information is incomplete (see `slice_info`).
+    pub fn slice_times(&self) -> Result<Vec<Option<f64>>> {
+        self.slice_info()?.slice_times(self.time_step_sec()?)
+    }
+
+    /// Set the slice acquisition parameters from the acquisition time of
+    /// each slice along the slice axis, in seconds (such as the
+    /// `SliceTiming` field of a BIDS sidecar). This infers `slice_code` and
+    /// `slice_duration` (in the header's time unit), and sets the slice
+    /// range to cover all slices.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::MissingSliceInfo` if the slice axis is not specified.
+    /// - `NiftiError::IncompatibleLength` if the number of times does not
+    ///   match the number of slices.
+    /// - `NiftiError::UnsupportedSliceTiming` if the times do not match any
+    ///   slice order.
+    pub fn set_slice_times(&mut self, times: &[f64]) -> Result<()> {
+        let axis = self.slice_dim().ok_or(NiftiError::MissingSliceInfo)?;
+        let axis = axis as usize;
+        if axis >= self.dim[0] as usize || times.len() != self.dim[axis + 1] as usize {
+            return Err(NiftiError::IncompatibleLength);
+        }
+        let (order, duration) =
+            SliceOrder::from_slice_times(times).ok_or(NiftiError::UnsupportedSliceTiming)?;
+        let unit = self.xyzt_to_time()?;
+        let factor = unit.seconds().ok_or(NiftiError::IncompatibleUnit(unit))?;
+
+        self.slice_code = order as u8;
+        self.slice_start = 0;
+        self.slice_end = times.len() as i16 - 1;
+        self.slice_duration = (duration / factor) as f32;
+        Ok(())
+    }
+
+    /// Get the slice acquisition parameters of the volume, which bring
+    /// together the slice axis in `dim_info`, the slice order in
+    /// `slice_code`, the slice range in `slice_start` and `slice_end`, and
+    /// the slice duration (converted to seconds). If `slice_end` is zero,
+    /// the range is assumed to extend to the last slice.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::MissingSliceInfo` if the slice axis is not specified.
+    /// - `NiftiError::InvalidCode` if the slice order or time unit is invalid.
+    /// - `NiftiError::OutOfBounds` if the slice range does not fit the
+    ///   number of slices.
+    /// - `NiftiError::IncompatibleUnit` if the time unit is spectral.
+    pub fn slice_info(&self) -> Result<SliceInfo> {
+        let axis = self.slice_dim().ok_or(NiftiError::MissingSliceInfo)?;
+        let order = self.slice_order()?;
+        let n = if (axis as usize) < self.dim[0] as usize {
+            self.dim[axis as usize + 1]
+        } else {
+            1
+        };
+        let start = self.slice_start;
+        let end = if self.slice_end == 0 {
+            n as i16 - 1
+        } else {
+            self.slice_end
+        };
+        if start < 0 || end < start || end as u16 >= n {
+            return Err(NiftiError::OutOfBounds(vec![start as u16, end as u16]));
+        }
+        let unit = self.xyzt_to_time()?;
+        let factor = unit.seconds().ok_or(NiftiError::IncompatibleUnit(unit))?;
+
+        Ok(SliceInfo {
+            axis,
+            order,
+            num_slices: n,
+            start: start as u16,
+            end: end as u16,
+            duration: f64::from(self.slice_duration) * factor,
+        })
+    }
+
     /// Get the intent as a validated enum.
     pub fn intent(&self) -> Result<Intent> {
         FromPrimitive::from_i16(self.intent_code)
             .ok_or_else(|| NiftiError::InvalidCode("intent", self.intent_code))
     }
 
+    /// Get the intent together with its typed parameters, as described by
+    /// `intent_code`, `intent_p1`, `intent_p2`, `intent_p3` and
+    /// `intent_name`.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::InvalidCode` if the intent code is not valid.
+    /// - `NiftiError::InvalidIntentParameters` if the intent parameters are
+    ///   out of range for the intent.
+    pub fn intent_spec(&self) -> Result<IntentSpec> {
+        let len = self
+            .intent_name
+            .iter()
+            .position(|c| *c == 0)
+            .unwrap_or(self.intent_name.len());
+        let name = String::from_utf8_lossy(&self.intent_name[..len]);
+        IntentSpec::from_parts(
+            self.intent()?,
+            [self.intent_p1, self.intent_p2, self.intent_p3],
+            &name,
+        )
+    }
+
+    /// Write an intent and its parameters into the header. Parameters not
+    /// used by the intent are set to zero. `intent_name` is only modified
+    /// if the intent gives it a meaning.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::InvalidIntentParameters` if the intent parameters are
+    ///   out of range for the intent.
+    pub fn set_intent_spec(&mut self, spec: &IntentSpec) -> Result<()> {
+        spec.validate()?;
+        let [p1, p2, p3] = spec.params();
+        self.intent_code = spec.intent() as i16;
+        self.intent_p1 = p1;
+        self.intent_p2 = p2;
+        self.intent_p3 = p3;
+        if let Some(name) = spec.name() {
+            self.intent_name = [0; 16];
+            self.intent_name[..name.len()].copy_from_slice(name.as_bytes());
+        }
+        Ok(())
+    }
+
     /// Get the qform coordinate mapping method as a validated enum.
     pub fn qform(&self) -> Result<XForm> {
         FromPrimitive::from_i16(self.qform_code)
@@ -278,6 +625,168 @@ impl NiftiHeader {
         FromPrimitive::from_i16(self.sform_code)
             .ok_or_else(|| NiftiError::InvalidCode("sform", self.sform_code as i16))
     }
+
+    /// Get the affine transformation described by the quaternion
+    /// parameters (qform, method 2), regardless of `qform_code`.
+    pub fn qform_affine(&self) -> Affine {
+        affine::from_quaternion(&Quaternion {
+            b: f64::from(self.quatern_b),
+            c: f64::from(self.quatern_c),
+            d: f64::from(self.quatern_d),
+            x: f64::from(self.quatern_x),
+            y: f64::from(self.quatern_y),
+            z: f64::from(self.quatern_z),
+            spacing: [
+                f64::from(self.pixdim[1]),
+                f64::from(self.pixdim[2]),
+                f64::from(self.pixdim[3]),
+            ],
+            qfac: f64::from(self.pixdim[0]),
+        })
+    }
+
+    /// Get the affine transformation described by the `srow_*` fields
+    /// (sform, method 3), regardless of `sform_code`.
+    pub fn sform_affine(&self) -> Affine {
+        let mut a = affine::identity();
+        for (row, srow) in a.iter_mut().zip(&[self.srow_x, self.srow_y, self.srow_z]) {
+            for (v, s) in row.iter_mut().zip(srow) {
+                *v = f64::from(*s);
+            }
+        }
+        a
+    }
+
+    /// Get the affine transformation from voxel indices to world
+    /// coordinates. The sform is preferred if `sform_code` is set, followed
+    /// by the qform if `qform_code` is set. Otherwise, the volume is assumed
+    /// to be scaled by `pixdim` without any rotation (method 1).
+    pub fn affine(&self) -> Affine {
+        if self.sform_code > 0 {
+            self.sform_affine()
+        } else if self.qform_code > 0 {
+            self.qform_affine()
+        } else {
+            let mut a = affine::identity();
+            for (i, row) in a.iter_mut().enumerate().take(3) {
+                row[i] = f64::from(self.pixdim[i + 1]);
+            }
+            a
+        }
+    }
+
+    /// Replace both the qform and the sform with the given affine
+    /// transformation. The voxel spacing in `pixdim` is updated accordingly.
+    /// Transformation codes which were previously unset are changed to
+    /// `XForm::AlignedAnat`, so that the new affine is effective.
+    pub fn set_affine(&mut self, affine: &Affine) {
+        for (srow, row) in [&mut self.srow_x, &mut self.srow_y, &mut self.srow_z]
+            .iter_mut()
+            .zip(affine)
+        {
+            for (s, v) in srow.iter_mut().zip(row) {
+                *s = *v as f32;
+            }
+        }
+
+        let q = affine::to_quaternion(affine);
+        self.quatern_b = q.b as f32;
+        self.quatern_c = q.c as f32;
+        self.quatern_d = q.d as f32;
+        self.quatern_x = q.x as f32;
+        self.quatern_y = q.y as f32;
+        self.quatern_z = q.z as f32;
+        self.pixdim[0] = q.qfac as f32;
+        for (p, s) in self.pixdim[1..4].iter_mut().zip(&q.spacing) {
+            *p = *s as f32;
+        }
+
+        if self.qform_code <= 0 {
+            self.qform_code = XForm::AlignedAnat as i16;
+        }
+        if self.sform_code <= 0 {
+            self.sform_code = XForm::AlignedAnat as i16;
+        }
+    }
+
+    /// Get the obliquity of the volume's affine transformation, in degrees.
+    /// This is the largest angle between a voxel axis and the closest world
+    /// axis, equivalent to AFNI's `3dinfo -obliquity`.
+    pub fn obliquity(&self) -> f64 {
+        affine::obliquity(&self.affine())
+    }
+
+    /// Check whether the volume is oblique, meaning that its obliquity
+    /// surpasses `OBLIQUITY_THRESHOLD`.
+    pub fn is_oblique(&self) -> bool {
+        self.obliquity() > OBLIQUITY_THRESHOLD
+    }
+}
+
+/// Slice acquisition parameters of a volume, as described by the
+/// `dim_info`, `slice_code`, `slice_start`, `slice_end` and `slice_duration`
+/// fields of the header (see `NiftiHeader::slice_info`).
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SliceInfo {
+    /// The zero-based slice acquisition axis
+    pub axis: u16,
+    /// The slice acquisition order
+    pub order: SliceOrder,
+    /// The total number of slices along the slice axis
+    pub num_slices: u16,
+    /// The first slice index in the acquisition pattern
+    pub start: u16,
+    /// The last slice index in the acquisition pattern (inclusive)
+    pub end: u16,
+    /// Time to acquire one slice, in seconds
+    pub duration: f64,
+}
+
+fn dim_info_axis(code: u8) -> Option<u16> {
+    match code & 0b11 {
+        0 => None,
+        c => Some(u16::from(c) - 1),
+    }
+}
+
+fn dim_info_code(axis: Option<u16>) -> Result<u8> {
+    match axis {
+        None => Ok(0),
+        Some(a) if a < 3 => Ok(a as u8 + 1),
+        Some(a) => Err(NiftiError::AxisOutOfBounds(a)),
+    }
+}
+
+impl SliceInfo {
+    /// Get the acquisition time of each slice along the slice axis, in
+    /// seconds. Slices outside of the `start..=end` range have no time. If
+    /// the slice duration is zero, `repetition_time / (end - start + 1)` is
+    /// used instead.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::MissingSliceInfo` if the slice order is unknown, or
+    ///   the slice duration cannot be determined.
+    pub fn slice_times(&self, repetition_time: f64) -> Result<Vec<Option<f64>>> {
+        let n = self.end - self.start + 1;
+        let duration = if self.duration > 0. {
+            self.duration
+        } else if repetition_time > 0. {
+            repetition_time / f64::from(n)
+        } else {
+            return Err(NiftiError::MissingSliceInfo);
+        };
+        let times = self
+            .order
+            .slice_times(n, duration)
+            .ok_or(NiftiError::MissingSliceInfo)?;
+
+        let mut out = vec![None; self.num_slices as usize];
+        for (o, t) in out[self.start as usize..].iter_mut().zip(times) {
+            *o = Some(t);
+        }
+        Ok(out)
+    }
 }
 
 fn parse_header_1<S: Read>(mut input: S) -> Result<NiftiHeader> {
@@ -364,9 +873,97 @@ fn parse_header_2<B: ByteOrder, S: Read>(mut h: NiftiHeader, mut input: S) -> Re
 
     debug_assert_eq!(h.descrip.len(), 80);
 
-    if &h.magic != MAGIC_CODE_NI1 && &h.magic != MAGIC_CODE_NIP1 {
-        Err(NiftiError::InvalidFormat)
-    } else {
-        Ok(h)
+    Ok(h)
+}
+
+/// The world directions (RAS+) in which the voxel axes increase, for each
+/// Analyze 7.5 `orient` code: transverse, coronal and sagittal, unflipped
+/// then flipped. Unflipped transverse images are in radiological order.
+const ANALYZE_ORIENTATIONS: [[[f64; 3]; 3]; 6] = [
+    [[-1., 0., 0.], [0., 1., 0.], [0., 0., 1.]],
+    [[-1., 0., 0.], [0., 0., 1.], [0., 1., 0.]],
+    [[0., 1., 0.], [0., 0., 1.], [1., 0., 0.]],
+    [[-1., 0., 0.], [0., -1., 0.], [0., 0., 1.]],
+    [[-1., 0., 0.], [0., 0., -1.], [0., 1., 0.]],
+    [[0., 1., 0.], [0., 0., 1.], [-1., 0., 0.]],
+];
+
+/// Build a NIfTI-1 header from an Analyze 7.5 header, which was parsed
+/// with the NIfTI-1 layout from the given raw bytes.
+fn analyze_to_nifti(analyze: &NiftiHeader, raw: &[u8]) -> Result<NiftiHeader> {
+    let e = analyze.endianness;
+    let orient = raw[252];
+    let mut originator = [0i16; 3];
+    for (i, o) in originator.iter_mut().enumerate() {
+        *o = e.read_i16(&raw[253 + 2 * i..])?;
+    }
+    // `funused1` shares its location with `scl_slope`
+    let scale = analyze.scl_slope;
+
+    let mut h = NiftiHeader {
+        sizeof_hdr: 348,
+        data_type: analyze.data_type,
+        db_name: analyze.db_name,
+        extents: analyze.extents,
+        session_error: analyze.session_error,
+        regular: analyze.regular,
+        dim: analyze.dim,
+        datatype: analyze.datatype,
+        bitpix: analyze.bitpix,
+        pixdim: analyze.pixdim,
+        vox_offset: analyze.vox_offset.max(0.),
+        scl_slope: if scale.is_normal() { scale } else { 0. },
+        cal_max: analyze.cal_max,
+        cal_min: analyze.cal_min,
+        glmax: analyze.glmax,
+        glmin: analyze.glmin,
+        descrip: analyze.descrip.clone(),
+        aux_file: analyze.aux_file,
+        magic: *MAGIC_CODE_NI1,
+        endianness: e,
+        ..Default::default()
+    };
+    h.set_xyzt_units(Unit::Mm, Unit::Sec)?;
+
+    let directions = ANALYZE_ORIENTATIONS
+        .get(orient as usize)
+        .unwrap_or(&ANALYZE_ORIENTATIONS[0]);
+    let mut dims = [1.; 3];
+    let mut spacing = [1.; 3];
+    for i in 0..3 {
+        if i < h.dim[0] as usize && h.dim[i + 1] > 0 {
+            dims[i] = f64::from(h.dim[i + 1]);
+        }
+        let s = f64::from(h.pixdim[i + 1]);
+        if s.is_normal() {
+            spacing[i] = s;
+        }
+    }
+    // zero-based voxel coordinates of the origin
+    let valid_origin = originator.iter().any(|&o| o != 0)
+        && originator
+            .iter()
+            .zip(&dims)
+            .all(|(&o, &d)| o >= 1 && f64::from(o) <= d);
+    let origin: Vec<f64> = originator
+        .iter()
+        .zip(&dims)
+        .map(|(&o, &d)| {
+            if valid_origin {
+                f64::from(o) - 1.
+            } else {
+                ((d + 1.) / 2.).floor() - 1.
+            }
+        })
+        .collect();
+
+    let mut a = affine::identity();
+    for (r, row) in a.iter_mut().enumerate().take(3) {
+        for j in 0..3 {
+            row[j] = directions[j][r] * spacing[j];
+        }
+        row[3] = -(0..3).map(|j| row[j] * origin[j]).sum::<f64>();
     }
+    h.set_affine(&a);
+    Ok(h)
 }
@@ -0,0 +1,481 @@
+//! This module defines `IntentSpec`, a typed representation of a header's
+//! intent: the intent code together with the meaning of the intent
+//! parameters (`intent_p1`, `intent_p2` and `intent_p3`) and, where
+//! relevant, of `intent_name`.
+//!
+//! # Examples
+//!
+//! ```
+//! use nifti::{IntentSpec, NiftiHeader};
+//! # use nifti::Result;
+//!
+//! # fn run() -> Result<()> {
+//! let mut hdr = NiftiHeader::default();
+//! hdr.set_intent_spec(&IntentSpec::Ftest { dof_num: 2., dof_den: 30. })?;
+//! assert_eq!(hdr.intent_code, 4);
+//! assert_eq!((hdr.intent_p1, hdr.intent_p2), (2., 30.));
+//! assert_eq!(hdr.intent_spec()?, IntentSpec::Ftest { dof_num: 2., dof_den: 30. });
+//! # Ok(())
+//! # }
+//! # run().unwrap();
+//! ```
+
+use error::{NiftiError, Result};
+use typedef::Intent;
+
+/// An intent with its parameters. Statistical distributions hold their
+/// parameters as they are stored in the header, while data set intents
+/// may hold the dimensions of the per-voxel data and the intent name.
+#[derive(Debug, PartialEq, Clone)]
+pub enum IntentSpec {
+    /// No intention is indicated in the header.
+    None,
+    /// Correlation coefficient.
+    Correl {
+        /// Degrees of freedom
+        dof: f32,
+    },
+    /// Student t statistic.
+    Ttest {
+        /// Degrees of freedom
+        dof: f32,
+    },
+    /// Fisher F statistic.
+    Ftest {
+        /// Numerator degrees of freedom
+        dof_num: f32,
+        /// Denominator degrees of freedom
+        dof_den: f32,
+    },
+    /// Standard normal.
+    Zscore,
+    /// Chi-squared.
+    Chisq {
+        /// Degrees of freedom
+        dof: f32,
+    },
+    /// Beta distribution.
+    Beta {
+        /// First shape parameter
+        a: f32,
+        /// Second shape parameter
+        b: f32,
+    },
+    /// Binomial distribution.
+    Binom {
+        /// Number of trials
+        trials: f32,
+        /// Probability per trial
+        prob: f32,
+    },
+    /// Gamma distribution.
+    Gamma {
+        /// Shape parameter
+        shape: f32,
+        /// Scale (rate) parameter
+        scale: f32,
+    },
+    /// Poisson distribution.
+    Poisson {
+        /// Mean
+        mean: f32,
+    },
+    /// Normal distribution.
+    Normal {
+        /// Mean
+        mean: f32,
+        /// Standard deviation
+        std_dev: f32,
+    },
+    /// Noncentral F statistic.
+    FtestNonc {
+        /// Numerator degrees of freedom
+        dof_num: f32,
+        /// Denominator degrees of freedom
+        dof_den: f32,
+        /// Numerator noncentrality parameter
+        noncentrality: f32,
+    },
+    /// Noncentral chi-squared statistic.
+    ChisqNonc {
+        /// Degrees of freedom
+        dof: f32,
+        /// Noncentrality parameter
+        noncentrality: f32,
+    },
+    /// Logistic distribution.
+    Logistic {
+        /// Location
+        location: f32,
+        /// Scale
+        scale: f32,
+    },
+    /// Laplace distribution.
+    Laplace {
+        /// Location
+        location: f32,
+        /// Scale
+        scale: f32,
+    },
+    /// Uniform distribution.
+    Uniform {
+        /// Lower end
+        lower: f32,
+        /// Upper end
+        upper: f32,
+    },
+    /// Noncentral t statistic.
+    TtestNonc {
+        /// Degrees of freedom
+        dof: f32,
+        /// Noncentrality parameter
+        noncentrality: f32,
+    },
+    /// Weibull distribution.
+    Weibull {
+        /// Location
+        location: f32,
+        /// Scale
+        scale: f32,
+        /// Power
+        power: f32,
+    },
+    /// Chi distribution.
+    Chi {
+        /// Degrees of freedom
+        dof: f32,
+    },
+    /// Inverse Gaussian.
+    Invgauss {
+        /// Mean (mu)
+        mu: f32,
+        /// Shape (lambda)
+        lambda: f32,
+    },
+    /// Extreme value type I.
+    Extval {
+        /// Location
+        location: f32,
+        /// Scale
+        scale: f32,
+    },
+    /// Data is a p-value.
+    Pval,
+    /// Data is ln(p-value).
+    Logpval,
+    /// Data is log10(p-value).
+    Log10pval,
+    /// Each voxel is an estimate of some parameter.
+    Estimate {
+        /// Name of the estimated parameter (`intent_name`)
+        name: String,
+    },
+    /// Each voxel is an index into a set of labels. The file name of the
+    /// label table may be stored in `aux_file`.
+    Label {
+        /// Name of the label set (`intent_name`)
+        name: String,
+    },
+    /// Each voxel is an index into the NeuroNames label set.
+    Neuroname,
+    /// Each voxel holds a `rows` x `cols` matrix, in row-major order.
+    Genmatrix {
+        /// Number of rows
+        rows: u16,
+        /// Number of columns
+        cols: u16,
+    },
+    /// Each voxel holds the lower triangle of an `n` x `n` symmetric matrix,
+    /// row by row.
+    Symmatrix {
+        /// Number of rows and columns
+        n: u16,
+    },
+    /// Each voxel holds a displacement vector.
+    Dispvect,
+    /// Each voxel holds a vector.
+    Vector,
+    /// Each voxel holds a spatial coordinate.
+    Pointset {
+        /// Object which the points come from (`intent_name`)
+        name: String,
+    },
+    /// Each voxel holds a triple of indices into a point set.
+    Triangle,
+    /// Each voxel holds a quaternion.
+    Quaternion,
+    /// Dimensionless value.
+    Dimless {
+        /// Name of the value (`intent_name`)
+        name: String,
+    },
+    /// Each location is from a time series.
+    TimeSeries,
+    /// Each location is a node index.
+    NodeIndex,
+    /// Each location holds an RGB triplet.
+    RgbVector,
+    /// Each location holds an RGBA vector.
+    RgbaVector,
+    /// Each location holds a shape value.
+    Shape,
+}
+
+impl IntentSpec {
+    /// Build an intent specification from an intent code, the three intent
+    /// parameters and the intent name. Parameters and names which are not
+    /// meaningful for the intent are ignored.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::InvalidIntentParameters` if the parameters are out of
+    ///   range for the intent (see `validate`).
+    pub fn from_parts(intent: Intent, params: [f32; 3], name: &str) -> Result<IntentSpec> {
+        let [p1, p2, p3] = params;
+        let name = name.to_string();
+        let dim = |v: f32| {
+            if v >= 1. && v <= f32::from(u16::MAX) && v.fract() == 0. {
+                Ok(v as u16)
+            } else {
+                Err(NiftiError::InvalidIntentParameters(intent))
+            }
+        };
+
+        let spec = match intent {
+            Intent::None => IntentSpec::None,
+            Intent::Correl => IntentSpec::Correl { dof: p1 },
+            Intent::Ttest => IntentSpec::Ttest { dof: p1 },
+            Intent::Ftest => IntentSpec::Ftest {
+                dof_num: p1,
+                dof_den: p2,
+            },
+            Intent::Zscore => IntentSpec::Zscore,
+            Intent::Chisq => IntentSpec::Chisq { dof: p1 },
+            Intent::Beta => IntentSpec::Beta { a: p1, b: p2 },
+            Intent::Binom => IntentSpec::Binom {
+                trials: p1,
+                prob: p2,
+            },
+            Intent::Gamma => IntentSpec::Gamma {
+                shape: p1,
+                scale: p2,
+            },
+            Intent::Poisson => IntentSpec::Poisson { mean: p1 },
+            Intent::Normal => IntentSpec::Normal {
+                mean: p1,
+                std_dev: p2,
+            },
+            Intent::FtestNonc => IntentSpec::FtestNonc {
+                dof_num: p1,
+                dof_den: p2,
+                noncentrality: p3,
+            },
+            Intent::ChisqNonc => IntentSpec::ChisqNonc {
+                dof: p1,
+                noncentrality: p2,
+            },
+            Intent::Logistic => IntentSpec::Logistic {
+                location: p1,
+                scale: p2,
+            },
+            Intent::Laplace => IntentSpec::Laplace {
+                location: p1,
+                scale: p2,
+            },
+            Intent::Uniform => IntentSpec::Uniform {
+                lower: p1,
+                upper: p2,
+            },
+            Intent::TtestNonc => IntentSpec::TtestNonc {
+                dof: p1,
+                noncentrality: p2,
+            },
+            Intent::Weibull => IntentSpec::Weibull {
+                location: p1,
+                scale: p2,
+                power: p3,
+            },
+            Intent::Chi => IntentSpec::Chi { dof: p1 },
+            Intent::Invgauss => IntentSpec::Invgauss { mu: p1, lambda: p2 },
+            Intent::Extval => IntentSpec::Extval {
+                location: p1,
+                scale: p2,
+            },
+            Intent::Pval => IntentSpec::Pval,
+            Intent::Logpval => IntentSpec::Logpval,
+            Intent::Log10pval => IntentSpec::Log10pval,
+            Intent::Estimate => IntentSpec::Estimate { name },
+            Intent::Label => IntentSpec::Label { name },
+            Intent::Neuroname => IntentSpec::Neuroname,
+            Intent::Genmatrix => IntentSpec::Genmatrix {
+                rows: dim(p1)?,
+                cols: dim(p2)?,
+            },
+            Intent::Symmatrix => IntentSpec::Symmatrix { n: dim(p1)? },
+            Intent::Dispvect => IntentSpec::Dispvect,
+            Intent::Vector => IntentSpec::Vector,
+            Intent::Pointset => IntentSpec::Pointset { name },
+            Intent::Triangle => IntentSpec::Triangle,
+            Intent::Quaternion => IntentSpec::Quaternion,
+            Intent::Dimless => IntentSpec::Dimless { name },
+            Intent::TimeSeries => IntentSpec::TimeSeries,
+            Intent::NodeIndex => IntentSpec::NodeIndex,
+            Intent::RgbVector => IntentSpec::RgbVector,
+            Intent::RgbaVector => IntentSpec::RgbaVector,
+            Intent::Shape => IntentSpec::Shape,
+        };
+        spec.validate()?;
+        Ok(spec)
+    }
+
+    /// Get the intent code of this specification.
+    pub fn intent(&self) -> Intent {
+        match *self {
+            IntentSpec::None => Intent::None,
+            IntentSpec::Correl { .. } => Intent::Correl,
+            IntentSpec::Ttest { .. } => Intent::Ttest,
+            IntentSpec::Ftest { .. } => Intent::Ftest,
+            IntentSpec::Zscore => Intent::Zscore,
+            IntentSpec::Chisq { .. } => Intent::Chisq,
+            IntentSpec::Beta { .. } => Intent::Beta,
+            IntentSpec::Binom { .. } => Intent::Binom,
+            IntentSpec::Gamma { .. } => Intent::Gamma,
+            IntentSpec::Poisson { .. } => Intent::Poisson,
+            IntentSpec::Normal { .. } => Intent::Normal,
+            IntentSpec::FtestNonc { .. } => Intent::FtestNonc,
+            IntentSpec::ChisqNonc { .. } => Intent::ChisqNonc,
+            IntentSpec::Logistic { .. } => Intent::Logistic,
+            IntentSpec::Laplace { .. } => Intent::Laplace,
+            IntentSpec::Uniform { .. } => Intent::Uniform,
+            IntentSpec::TtestNonc { .. } => Intent::TtestNonc,
+            IntentSpec::Weibull { .. } => Intent::Weibull,
+            IntentSpec::Chi { .. } => Intent::Chi,
+            IntentSpec::Invgauss { .. } => Intent::Invgauss,
+            IntentSpec::Extval { .. } => Intent::Extval,
+            IntentSpec::Pval => Intent::Pval,
+            IntentSpec::Logpval => Intent::Logpval,
+            IntentSpec::Log10pval => Intent::Log10pval,
+            IntentSpec::Estimate { .. } => Intent::Estimate,
+            IntentSpec::Label { .. } => Intent::Label,
+            IntentSpec::Neuroname => Intent::Neuroname,
+            IntentSpec::Genmatrix { .. } => Intent::Genmatrix,
+            IntentSpec::Symmatrix { .. } => Intent::Symmatrix,
+            IntentSpec::Dispvect => Intent::Dispvect,
+            IntentSpec::Vector => Intent::Vector,
+            IntentSpec::Pointset { .. } => Intent::Pointset,
+            IntentSpec::Triangle => Intent::Triangle,
+            IntentSpec::Quaternion => Intent::Quaternion,
+            IntentSpec::Dimless { .. } => Intent::Dimless,
+            IntentSpec::TimeSeries => Intent::TimeSeries,
+            IntentSpec::NodeIndex => Intent::NodeIndex,
+            IntentSpec::RgbVector => Intent::RgbVector,
+            IntentSpec::RgbaVector => Intent::RgbaVector,
+            IntentSpec::Shape => Intent::Shape,
+        }
+    }
+
+    /// Get the values of `intent_p1`, `intent_p2` and `intent_p3` for this
+    /// specification. Unused parameters are zero.
+    pub fn params(&self) -> [f32; 3] {
+        match *self {
+            IntentSpec::Correl { dof }
+            | IntentSpec::Ttest { dof }
+            | IntentSpec::Chisq { dof }
+            | IntentSpec::Chi { dof } => [dof, 0., 0.],
+            IntentSpec::Poisson { mean } => [mean, 0., 0.],
+            IntentSpec::Ftest { dof_num, dof_den } => [dof_num, dof_den, 0.],
+            IntentSpec::Beta { a, b } => [a, b, 0.],
+            IntentSpec::Binom { trials, prob } => [trials, prob, 0.],
+            IntentSpec::Gamma { shape, scale } => [shape, scale, 0.],
+            IntentSpec::Normal { mean, std_dev } => [mean, std_dev, 0.],
+            IntentSpec::FtestNonc {
+                dof_num,
+                dof_den,
+                noncentrality,
+            } => [dof_num, dof_den, noncentrality],
+            IntentSpec::ChisqNonc { dof, noncentrality }
+            | IntentSpec::TtestNonc { dof, noncentrality } => [dof, noncentrality, 0.],
+            IntentSpec::Logistic { location, scale }
+            | IntentSpec::Laplace { location, scale }
+            | IntentSpec::Extval { location, scale } => [location, scale, 0.],
+            IntentSpec::Uniform { lower, upper } => [lower, upper, 0.],
+            IntentSpec::Weibull {
+                location,
+                scale,
+                power,
+            } => [location, scale, power],
+            IntentSpec::Invgauss { mu, lambda } => [mu, lambda, 0.],
+            IntentSpec::Genmatrix { rows, cols } => [f32::from(rows), f32::from(cols), 0.],
+            IntentSpec::Symmatrix { n } => [f32::from(n), 0., 0.],
+            _ => [0.; 3],
+        }
+    }
+
+    /// Get the intent name held by this specification, if the intent
+    /// gives a meaning to `intent_name`.
+    pub fn name(&self) -> Option<&str> {
+        match *self {
+            IntentSpec::Estimate { ref name }
+            | IntentSpec::Label { ref name }
+            | IntentSpec::Pointset { ref name }
+            | IntentSpec::Dimless { ref name } => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Check whether the parameters are within the range accepted by the
+    /// intent: degrees of freedom, scales and shape parameters must be
+    /// positive, noncentrality parameters non-negative, probabilities
+    /// between 0 and 1, the lower end of a uniform distribution below its
+    /// upper end, and matrix dimensions at least 1. Names must fit in the
+    /// 16 bytes of `intent_name`.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::InvalidIntentParameters` if any of the conditions
+    ///   above is not met, or if any parameter is not finite.
+    pub fn validate(&self) -> Result<()> {
+        let positive = |v: f32| v > 0.;
+        let non_negative = |v: f32| v >= 0.;
+        let valid = self.params().iter().all(|p| p.is_finite())
+            && self.name().map(str::len).unwrap_or(0) <= 16
+            && match *self {
+                IntentSpec::Correl { dof }
+                | IntentSpec::Ttest { dof }
+                | IntentSpec::Chisq { dof }
+                | IntentSpec::Chi { dof } => positive(dof),
+                IntentSpec::Poisson { mean } => positive(mean),
+                IntentSpec::Ftest { dof_num, dof_den } => positive(dof_num) && positive(dof_den),
+                IntentSpec::Beta { a, b } => positive(a) && positive(b),
+                IntentSpec::Binom { trials, prob } => {
+                    positive(trials) && (0. ..=1.).contains(&prob)
+                }
+                IntentSpec::Gamma { shape, scale } => positive(shape) && positive(scale),
+                IntentSpec::Normal { std_dev, .. } => positive(std_dev),
+                IntentSpec::FtestNonc {
+                    dof_num,
+                    dof_den,
+                    noncentrality,
+                } => positive(dof_num) && positive(dof_den) && non_negative(noncentrality),
+                IntentSpec::ChisqNonc { dof, noncentrality } => {
+                    positive(dof) && non_negative(noncentrality)
+                }
+                IntentSpec::TtestNonc { dof, .. } => positive(dof),
+                IntentSpec::Logistic { scale, .. }
+                | IntentSpec::Laplace { scale, .. }
+                | IntentSpec::Extval { scale, .. } => positive(scale),
+                IntentSpec::Uniform { lower, upper } => lower < upper,
+                IntentSpec::Weibull { scale, power, .. } => positive(scale) && positive(power),
+                IntentSpec::Invgauss { mu, lambda } => positive(mu) && positive(lambda),
+                IntentSpec::Genmatrix { rows, cols } => rows > 0 && cols > 0,
+                IntentSpec::Symmatrix { n } => n > 0,
+                _ => true,
+            };
+
+        if valid {
+            Ok(())
+        } else {
+            Err(NiftiError::InvalidIntentParameters(self.intent()))
+        }
+    }
+}
@@ -0,0 +1,258 @@
+//! This module provides label tables for label maps (`Intent::Label`
+//! volumes, such as atlases and segmentations), which associate each
+//! integer voxel value with a name and a colour.
+//!
+//! Label tables are read from and written to text in the format of
+//! FreeSurfer's `FreeSurferColorLUT.txt`: one label per line, with the
+//! value, the name and the red, green, blue and alpha components, separated
+//! by white space. Lines starting with `#` are comments. The same text can
+//! be embedded in a NIfTI extension with the FreeSurfer code (14).
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use nifti::{InMemNiftiObject, LabelTable, NiftiObject};
+//! # use nifti::Result;
+//!
+//! # fn run() -> Result<()> {
+//! let obj = InMemNiftiObject::from_file("aseg.nii.gz")?;
+//! let table = LabelTable::from_file("FreeSurferColorLUT.txt")?;
+//! for summary in table.summary(obj.volume(), obj.header())? {
+//!     println!("{:?}: {} mm³", summary.name, summary.volume_mm3);
+//! }
+//! let hippocampus = table.mask(obj.volume(), "Left-Hippocampus")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use error::{NiftiError, Result};
+use extension::{Extension, ExtensionSequence};
+use header::NiftiHeader;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use typedef::{ExtensionCode, NiftiType};
+use volume::{InMemNiftiVolume, NiftiVolume};
+
+/// The extension code under which label tables are embedded (FreeSurfer).
+pub const LABEL_TABLE_ECODE: i32 = ExtensionCode::Freesurfer as i32;
+
+/// A single entry of a label table.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Label {
+    /// The voxel value of the label
+    pub value: i32,
+    /// The label's name
+    pub name: String,
+    /// The label's colour, as red, green, blue and alpha components. Note
+    /// that FreeSurfer look-up tables usually hold zero in the last
+    /// component.
+    pub color: [u8; 4],
+}
+
+/// A table of labels, indexed by voxel value.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct LabelTable {
+    labels: BTreeMap<i32, Label>,
+}
+
+/// The number of voxels (and respective volume) holding a given value in a
+/// label map.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LabelSummary {
+    /// The voxel value
+    pub value: i32,
+    /// The label's name, if the value is in the label table
+    pub name: Option<String>,
+    /// The number of voxels with this value
+    pub voxels: usize,
+    /// The total volume of these voxels, in cubic millimeters
+    pub volume_mm3: f64,
+}
+
+impl LabelTable {
+    /// Create an empty label table.
+    pub fn new() -> Self {
+        LabelTable::default()
+    }
+
+    /// Read a label table from a `ColorLUT` text file.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::InvalidLabelTable` if a line is not a valid entry.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        LabelTable::from_color_lut(BufReader::new(File::open(path)?))
+    }
+
+    /// Read a label table from `ColorLUT` text. The alpha component may be
+    /// omitted, in which case it is zero.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::InvalidLabelTable` if a line is not a valid entry.
+    pub fn from_color_lut<R: BufRead>(source: R) -> Result<Self> {
+        let mut table = LabelTable::new();
+        for (i, line) in source.lines().enumerate() {
+            let line = line?;
+            let line = line.trim_matches(|c: char| c.is_whitespace() || c == '\0');
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || NiftiError::InvalidLabelTable(i + 1);
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 5 || fields.len() > 6 {
+                return Err(invalid());
+            }
+            let value = fields[0].parse().map_err(|_| invalid())?;
+            let mut color = [0u8; 4];
+            for (c, field) in color.iter_mut().zip(&fields[2..]) {
+                *c = field.parse().map_err(|_| invalid())?;
+            }
+            let _ = table.insert(Label {
+                value,
+                name: fields[1].to_string(),
+                color,
+            });
+        }
+        Ok(table)
+    }
+
+    /// Write the label table as `ColorLUT` text.
+    pub fn to_color_lut<W: Write>(&self, mut dst: W) -> Result<()> {
+        writeln!(dst, "#No. Label Name                        R   G   B   A")?;
+        for label in self.iter() {
+            let [r, g, b, a] = label.color;
+            writeln!(
+                dst,
+                "{:<4} {:<32} {:>3} {:>3} {:>3} {:>3}",
+                label.value, label.name, r, g, b, a
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Read a label table embedded in an extension.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::InvalidLabelTable` if the extension's data is not a
+    ///   valid label table.
+    pub fn from_extension(extension: &Extension) -> Result<Self> {
+        LabelTable::from_color_lut(extension.data().as_slice())
+    }
+
+    /// Read the label table embedded in the first extension with the
+    /// FreeSurfer code (`LABEL_TABLE_ECODE`), if any.
+    pub fn from_extensions(extensions: &ExtensionSequence) -> Option<Result<Self>> {
+        extensions
+            .iter()
+            .find(|e| e.code() == LABEL_TABLE_ECODE)
+            .map(LabelTable::from_extension)
+    }
+
+    /// Embed the label table in an extension with the FreeSurfer code
+    /// (`LABEL_TABLE_ECODE`). The text is padded with NUL characters, so
+    /// that the size of the extension is a multiple of 16 bytes.
+    pub fn to_extension(&self) -> Extension {
+        let mut data = Vec::new();
+        self.to_color_lut(&mut data)
+            .expect("writing to a vector should not fail");
+        Extension::from_data(LABEL_TABLE_ECODE, data)
+    }
+
+    /// Add a label to the table, returning the label which previously had
+    /// the same value, if any.
+    pub fn insert(&mut self, label: Label) -> Option<Label> {
+        self.labels.insert(label.value, label)
+    }
+
+    /// Get the label with the given voxel value.
+    pub fn get(&self, value: i32) -> Option<&Label> {
+        self.labels.get(&value)
+    }
+
+    /// Get the first label with the given name.
+    pub fn find(&self, name: &str) -> Option<&Label> {
+        self.iter().find(|l| l.name == name)
+    }
+
+    /// Obtain an iterator to the labels, by increasing value.
+    pub fn iter(&self) -> ::std::collections::btree_map::Values<'_, i32, Label> {
+        self.labels.values()
+    }
+
+    /// Whether the table is empty.
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    /// Obtain the number of labels in the table.
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    /// Count the voxels of each value present in a label map, by increasing
+    /// value. Voxel values are rounded to the nearest integer, and the
+    /// volume of each voxel is obtained from the header (see
+    /// `NiftiHeader::voxel_volume_mm3`).
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::IncompatibleUnit` if the spatial unit of the header
+    ///   is not a length.
+    pub fn summary<V>(&self, volume: &V, header: &NiftiHeader) -> Result<Vec<LabelSummary>>
+    where
+        V: NiftiVolume,
+    {
+        let voxel_volume = header.voxel_volume_mm3()?;
+        let mut counts: BTreeMap<i32, usize> = BTreeMap::new();
+        let dim = volume.dim();
+        let total: usize = dim.iter().map(|d| *d as usize).product();
+        let mut coords = vec![0u16; dim.len()];
+        for _ in 0..total {
+            let value = volume.get_f64(&coords)?.round() as i32;
+            *counts.entry(value).or_insert(0) += 1;
+            // advance in column major order
+            for (c, d) in coords.iter_mut().zip(dim) {
+                *c += 1;
+                if *c < *d {
+                    break;
+                }
+                *c = 0;
+            }
+        }
+
+        Ok(counts
+            .into_iter()
+            .map(|(value, voxels)| LabelSummary {
+                value,
+                name: self.get(value).map(|l| l.name.clone()),
+                voxels,
+                volume_mm3: voxels as f64 * voxel_volume,
+            })
+            .collect())
+    }
+
+    /// Extract a binary mask of the label with the given name from a label
+    /// map. The mask is an unsigned 8-bit volume, holding 1 where the
+    /// (rounded) voxel value is the label's value, and 0 elsewhere.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::UnknownLabel` if no label has the given name.
+    pub fn mask(&self, volume: &InMemNiftiVolume, name: &str) -> Result<InMemNiftiVolume> {
+        let value = self
+            .find(name)
+            .ok_or_else(|| NiftiError::UnknownLabel(name.to_string()))?
+            .value;
+        volume.map_values(NiftiType::Uint8, |v| {
+            if v.round() as i32 == value {
+                1.
+            } else {
+                0.
+            }
+        })
+    }
+}
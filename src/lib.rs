@@ -47,32 +47,57 @@
 //!
 #![deny(missing_debug_implementations)]
 #![warn(missing_docs, unused_extern_crates, trivial_casts, unused_results)]
+#![recursion_limit = "256"]
 
 #[macro_use] extern crate quick_error;
 #[macro_use] extern crate num_derive;
 #[macro_use] extern crate derive_builder;
 #[cfg(feature = "ndarray_volumes")] extern crate ndarray;
+#[cfg(feature = "bids")] extern crate serde_json;
+#[cfg(feature = "bzip2")] extern crate bzip2;
+#[cfg(feature = "xz2")] extern crate xz2;
+#[cfg(feature = "zstd")] extern crate zstd;
+#[cfg(feature = "gifti")] extern crate base64;
+#[cfg(any(feature = "afni", feature = "cifti", feature = "gifti"))] extern crate xml;
 
 extern crate byteorder;
 extern crate flate2;
 extern crate num_traits;
 extern crate safe_transmute;
 
+pub mod affine;
+#[cfg(feature = "afni")] pub mod afni;
+#[cfg(feature = "bids")] pub mod bids;
+#[cfg(feature = "cifti")] pub mod cifti;
+#[cfg(feature = "gifti")] pub mod gifti;
+pub mod dicom;
 pub mod extension;
 pub mod header;
+pub mod intent;
+pub mod label;
+pub mod mgh;
+pub mod npy;
+pub mod nrrd;
 pub mod object;
+pub mod stats;
 pub mod volume;
 pub mod error;
 pub mod typedef;
 #[cfg(feature = "ndarray_volumes")] pub mod writer;
 mod util;
+#[cfg(any(feature = "afni", feature = "cifti", feature = "gifti"))] mod dom;
 
 pub use error::{NiftiError, Result};
 pub use object::{NiftiObject, InMemNiftiObject};
 pub use extension::{Extender, Extension, ExtensionSequence};
-pub use header::{NiftiHeader, NiftiHeaderBuilder};
+pub use header::{NiftiHeader, NiftiHeaderBuilder, SliceInfo};
+pub use intent::IntentSpec;
+pub use label::{Label, LabelSummary, LabelTable};
 pub use volume::{NiftiVolume, InMemNiftiVolume, Sliceable};
+pub use volume::component::{ComponentLayout, ComponentVolume};
+pub use volume::resample::Interpolation;
+pub use volume::warp::{WarpConvention, WarpMode, WarpSpace};
 pub use volume::element::DataElement;
 #[cfg(feature = "ndarray_volumes")] pub use volume::ndarray::IntoNdArray;
-pub use typedef::{NiftiType, Unit, Intent, XForm, SliceOrder};
+pub use typedef::{NiftiType, Unit, Intent, XForm, SliceOrder, ExtensionCode};
 pub use util::Endianness;
@@ -0,0 +1,448 @@
+//! This module provides reading and writing of FreeSurfer MGH volumes
+//! (".mgh", or ".mgz" and ".mgh.gz" when GZip compressed).
+//!
+//! MGH volumes are read into the same `InMemNiftiObject` representation as
+//! NIfTI volumes, so that they can be inspected, processed and written as
+//! NIfTI without loss:
+//!
+//! - the voxel data keeps its data type and (big endian) byte order;
+//! - the voxel to RAS transformation (`vox2ras`) becomes both the qform and
+//!   the sform, in scanner coordinates;
+//! - the repetition time becomes `pixdim[4]`, in milliseconds;
+//! - the other scan parameters (echo time, inversion time, flip angle, field
+//!   of view and degrees of freedom) are kept in `descrip`, from which they
+//!   are restored when writing an MGH volume back.
+//!
+//! The tags which may follow the scan parameters (such as the command
+//! lines which produced the volume) are not kept.
+//!
+//! Since volume dimensions are 16-bit, MGH volumes with a dimension larger
+//! than 65535 cannot be read. This notably excludes surface overlays, which
+//! FreeSurfer stores with one vertex per column (`163842 x 1 x 1` for the
+//! `fsaverage` subject).
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use nifti::mgh;
+//! use nifti::{NiftiObject, NiftiVolume};
+//! # use nifti::Result;
+//!
+//! # fn run() -> Result<()> {
+//! let obj = mgh::read_file("subject/mri/brain.mgz")?;
+//! println!("{:?}", obj.volume().dim());
+//! println!("{:?}", obj.header().affine());
+//! mgh::write_file("brain_copy.mgz", &obj)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use affine::{self, Affine};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use error::{NiftiError, Result};
+use extension::ExtensionSequence;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use header::{NiftiHeader, MAGIC_CODE_NIP1};
+use object::{InMemNiftiObject, NiftiObject};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use typedef::{NiftiType, Unit, XForm};
+use util::{decompress, is_gz_file, Endianness};
+use volume::inmem::InMemNiftiVolume;
+use volume::NiftiVolume;
+
+/// Size of the MGH header, including the unused space before the data.
+const HEADER_SIZE: usize = 284;
+/// Size of the used part of the MGH header.
+const USED_HEADER_SIZE: usize = 90;
+
+/// Prefix of the `descrip` field holding the scan parameters.
+const DESCRIP_PREFIX: &str = "FreeSurfer";
+
+/// The header of an MGH volume, without the data type, which is that of
+/// the volume.
+#[derive(Debug, PartialEq, Clone)]
+pub struct MghHeader {
+    /// Number of voxels along each axis, then number of frames
+    pub dim: [usize; 4],
+    /// Degrees of freedom
+    pub dof: i32,
+    /// Voxel spacing along each axis, in millimeters
+    pub spacing: [f32; 3],
+    /// Direction cosines of each voxel axis, in RAS coordinates
+    pub directions: [[f32; 3]; 3],
+    /// RAS coordinates of the center of the volume (`c_ras`), in millimeters
+    pub center: [f32; 3],
+    /// Repetition time, in milliseconds
+    pub tr: f32,
+    /// Flip angle, in radians
+    pub flip_angle: f32,
+    /// Echo time, in milliseconds
+    pub te: f32,
+    /// Inversion time, in milliseconds
+    pub ti: f32,
+    /// Field of view, in millimeters
+    pub fov: f32,
+}
+
+impl Default for MghHeader {
+    /// A single voxel in FreeSurfer's default (coronal) orientation.
+    fn default() -> MghHeader {
+        MghHeader {
+            dim: [1; 4],
+            dof: 0,
+            spacing: [1.; 3],
+            directions: [[-1., 0., 0.], [0., 0., -1.], [0., 1., 0.]],
+            center: [0.; 3],
+            tr: 0.,
+            flip_angle: 0.,
+            te: 0.,
+            ti: 0.,
+            fov: 0.,
+        }
+    }
+}
+
+impl MghHeader {
+    /// Build an MGH header from a NIfTI header. The geometry is taken from
+    /// the header's affine transformation, the repetition time from
+    /// `pixdim[4]`, and the other scan parameters from `descrip`, if they
+    /// were stored there when reading an MGH volume.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::InvalidMgh` if the volume has more than four
+    ///   dimensions.
+    /// - `NiftiError::IncompatibleUnit` if the time unit is spectral.
+    pub fn from_nifti(header: &NiftiHeader) -> Result<MghHeader> {
+        let ndim = header.dim[0] as usize;
+        if ndim > 4 {
+            return Err(NiftiError::InvalidMgh(format!(
+                "cannot store {} dimensions",
+                ndim
+            )));
+        }
+        let mut mgh = MghHeader::default();
+        for (d, &n) in mgh.dim.iter_mut().zip(&header.dim[1..=ndim]) {
+            *d = usize::from(n);
+        }
+
+        let unit = header.xyzt_to_time()?;
+        let seconds = unit.seconds().ok_or(NiftiError::IncompatibleUnit(unit))?;
+        mgh.tr = (f64::from(header.pixdim[4]) * seconds * 1e3) as f32;
+        mgh.set_vox2ras(&header.affine());
+
+        let descrip = String::from_utf8_lossy(&header.descrip);
+        let mut words = descrip.trim_end_matches('\0').split_whitespace();
+        if words.next() == Some(DESCRIP_PREFIX) {
+            for word in words {
+                let mut kv = word.splitn(2, '=');
+                let (key, value) = match (kv.next(), kv.next()) {
+                    (Some(key), Some(value)) => (key, value),
+                    _ => continue,
+                };
+                let param = match key {
+                    "TE" => &mut mgh.te,
+                    "TI" => &mut mgh.ti,
+                    "flip" => &mut mgh.flip_angle,
+                    "FoV" => &mut mgh.fov,
+                    "dof" => {
+                        if let Ok(dof) = value.parse() {
+                            mgh.dof = dof;
+                        }
+                        continue;
+                    }
+                    _ => continue,
+                };
+                if let Ok(value) = value.parse() {
+                    *param = value;
+                }
+            }
+        }
+        Ok(mgh)
+    }
+
+    /// Get the voxel to RAS transformation (`vox2ras`).
+    pub fn vox2ras(&self) -> Affine {
+        let mut a = affine::identity();
+        for (r, row) in a.iter_mut().enumerate().take(3) {
+            for (j, v) in row.iter_mut().enumerate().take(3) {
+                *v = f64::from(self.directions[j][r]) * f64::from(self.spacing[j]);
+            }
+            let half: f64 = (0..3).map(|j| row[j] * self.dim[j] as f64 / 2.).sum();
+            row[3] = f64::from(self.center[r]) - half;
+        }
+        a
+    }
+
+    /// Set the spacing, the directions and the center from a voxel to RAS
+    /// transformation. Axes with a zero scale keep their direction.
+    pub fn set_vox2ras(&mut self, vox2ras: &Affine) {
+        for (j, (spacing, direction)) in self
+            .spacing
+            .iter_mut()
+            .zip(&mut self.directions)
+            .enumerate()
+        {
+            let column = [vox2ras[0][j], vox2ras[1][j], vox2ras[2][j]];
+            let norm = column.iter().map(|v| v * v).sum::<f64>().sqrt();
+            if norm > 0. {
+                *spacing = norm as f32;
+                for (d, v) in direction.iter_mut().zip(&column) {
+                    *d = (v / norm) as f32;
+                }
+            }
+        }
+        for (r, row) in vox2ras.iter().enumerate().take(3) {
+            let half: f64 = (0..3).map(|j| row[j] * self.dim[j] as f64 / 2.).sum();
+            self.center[r] = (row[3] + half) as f32;
+        }
+    }
+
+    /// Build the NIfTI header of a volume with this header.
+    fn to_nifti(&self, datatype: NiftiType) -> NiftiHeader {
+        let mut h = NiftiHeader {
+            dim: [1; 8],
+            datatype: datatype as i16,
+            bitpix: (datatype.size_of() * 8) as i16,
+            magic: *MAGIC_CODE_NIP1,
+            endianness: Endianness::BE,
+            ..Default::default()
+        };
+        h.dim[0] = if self.dim[3] > 1 { 4 } else { 3 };
+        for (d, &n) in h.dim[1..5].iter_mut().zip(&self.dim) {
+            *d = n as u16;
+        }
+        h.pixdim[4] = self.tr;
+        h.xyzt_units = Unit::Mm as u8 | Unit::Msec as u8;
+        h.set_affine(&self.vox2ras());
+        h.qform_code = XForm::ScannerAnat as i16;
+        h.sform_code = XForm::ScannerAnat as i16;
+
+        let mut descrip = DESCRIP_PREFIX.to_string();
+        for &(key, value) in &[
+            ("TE", self.te),
+            ("TI", self.ti),
+            ("flip", self.flip_angle),
+            ("FoV", self.fov),
+        ] {
+            if value != 0. {
+                descrip += &format!(" {}={}", key, value);
+            }
+        }
+        if self.dof != 0 {
+            descrip += &format!(" dof={}", self.dof);
+        }
+        if descrip.len() <= h.descrip.len() {
+            h.descrip[..descrip.len()].copy_from_slice(descrip.as_bytes());
+        }
+        h
+    }
+}
+
+/// Read an MGH volume from a file. Compressed files (such as ".mgz" files)
+/// are detected from their first bytes and automatically decoded,
+/// regardless of their extension.
+///
+/// # Errors
+///
+/// - `NiftiError::InvalidMgh` if the file is not a valid MGH volume.
+/// - `NiftiError::UnsupportedMghDimensions` if a dimension is larger than
+///   65535.
+/// - `NiftiError::UnsupportedCompression` if the file is compressed with a
+///   format whose Cargo feature is not enabled.
+pub fn read_file<P: AsRef<Path>>(path: P) -> Result<InMemNiftiObject> {
+    let file = BufReader::new(File::open(path)?);
+    read_stream(decompress(file)?)
+}
+
+/// Read an MGH volume from a stream of data.
+///
+/// # Errors
+///
+/// - `NiftiError::InvalidMgh` if the stream is not a valid MGH volume.
+/// - `NiftiError::UnsupportedMghDimensions` if a dimension is larger than
+///   65535.
+pub fn read_stream<R: Read>(mut source: R) -> Result<InMemNiftiObject> {
+    let mut raw = [0u8; HEADER_SIZE];
+    source.read_exact(&mut raw)?;
+    let mut input = &raw[..];
+
+    let version = input.read_i32::<BigEndian>()?;
+    if version != 1 {
+        return Err(NiftiError::InvalidMgh(format!(
+            "unsupported version {}",
+            version
+        )));
+    }
+    let mut mgh = MghHeader::default();
+    for d in &mut mgh.dim {
+        let n = input.read_i32::<BigEndian>()?;
+        if n < 1 {
+            return Err(NiftiError::InvalidMgh(format!("invalid dimension {}", n)));
+        }
+        *d = n as usize;
+    }
+    if mgh.dim.iter().any(|&d| d > usize::from(u16::MAX)) {
+        return Err(NiftiError::UnsupportedMghDimensions(mgh.dim));
+    }
+    let datatype = mgh_data_type(input.read_i32::<BigEndian>()?)?;
+    mgh.dof = input.read_i32::<BigEndian>()?;
+    if input.read_i16::<BigEndian>()? != 0 {
+        input.read_f32_into::<BigEndian>(&mut mgh.spacing)?;
+        for d in &mut mgh.directions {
+            input.read_f32_into::<BigEndian>(d)?;
+        }
+        input.read_f32_into::<BigEndian>(&mut mgh.center)?;
+    }
+
+    let header = mgh.to_nifti(datatype);
+    let len = mgh.dim.iter().product::<usize>() * datatype.size_of();
+    let mut data = vec![0u8; len];
+    source.read_exact(&mut data)?;
+    let volume = InMemNiftiVolume::from_raw_data(&header, data)?;
+
+    // the scan parameters are optional
+    let mut tail = Vec::new();
+    let _ = source.read_to_end(&mut tail)?;
+    if tail.len() >= 20 {
+        let mut params = [0f32; 5];
+        (&tail[..20]).read_f32_into::<BigEndian>(&mut params)?;
+        mgh.tr = params[0];
+        mgh.flip_angle = params[1];
+        mgh.te = params[2];
+        mgh.ti = params[3];
+        mgh.fov = params[4];
+    }
+
+    InMemNiftiObject::from_parts(mgh.to_nifti(datatype), ExtensionSequence::new(), volume)
+}
+
+/// Write a NIFTI object as an MGH volume file. If the file's name ends with
+/// ".mgz" or ".gz", the file is GZip compressed. See `write_stream`.
+///
+/// # Errors
+///
+/// - See `write_stream`.
+pub fn write_file<P, O>(path: P, object: &O) -> Result<()>
+where
+    P: AsRef<Path>,
+    O: NiftiObject<Volume = InMemNiftiVolume>,
+{
+    let gz = is_mgz_file(&path);
+    let writer = BufWriter::new(File::create(&path)?);
+    if gz {
+        let mut e = GzEncoder::new(writer, Compression::fast());
+        write_stream(&mut e, object)?;
+        let _ = e.finish()?;
+    } else {
+        write_stream(writer, object)?;
+    }
+    Ok(())
+}
+
+/// Write a NIFTI object as an MGH volume to a stream of data. The MGH
+/// header is built with `MghHeader::from_nifti`. Volumes of the data types
+/// supported by MGH (`Uint8`, `Int16`, `Int32` and `Float32`) are written
+/// as they are, unless they are scaled, while other volumes are converted
+/// to `Float32`, with scaling applied.
+///
+/// # Errors
+///
+/// - `NiftiError::InvalidMgh` if the volume has more than four
+///   dimensions.
+/// - `NiftiError::UnsupportedDataType` if the volume's data type cannot
+///   be converted to `Float32`.
+pub fn write_stream<W, O>(mut writer: W, object: &O) -> Result<()>
+where
+    W: Write,
+    O: NiftiObject<Volume = InMemNiftiVolume>,
+{
+    let header = object.header();
+    let mgh = MghHeader::from_nifti(header)?;
+    let volume = object.volume();
+
+    let scaled = header.scl_slope != 0. && (header.scl_slope != 1. || header.scl_inter != 0.);
+    let converted;
+    let volume = match mgh_type_code(volume.data_type()) {
+        Some(_) if !scaled => volume,
+        _ => {
+            converted = volume.map_values(NiftiType::Float32, |v| v)?;
+            &converted
+        }
+    };
+    let datatype = volume.data_type();
+
+    writer.write_i32::<BigEndian>(1)?;
+    for &d in &mgh.dim {
+        writer.write_i32::<BigEndian>(d as i32)?;
+    }
+    writer.write_i32::<BigEndian>(mgh_type_code(datatype).unwrap_or(MGH_FLOAT))?;
+    writer.write_i32::<BigEndian>(mgh.dof)?;
+    writer.write_i16::<BigEndian>(1)?;
+    for v in mgh
+        .spacing
+        .iter()
+        .chain(mgh.directions.iter().flat_map(|d| d.iter()))
+        .chain(&mgh.center)
+    {
+        writer.write_f32::<BigEndian>(*v)?;
+    }
+    writer.write_all(&[0; HEADER_SIZE - USED_HEADER_SIZE])?;
+
+    let data = volume.raw_data();
+    match volume.endianness() {
+        Endianness::BE => writer.write_all(data)?,
+        Endianness::LE => {
+            let mut swapped = data.to_vec();
+            for v in swapped.chunks_mut(datatype.size_of()) {
+                v.reverse();
+            }
+            writer.write_all(&swapped)?;
+        }
+    }
+
+    for v in &[mgh.tr, mgh.flip_angle, mgh.te, mgh.ti, mgh.fov] {
+        writer.write_f32::<BigEndian>(*v)?;
+    }
+    Ok(())
+}
+
+const MGH_UCHAR: i32 = 0;
+const MGH_INT: i32 = 1;
+const MGH_FLOAT: i32 = 3;
+const MGH_SHORT: i32 = 4;
+
+fn mgh_data_type(code: i32) -> Result<NiftiType> {
+    match code {
+        MGH_UCHAR => Ok(NiftiType::Uint8),
+        MGH_INT => Ok(NiftiType::Int32),
+        MGH_FLOAT => Ok(NiftiType::Float32),
+        MGH_SHORT => Ok(NiftiType::Int16),
+        _ => Err(NiftiError::InvalidMgh(format!(
+            "unsupported data type {}",
+            code
+        ))),
+    }
+}
+
+fn mgh_type_code(datatype: NiftiType) -> Option<i32> {
+    match datatype {
+        NiftiType::Uint8 => Some(MGH_UCHAR),
+        NiftiType::Int32 => Some(MGH_INT),
+        NiftiType::Float32 => Some(MGH_FLOAT),
+        NiftiType::Int16 => Some(MGH_SHORT),
+        _ => None,
+    }
+}
+
+fn is_mgz_file<P: AsRef<Path>>(path: P) -> bool {
+    is_gz_file(&path)
+        || path
+            .as_ref()
+            .extension()
+            .map(|e| e == "mgz")
+            .unwrap_or(false)
+}
@@ -0,0 +1,358 @@
+//! This module provides reading and writing of volumes in the NumPy
+//! ".npy" format (versions 1 to 3), without depending on NumPy or on
+//! any other crate.
+//!
+//! Volumes are written with their data type and byte order, in Fortran
+//! order (`fortran_order` is `True`), so that the raw data is written as
+//! it is and the array has the same shape as the volume. Arrays in either
+//! order can be read. The stored values are written, without applying
+//! the volume's scaling parameters.
+//!
+//! Since ".npy" files only hold an array, `write_file` also writes the
+//! volume's affine transformation to a sidecar file (".affine.npy"), as a
+//! 4x4 array of 64-bit floats, which `read_file` reads back if present.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use nifti::{npy, InMemNiftiObject};
+//! # use nifti::Result;
+//!
+//! # fn run() -> Result<()> {
+//! let obj = InMemNiftiObject::from_file("t1.nii.gz")?;
+//! // writes "t1.npy" and "t1.affine.npy"
+//! npy::write_file("t1.npy", &obj)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The files can then be loaded with NumPy:
+//!
+//! ```python
+//! data = np.load("t1.npy")
+//! affine = np.load("t1.affine.npy")
+//! ```
+
+use affine::{self, Affine};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use error::{NiftiError, Result};
+use extension::ExtensionSequence;
+use header::{NiftiHeader, MAGIC_CODE_NIP1};
+use object::{InMemNiftiObject, NiftiObject};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use typedef::NiftiType;
+use util::{reorder_axes, Endianness};
+use volume::inmem::InMemNiftiVolume;
+use volume::NiftiVolume;
+
+/// Magic string at the start of ".npy" files.
+const MAGIC: &[u8; 6] = b"\x93NUMPY";
+/// Alignment of the data, including the header.
+const ALIGNMENT: usize = 64;
+
+/// Read a volume, along with its affine transformation from the sidecar
+/// file (see `sidecar_path`), if present. Without a sidecar file, the
+/// header has a unit voxel spacing and no transformation.
+///
+/// # Errors
+///
+/// - `NiftiError::InvalidNpy` if a file is not a valid ".npy" file, or if
+///   the sidecar file does not hold a 4x4 array.
+pub fn read_file<P: AsRef<Path>>(path: P) -> Result<InMemNiftiObject> {
+    let volume = read_volume(BufReader::new(File::open(&path)?))?;
+    let mut header = volume_header(volume.dim(), volume.data_type(), volume.endianness());
+    header.pixdim = [1.; 8];
+    let sidecar = sidecar_path(&path);
+    if sidecar.is_file() {
+        let affine = read_affine(BufReader::new(File::open(sidecar)?))?;
+        header.set_affine(&affine);
+    }
+    InMemNiftiObject::from_parts(header, ExtensionSequence::new(), volume)
+}
+
+/// Write the volume of a NIFTI object, along with its affine
+/// transformation to the sidecar file (see `sidecar_path`).
+///
+/// # Errors
+///
+/// - See `write_volume`.
+pub fn write_file<P, O>(path: P, object: &O) -> Result<()>
+where
+    P: AsRef<Path>,
+    O: NiftiObject<Volume = InMemNiftiVolume>,
+{
+    let mut writer = BufWriter::new(File::create(&path)?);
+    write_volume(&mut writer, object.volume())?;
+    writer.flush()?;
+    let mut writer = BufWriter::new(File::create(sidecar_path(&path))?);
+    write_affine(&mut writer, &object.header().affine())?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Obtain the path of the sidecar file holding the affine transformation
+/// of a volume, which replaces the extension ".npy" with ".affine.npy".
+pub fn sidecar_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    path.as_ref().with_extension("affine.npy")
+}
+
+/// Read a volume from a stream of data in the ".npy" format.
+///
+/// # Errors
+///
+/// - `NiftiError::InvalidNpy` if the stream is not a valid ".npy" file,
+///   its data type has no NIfTI equivalent, or its shape is not valid
+///   for a volume.
+pub fn read_volume<R: Read>(mut source: R) -> Result<InMemNiftiVolume> {
+    let mut magic = [0u8; 8];
+    source.read_exact(&mut magic)?;
+    if &magic[..6] != MAGIC {
+        return Err(invalid("missing magic string"));
+    }
+    let len = match magic[6] {
+        1 => source.read_u16::<LittleEndian>()? as usize,
+        2 | 3 => source.read_u32::<LittleEndian>()? as usize,
+        v => return Err(invalid(format!("unsupported version {}", v))),
+    };
+    let mut dict = vec![0u8; len];
+    source.read_exact(&mut dict)?;
+    let dict = String::from_utf8(dict).map_err(|_| invalid("header is not UTF-8"))?;
+
+    let (datatype, endianness) = parse_descr(dict_value(&dict, "descr")?)?;
+    let fortran_order = match dict_value(&dict, "fortran_order")? {
+        "True" => true,
+        "False" => false,
+        v => return Err(invalid(format!("invalid fortran_order `{}`", v))),
+    };
+    let mut shape = parse_shape(dict_value(&dict, "shape")?)?;
+
+    let mut data = vec![0u8; shape.iter().product::<usize>() * datatype.size_of()];
+    source.read_exact(&mut data)?;
+    if !fortran_order && shape.len() > 1 {
+        // a C order array is a Fortran order array with the axes reversed
+        shape.reverse();
+        let order: Vec<usize> = (0..shape.len()).rev().collect();
+        data = reorder_axes(&data, &shape, &order, datatype.size_of());
+        shape.reverse();
+    }
+
+    let dim: Vec<u16> = shape.iter().map(|&s| s as u16).collect();
+    InMemNiftiVolume::from_raw_data(&volume_header(&dim, datatype, endianness), data)
+}
+
+/// Write a volume to a stream of data in the ".npy" format, with its data
+/// type and byte order, in Fortran order. The version 1 header is used
+/// unless the header is too long for it.
+///
+/// # Errors
+///
+/// - `NiftiError::UnsupportedDataType` if the volume's data type has no
+///   NumPy equivalent (`Float128` and `Complex256`).
+pub fn write_volume<W: Write>(writer: W, volume: &InMemNiftiVolume) -> Result<()> {
+    let datatype = volume.data_type();
+    let shape: Vec<usize> = volume.dim().iter().map(|&d| usize::from(d)).collect();
+    let descr =
+        descr(datatype, volume.endianness()).ok_or(NiftiError::UnsupportedDataType(datatype))?;
+    write_array(writer, &descr, true, &shape, volume.raw_data())
+}
+
+/// Read an affine transformation from a stream of data in the ".npy"
+/// format, which must hold a 4x4 array of numbers, in either order.
+///
+/// # Errors
+///
+/// - `NiftiError::InvalidNpy` if the stream is not a valid ".npy" file, or
+///   does not hold a 4x4 array.
+pub fn read_affine<R: Read>(source: R) -> Result<Affine> {
+    let array = read_volume(source)?;
+    if array.dim() != [4, 4] {
+        return Err(invalid("the affine transformation is not a 4x4 array"));
+    }
+    let mut a = affine::identity();
+    for (r, row) in a.iter_mut().enumerate() {
+        for (c, v) in row.iter_mut().enumerate() {
+            *v = array.get_f64(&[r as u16, c as u16])?;
+        }
+    }
+    Ok(a)
+}
+
+/// Write an affine transformation to a stream of data in the ".npy"
+/// format, as a 4x4 array of little endian 64-bit floats, in C order.
+pub fn write_affine<W: Write>(writer: W, affine: &Affine) -> Result<()> {
+    let mut data = Vec::with_capacity(16 * 8);
+    for v in affine.iter().flat_map(|row| row.iter()) {
+        data.write_f64::<LittleEndian>(*v)?;
+    }
+    write_array(writer, "'<f8'", false, &[4, 4], &data)
+}
+
+fn write_array<W: Write>(
+    mut writer: W,
+    descr: &str,
+    fortran_order: bool,
+    shape: &[usize],
+    data: &[u8],
+) -> Result<()> {
+    let shape = match shape.len() {
+        1 => format!("({},)", shape[0]),
+        _ => {
+            let sizes: Vec<String> = shape.iter().map(usize::to_string).collect();
+            format!("({})", sizes.join(", "))
+        }
+    };
+    let mut dict = format!(
+        "{{'descr': {}, 'fortran_order': {}, 'shape': {}, }}",
+        descr,
+        if fortran_order { "True" } else { "False" },
+        shape
+    );
+
+    // pad with spaces and a newline, so that the data is aligned
+    let v1 = dict.len() + 1 + 10 <= usize::from(u16::MAX);
+    let preamble = if v1 { 10 } else { 12 };
+    let padding = ALIGNMENT - (preamble + dict.len() + 1) % ALIGNMENT;
+    dict.extend(::std::iter::repeat_n(' ', padding % ALIGNMENT));
+    dict.push('\n');
+
+    writer.write_all(MAGIC)?;
+    if v1 {
+        writer.write_all(&[1, 0])?;
+        writer.write_u16::<LittleEndian>(dict.len() as u16)?;
+    } else {
+        writer.write_all(&[2, 0])?;
+        writer.write_u32::<LittleEndian>(dict.len() as u32)?;
+    }
+    writer.write_all(dict.as_bytes())?;
+    writer.write_all(data)?;
+    Ok(())
+}
+
+/// Build the header of a volume with the given shape and data type.
+fn volume_header(dim: &[u16], datatype: NiftiType, endianness: Endianness) -> NiftiHeader {
+    let mut header = NiftiHeader {
+        dim: [1; 8],
+        datatype: datatype as i16,
+        bitpix: (datatype.size_of() * 8) as i16,
+        magic: *MAGIC_CODE_NIP1,
+        endianness,
+        ..Default::default()
+    };
+    header.dim[0] = dim.len() as u16;
+    header.dim[1..=dim.len()].copy_from_slice(dim);
+    header
+}
+
+/// Get the value of a key in the header's dictionary, as the literal text.
+fn dict_value<'a>(dict: &'a str, key: &str) -> Result<&'a str> {
+    let pattern = format!("'{}':", key);
+    let start = dict
+        .find(&pattern)
+        .ok_or_else(|| invalid(format!("missing `{}`", key)))?;
+    let rest = dict[start + pattern.len()..].trim_start();
+    let end = match rest.chars().next() {
+        Some('\'') => rest[1..].find('\'').map(|i| i + 2),
+        Some('(') => rest.find(')').map(|i| i + 1),
+        Some('[') => rest.find(']').map(|i| i + 1),
+        _ => rest.find([',', '}']),
+    };
+    end.map(|end| rest[..end].trim())
+        .ok_or_else(|| invalid(format!("invalid `{}`", key)))
+}
+
+fn parse_shape(value: &str) -> Result<Vec<usize>> {
+    let inner = value
+        .strip_prefix('(')
+        .and_then(|v| v.strip_suffix(')'))
+        .ok_or_else(|| invalid(format!("invalid shape `{}`", value)))?;
+    let shape: Vec<usize> = inner
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            s.parse()
+                .map_err(|_| invalid(format!("invalid shape `{}`", value)))
+        })
+        .collect::<Result<_>>()?;
+    if shape.is_empty()
+        || shape.len() > 7
+        || shape.iter().any(|&s| s == 0 || s > usize::from(u16::MAX))
+    {
+        return Err(invalid(format!("unsupported shape `{}`", value)));
+    }
+    Ok(shape)
+}
+
+fn parse_descr(value: &str) -> Result<(NiftiType, Endianness)> {
+    let unsupported = || invalid(format!("unsupported data type `{}`", value));
+    let compact: String = value.chars().filter(|c| !c.is_whitespace()).collect();
+    match compact.as_str() {
+        "[('r','|u1'),('g','|u1'),('b','|u1')]" | "[('R','|u1'),('G','|u1'),('B','|u1')]" => {
+            return Ok((NiftiType::Rgb24, Endianness::LE))
+        }
+        "[('r','|u1'),('g','|u1'),('b','|u1'),('a','|u1')]"
+        | "[('R','|u1'),('G','|u1'),('B','|u1'),('A','|u1')]" => {
+            return Ok((NiftiType::Rgba32, Endianness::LE))
+        }
+        _ => {}
+    }
+
+    let descr = value.trim_matches('\'');
+    let mut chars = descr.chars();
+    let endianness = match chars.next() {
+        Some('<') | Some('|') => Endianness::LE,
+        Some('>') => Endianness::BE,
+        Some('=') => Endianness::system(),
+        _ => return Err(unsupported()),
+    };
+    let datatype = match chars.as_str() {
+        "u1" | "b1" => NiftiType::Uint8,
+        "i1" => NiftiType::Int8,
+        "u2" => NiftiType::Uint16,
+        "i2" => NiftiType::Int16,
+        "u4" => NiftiType::Uint32,
+        "i4" => NiftiType::Int32,
+        "u8" => NiftiType::Uint64,
+        "i8" => NiftiType::Int64,
+        "f4" => NiftiType::Float32,
+        "f8" => NiftiType::Float64,
+        "c8" => NiftiType::Complex64,
+        "c16" => NiftiType::Complex128,
+        _ => return Err(unsupported()),
+    };
+    Ok((datatype, endianness))
+}
+
+fn descr(datatype: NiftiType, endianness: Endianness) -> Option<String> {
+    let code = match datatype {
+        NiftiType::Uint8 => "u1",
+        NiftiType::Int8 => "i1",
+        NiftiType::Uint16 => "u2",
+        NiftiType::Int16 => "i2",
+        NiftiType::Uint32 => "u4",
+        NiftiType::Int32 => "i4",
+        NiftiType::Uint64 => "u8",
+        NiftiType::Int64 => "i8",
+        NiftiType::Float32 => "f4",
+        NiftiType::Float64 => "f8",
+        NiftiType::Complex64 => "c8",
+        NiftiType::Complex128 => "c16",
+        NiftiType::Rgb24 => return Some("[('R', '|u1'), ('G', '|u1'), ('B', '|u1')]".into()),
+        NiftiType::Rgba32 => {
+            return Some("[('R', '|u1'), ('G', '|u1'), ('B', '|u1'), ('A', '|u1')]".into())
+        }
+        NiftiType::Float128 | NiftiType::Complex256 => return None,
+    };
+    let order = match (datatype.size_of(), endianness) {
+        (1, _) => '|',
+        (_, Endianness::LE) => '<',
+        (_, Endianness::BE) => '>',
+    };
+    Some(format!("'{}{}'", order, code))
+}
+
+fn invalid<S: Into<String>>(reason: S) -> NiftiError {
+    NiftiError::InvalidNpy(reason.into())
+}
@@ -0,0 +1,612 @@
+//! This module provides reading and writing of NRRD volumes, as used by
+//! 3D Slicer, either with the data attached to the header (".nrrd") or in
+//! a separate data file (".nhdr").
+//!
+//! Volumes are read into the same `InMemNiftiObject` representation as
+//! NIfTI volumes. The `space directions` and `space origin` fields become
+//! both the qform and the sform of the NIfTI header, converted from the
+//! NRRD space (`left-posterior-superior`, `left-anterior-superior` or
+//! `right-anterior-superior`) to the RAS coordinates of NIfTI. Axes
+//! without a space direction (such as the gradients of diffusion data) are
+//! placed after the spatial axes. Volumes are written in the
+//! `left-posterior-superior` space preferred by 3D Slicer.
+//!
+//! Only the `raw` and `gzip` data encodings are supported, with the data
+//! in a single file: the `data file` forms listing several files (`LIST`,
+//! or a format such as `slice%03d.raw 1 10 1`) are not supported.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use nifti::nrrd::{self, NrrdEncoding};
+//! use nifti::{InMemNiftiObject, NiftiObject};
+//! # use nifti::Result;
+//!
+//! # fn run() -> Result<()> {
+//! let obj = InMemNiftiObject::from_file("t1.nii.gz")?;
+//! nrrd::write_file("t1.nrrd", &obj, NrrdEncoding::Gzip)?;
+//! let obj = nrrd::read_file("segmentation.seg.nrrd")?;
+//! println!("{:?}", obj.header().affine());
+//! # Ok(())
+//! # }
+//! ```
+
+use affine;
+use error::{NiftiError, Result};
+use extension::ExtensionSequence;
+use flate2::bufread::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use header::{NiftiHeader, MAGIC_CODE_NIP1};
+use object::{InMemNiftiObject, NiftiObject};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use typedef::{NiftiType, Unit, XForm};
+use util::{reorder_axes, Endianness};
+use volume::inmem::InMemNiftiVolume;
+use volume::NiftiVolume;
+
+/// The encoding of the data of a NRRD volume.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum NrrdEncoding {
+    /// Raw data, as in memory
+    Raw,
+    /// GZip compressed raw data
+    Gzip,
+}
+
+/// Read a NRRD volume from a file. The data is read from the file given
+/// by the `data file` field, relative to the header's directory, if
+/// present, and from the header's file otherwise.
+///
+/// # Errors
+///
+/// - `NiftiError::InvalidNrrd` if the header is not valid or uses
+///   unsupported features.
+pub fn read_file<P: AsRef<Path>>(path: P) -> Result<InMemNiftiObject> {
+    let mut source = BufReader::new(File::open(&path)?);
+    let nrrd = NrrdHeader::parse(&mut source)?;
+    match nrrd.data_file {
+        Some(ref name) => {
+            let data_path = path
+                .as_ref()
+                .parent()
+                .map(|d| d.join(name))
+                .unwrap_or_else(|| PathBuf::from(name));
+            nrrd.read_volume(BufReader::new(File::open(data_path)?))
+        }
+        None => nrrd.read_volume(source),
+    }
+}
+
+/// Read a NRRD volume from a stream of data, which must hold the data
+/// after the header.
+///
+/// # Errors
+///
+/// - `NiftiError::InvalidNrrd` if the header is not valid, uses
+///   unsupported features, or refers to a separate data file.
+pub fn read_stream<R: Read>(source: R) -> Result<InMemNiftiObject> {
+    let mut source = BufReader::new(source);
+    let nrrd = NrrdHeader::parse(&mut source)?;
+    if nrrd.data_file.is_some() {
+        return Err(invalid("the data is in a separate file"));
+    }
+    nrrd.read_volume(source)
+}
+
+/// Write a NIFTI object as a NRRD volume file. If the file's name ends
+/// with ".nhdr", the data is written to a separate file, with the same
+/// name and the extension ".raw" (or ".raw.gz" with the GZip encoding).
+/// See `write_stream`.
+///
+/// # Errors
+///
+/// - See `write_stream`.
+pub fn write_file<P, O>(path: P, object: &O, encoding: NrrdEncoding) -> Result<()>
+where
+    P: AsRef<Path>,
+    O: NiftiObject<Volume = InMemNiftiVolume>,
+{
+    let path = path.as_ref();
+    let detached = path.extension().map(|e| e == "nhdr").unwrap_or(false);
+    let mut writer = BufWriter::new(File::create(path)?);
+    if detached {
+        let data_path = match encoding {
+            NrrdEncoding::Raw => path.with_extension("raw"),
+            NrrdEncoding::Gzip => path.with_extension("raw.gz"),
+        };
+        let name = data_path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let volume = write_header(&mut writer, object, encoding, Some(&name))?;
+        writer.flush()?;
+        write_data(BufWriter::new(File::create(data_path)?), &volume, encoding)
+    } else {
+        let volume = write_header(&mut writer, object, encoding, None)?;
+        write_data(writer, &volume, encoding)
+    }
+}
+
+/// Write a NIFTI object as a NRRD volume to a stream of data, with the
+/// data attached to the header. Volumes are written with their data type
+/// and byte order, unless they are scaled, in which case they are
+/// converted to `Float32` (or `Float64`), with scaling applied.
+///
+/// # Errors
+///
+/// - `NiftiError::UnsupportedDataType` if the volume's data type is not
+///   supported by NRRD (complex and RGB types).
+/// - `NiftiError::IncompatibleUnit` if the spatial unit is not a unit of
+///   length.
+pub fn write_stream<W, O>(mut writer: W, object: &O, encoding: NrrdEncoding) -> Result<()>
+where
+    W: Write,
+    O: NiftiObject<Volume = InMemNiftiVolume>,
+{
+    let volume = write_header(&mut writer, object, encoding, None)?;
+    write_data(writer, &volume, encoding)
+}
+
+/// The fields of a NRRD header which are relevant to this crate.
+#[derive(Debug)]
+struct NrrdHeader {
+    datatype: Option<NiftiType>,
+    dimension: usize,
+    sizes: Vec<usize>,
+    endianness: Option<Endianness>,
+    encoding: Option<NrrdEncoding>,
+    space: Option<[f64; 3]>,
+    directions: Option<Vec<Option<Vec<f64>>>>,
+    origin: Option<Vec<f64>>,
+    spacings: Vec<f64>,
+    kinds: Vec<String>,
+    /// Number of lines before the (possibly compressed) data
+    line_skip: usize,
+    /// Number of bytes before the data, or -1 if the data is at the end
+    byte_skip: i64,
+    data_file: Option<String>,
+}
+
+impl NrrdHeader {
+    fn parse<R: BufRead>(source: &mut R) -> Result<NrrdHeader> {
+        let mut line = String::new();
+        let _ = source.read_line(&mut line)?;
+        if !line.starts_with("NRRD") {
+            return Err(invalid("missing NRRD magic"));
+        }
+
+        let mut h = NrrdHeader {
+            datatype: None,
+            dimension: 0,
+            sizes: Vec::new(),
+            endianness: None,
+            encoding: None,
+            space: None,
+            directions: None,
+            origin: None,
+            spacings: Vec::new(),
+            kinds: Vec::new(),
+            line_skip: 0,
+            byte_skip: 0,
+            data_file: None,
+        };
+        loop {
+            line.clear();
+            if source.read_line(&mut line)? == 0 {
+                break;
+            }
+            let line = line.trim_end_matches(['\n', '\r']);
+            if line.is_empty() {
+                break;
+            }
+            // comments and key/value pairs
+            if line.starts_with('#') || line.contains(":=") {
+                continue;
+            }
+            let mut field = line.splitn(2, ": ");
+            let (key, value) = match (field.next(), field.next()) {
+                (Some(key), Some(value)) => (key.trim(), value.trim()),
+                _ => return Err(invalid(format!("invalid line `{}`", line))),
+            };
+            match key {
+                "type" => h.datatype = Some(parse_type(value)?),
+                "dimension" => {
+                    h.dimension = parse_number(key, value)?;
+                    if h.dimension == 0 || h.dimension > 7 {
+                        return Err(invalid(format!("unsupported dimension {}", h.dimension)));
+                    }
+                }
+                "sizes" => {
+                    h.sizes = value
+                        .split_whitespace()
+                        .map(|v| parse_number(key, v))
+                        .collect::<Result<_>>()?
+                }
+                "endian" => {
+                    h.endianness = match value {
+                        "little" => Some(Endianness::LE),
+                        "big" => Some(Endianness::BE),
+                        _ => return Err(invalid(format!("invalid endian `{}`", value))),
+                    }
+                }
+                "encoding" => {
+                    h.encoding = match value {
+                        "raw" => Some(NrrdEncoding::Raw),
+                        "gzip" | "gz" => Some(NrrdEncoding::Gzip),
+                        _ => return Err(invalid(format!("unsupported encoding `{}`", value))),
+                    }
+                }
+                "space" => {
+                    h.space = match value {
+                        "right-anterior-superior" | "RAS" => Some([1., 1., 1.]),
+                        "left-anterior-superior" | "LAS" => Some([-1., 1., 1.]),
+                        "left-posterior-superior" | "LPS" => Some([-1., -1., 1.]),
+                        _ => return Err(invalid(format!("unsupported space `{}`", value))),
+                    }
+                }
+                "space dimension" => {
+                    if parse_number::<usize>(key, value)? != 3 {
+                        return Err(invalid("unsupported space dimension"));
+                    }
+                    if h.space.is_none() {
+                        h.space = Some([1., 1., 1.]);
+                    }
+                }
+                "space directions" => h.directions = Some(parse_vectors(value)?),
+                "space origin" => {
+                    h.origin = match parse_vectors(value)?.pop() {
+                        Some(Some(v)) => Some(v),
+                        _ => return Err(invalid("invalid space origin")),
+                    }
+                }
+                "spacings" => {
+                    h.spacings = value
+                        .split_whitespace()
+                        .map(|v| v.parse().unwrap_or(f64::NAN))
+                        .collect()
+                }
+                "kinds" => h.kinds = value.split_whitespace().map(str::to_string).collect(),
+                "byte skip" => {
+                    h.byte_skip = parse_number(key, value)?;
+                    if h.byte_skip < -1 {
+                        return Err(invalid(format!("invalid byte skip `{}`", value)));
+                    }
+                }
+                "line skip" | "lineskip" => h.line_skip = parse_number(key, value)?,
+                "data file" | "datafile" => {
+                    let mut words = value.split_whitespace();
+                    let name = words.next().unwrap_or("");
+                    if name == "LIST" || (name.contains('%') && words.next().is_some()) {
+                        return Err(invalid("multiple data files are not supported"));
+                    }
+                    h.data_file = Some(value.to_string());
+                }
+                _ => {}
+            }
+        }
+        Ok(h)
+    }
+
+    fn read_volume<R: BufRead>(&self, mut source: R) -> Result<InMemNiftiObject> {
+        let datatype = self.datatype.ok_or_else(|| invalid("missing type"))?;
+        if self.dimension == 0 {
+            return Err(invalid("missing dimension"));
+        }
+        if self.sizes.len() != self.dimension
+            || self
+                .sizes
+                .iter()
+                .any(|&s| s == 0 || s > usize::from(u16::MAX))
+        {
+            return Err(invalid("invalid sizes"));
+        }
+        let endianness = match self.endianness {
+            Some(e) => e,
+            None if datatype.size_of() == 1 => Endianness::LE,
+            None => return Err(invalid("missing endian")),
+        };
+
+        // lines are skipped before decoding the data, and bytes after
+        let mut line = Vec::new();
+        for _ in 0..self.line_skip {
+            line.clear();
+            if source.read_until(b'\n', &mut line)? == 0 {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
+        }
+
+        let len = self.sizes.iter().product::<usize>() * datatype.size_of();
+        let mut data = vec![0u8; len];
+        match self.encoding {
+            Some(NrrdEncoding::Raw) => read_data(source, self.byte_skip, &mut data)?,
+            Some(NrrdEncoding::Gzip) if self.byte_skip == -1 => {
+                return Err(invalid("byte skip -1 requires the raw encoding"))
+            }
+            Some(NrrdEncoding::Gzip) => {
+                read_data(GzDecoder::new(source), self.byte_skip, &mut data)?
+            }
+            None => return Err(invalid("missing encoding")),
+        }
+
+        // spatial axes first
+        let ndim = self.sizes.len();
+        let spatial: Vec<usize> = match self.directions {
+            Some(ref directions) => {
+                if directions.len() != ndim {
+                    return Err(invalid("invalid space directions"));
+                }
+                (0..ndim).filter(|&i| directions[i].is_some()).collect()
+            }
+            None if !self.kinds.is_empty() => (0..ndim)
+                .filter(|&i| {
+                    self.kinds
+                        .get(i)
+                        .map(|k| k == "domain" || k == "space")
+                        .unwrap_or(false)
+                })
+                .collect(),
+            None => (0..ndim.min(3)).collect(),
+        };
+        if spatial.len() > 3 {
+            return Err(invalid("more than three spatial axes"));
+        }
+        let order: Vec<usize> = spatial
+            .iter()
+            .cloned()
+            .chain((0..ndim).filter(|i| !spatial.contains(i)))
+            .collect();
+        if order.iter().enumerate().any(|(i, &a)| i != a) {
+            data = reorder_axes(&data, &self.sizes, &order, datatype.size_of());
+        }
+
+        let mut header = NiftiHeader {
+            dim: [1; 8],
+            datatype: datatype as i16,
+            bitpix: (datatype.size_of() * 8) as i16,
+            pixdim: [1.; 8],
+            magic: *MAGIC_CODE_NIP1,
+            endianness,
+            ..Default::default()
+        };
+        header.dim[0] = ndim as u16;
+        for (i, &a) in order.iter().enumerate() {
+            header.dim[i + 1] = self.sizes[a] as u16;
+            if let Some(&s) = self.spacings.get(a) {
+                if s.is_finite() && s != 0. {
+                    header.pixdim[i + 1] = s.abs() as f32;
+                }
+            }
+        }
+        header.set_xyzt_units(Unit::Mm, Unit::Unknown)?;
+        if let (Some(space), Some(directions)) = (self.space, self.directions.as_ref()) {
+            let mut a = affine::identity();
+            for (j, &axis) in spatial.iter().enumerate() {
+                let d = directions[axis].as_ref().filter(|d| d.len() == 3);
+                let d = d.ok_or_else(|| invalid("invalid space directions"))?;
+                for r in 0..3 {
+                    a[r][j] = space[r] * d[r];
+                }
+            }
+            if let Some(ref origin) = self.origin {
+                if origin.len() != 3 {
+                    return Err(invalid("invalid space origin"));
+                }
+                for r in 0..3 {
+                    a[r][3] = space[r] * origin[r];
+                }
+            }
+            header.set_affine(&a);
+            header.qform_code = XForm::ScannerAnat as i16;
+            header.sform_code = XForm::ScannerAnat as i16;
+        }
+
+        let volume = InMemNiftiVolume::from_raw_data(&header, data)?;
+        InMemNiftiObject::from_parts(header, ExtensionSequence::new(), volume)
+    }
+}
+
+/// Read the data after skipping `byte_skip` bytes, or from the last bytes
+/// of the source if `byte_skip` is -1.
+fn read_data<R: Read>(mut source: R, byte_skip: i64, data: &mut [u8]) -> Result<()> {
+    if byte_skip == -1 {
+        let mut rest = Vec::new();
+        let _ = source.read_to_end(&mut rest)?;
+        if rest.len() < data.len() {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        data.copy_from_slice(&rest[rest.len() - data.len()..]);
+    } else {
+        let _ = io::copy(&mut (&mut source).take(byte_skip as u64), &mut io::sink())?;
+        source.read_exact(data)?;
+    }
+    Ok(())
+}
+
+/// Write the header of a NIFTI object, returning the volume to be written
+/// as its data.
+fn write_header<W, O>(
+    writer: &mut W,
+    object: &O,
+    encoding: NrrdEncoding,
+    data_file: Option<&str>,
+) -> Result<InMemNiftiVolume>
+where
+    W: Write,
+    O: NiftiObject<Volume = InMemNiftiVolume>,
+{
+    let header = object.header();
+    let volume = object.volume();
+    let scaled = header.scl_slope != 0. && (header.scl_slope != 1. || header.scl_inter != 0.);
+    let volume = if scaled {
+        let datatype = match volume.data_type() {
+            NiftiType::Float64 => NiftiType::Float64,
+            _ => NiftiType::Float32,
+        };
+        volume.map_values(datatype, |v| v)?
+    } else {
+        volume.clone()
+    };
+    let datatype = volume.data_type();
+    let type_name = type_name(datatype).ok_or(NiftiError::UnsupportedDataType(datatype))?;
+
+    let space = header.xyzt_to_space()?;
+    let mm = space
+        .millimeters()
+        .ok_or(NiftiError::IncompatibleUnit(space))?;
+    let a = header.affine();
+    let lps = |r: usize, v: f64| if r < 2 { -v * mm } else { v * mm };
+
+    let dim = volume.dim();
+    let spatial = dim.len().min(3);
+    writeln!(writer, "NRRD0004")?;
+    writeln!(writer, "type: {}", type_name)?;
+    writeln!(writer, "dimension: {}", dim.len())?;
+    writeln!(writer, "space: left-posterior-superior")?;
+    let sizes: Vec<String> = dim.iter().map(u16::to_string).collect();
+    writeln!(writer, "sizes: {}", sizes.join(" "))?;
+    let directions: Vec<String> = (0..dim.len())
+        .map(|j| {
+            if j < spatial {
+                format!(
+                    "({},{},{})",
+                    lps(0, a[0][j]),
+                    lps(1, a[1][j]),
+                    lps(2, a[2][j])
+                )
+            } else {
+                "none".to_string()
+            }
+        })
+        .collect();
+    writeln!(writer, "space directions: {}", directions.join(" "))?;
+    let kinds: Vec<&str> = (0..dim.len())
+        .map(|j| if j < spatial { "domain" } else { "list" })
+        .collect();
+    writeln!(writer, "kinds: {}", kinds.join(" "))?;
+    if datatype.size_of() > 1 {
+        let endian = match volume.endianness() {
+            Endianness::LE => "little",
+            Endianness::BE => "big",
+        };
+        writeln!(writer, "endian: {}", endian)?;
+    }
+    let encoding = match encoding {
+        NrrdEncoding::Raw => "raw",
+        NrrdEncoding::Gzip => "gzip",
+    };
+    writeln!(writer, "encoding: {}", encoding)?;
+    writeln!(
+        writer,
+        "space origin: ({},{},{})",
+        lps(0, a[0][3]),
+        lps(1, a[1][3]),
+        lps(2, a[2][3])
+    )?;
+    if let Some(name) = data_file {
+        writeln!(writer, "data file: {}", name)?;
+    }
+    writeln!(writer)?;
+    Ok(volume)
+}
+
+fn write_data<W: Write>(
+    mut writer: W,
+    volume: &InMemNiftiVolume,
+    encoding: NrrdEncoding,
+) -> Result<()> {
+    match encoding {
+        NrrdEncoding::Raw => writer.write_all(volume.raw_data())?,
+        NrrdEncoding::Gzip => {
+            let mut e = GzEncoder::new(writer, Compression::fast());
+            e.write_all(volume.raw_data())?;
+            let _ = e.finish()?;
+        }
+    }
+    Ok(())
+}
+
+/// Parse a list of vectors, such as `(1,0,0) (0,1,0) none`.
+fn parse_vectors(value: &str) -> Result<Vec<Option<Vec<f64>>>> {
+    let mut vectors = Vec::new();
+    let mut rest = value.trim_start();
+    while !rest.is_empty() {
+        if rest.starts_with('(') {
+            let end = rest
+                .find(')')
+                .ok_or_else(|| invalid("unterminated vector"))?;
+            let vector = rest[1..end]
+                .split(',')
+                .map(|v| v.trim().parse())
+                .collect::<::std::result::Result<_, _>>()
+                .map_err(|_| invalid(format!("invalid vector `{}`", &rest[..=end])))?;
+            vectors.push(Some(vector));
+            rest = &rest[end + 1..];
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            if &rest[..end] != "none" {
+                return Err(invalid(format!("invalid vector `{}`", &rest[..end])));
+            }
+            vectors.push(None);
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(vectors)
+}
+
+fn parse_number<T: ::std::str::FromStr>(key: &str, value: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| invalid(format!("invalid {} `{}`", key, value)))
+}
+
+fn parse_type(name: &str) -> Result<NiftiType> {
+    Ok(match name {
+        "signed char" | "int8" | "int8_t" => NiftiType::Int8,
+        "uchar" | "unsigned char" | "uint8" | "uint8_t" => NiftiType::Uint8,
+        "short" | "short int" | "signed short" | "signed short int" | "int16" | "int16_t" => {
+            NiftiType::Int16
+        }
+        "ushort" | "unsigned short" | "unsigned short int" | "uint16" | "uint16_t" => {
+            NiftiType::Uint16
+        }
+        "int" | "signed int" | "int32" | "int32_t" => NiftiType::Int32,
+        "uint" | "unsigned int" | "uint32" | "uint32_t" => NiftiType::Uint32,
+        "longlong"
+        | "long long"
+        | "long long int"
+        | "signed long long"
+        | "signed long long int"
+        | "int64"
+        | "int64_t" => NiftiType::Int64,
+        "ulonglong" | "unsigned long long" | "unsigned long long int" | "uint64" | "uint64_t" => {
+            NiftiType::Uint64
+        }
+        "float" => NiftiType::Float32,
+        "double" => NiftiType::Float64,
+        _ => return Err(invalid(format!("unsupported type `{}`", name))),
+    })
+}
+
+fn type_name(datatype: NiftiType) -> Option<&'static str> {
+    Some(match datatype {
+        NiftiType::Int8 => "int8",
+        NiftiType::Uint8 => "uint8",
+        NiftiType::Int16 => "int16",
+        NiftiType::Uint16 => "uint16",
+        NiftiType::Int32 => "int32",
+        NiftiType::Uint32 => "uint32",
+        NiftiType::Int64 => "int64",
+        NiftiType::Uint64 => "uint64",
+        NiftiType::Float32 => "float",
+        NiftiType::Float64 => "double",
+        _ => return None,
+    })
+}
+
+fn invalid<S: Into<String>>(reason: S) -> NiftiError {
+    NiftiError::InvalidNrrd(reason.into())
+}
@@ -3,17 +3,24 @@
 use std::fs::File;
 use std::path::Path;
 use std::io::{self, BufReader, Read};
+use std::ops::Range;
 
+use affine;
+#[cfg(feature = "bids")]
+use bids::Sidecar;
 use error::NiftiError;
 use extension::{Extender, ExtensionSequence};
 use header::NiftiHeader;
 use header::MAGIC_CODE_NI1;
+use stats::{self, StatOutput};
+use typedef::NiftiType;
 use volume::NiftiVolume;
 use volume::inmem::InMemNiftiVolume;
-use util::{is_gz_file, into_img_file_gz, Endianness};
+use volume::resample::{resample, Interpolation};
+use volume::warp::{self, WarpConvention};
+use util::{decompress, into_img_file_gz, Endianness};
 use error::Result;
 use byteorder::{BigEndian, LittleEndian};
-use flate2::bufread::GzDecoder;
 
 /// Trait type for all possible implementations of
 /// owning NIFTI-1 objects. Objects contain a NIFTI header,
@@ -31,6 +38,9 @@ pub trait NiftiObject {
     /// Obtain a reference to the object's extensions.
     fn extensions(&self) -> &ExtensionSequence;
 
+    /// Obtain a mutable reference to the object's extensions.
+    fn extensions_mut(&mut self) -> &mut ExtensionSequence;
+
     /// Obtain a reference to the object's volume.
     fn volume(&self) -> &Self::Volume;
 
@@ -52,7 +62,9 @@ impl InMemNiftiObject {
     /// The given file system path is used as reference.
     /// If the file only contains the header, this method will
     /// look for the corresponding file with the extension ".img",
-    /// or ".img.gz" if the former wasn't found.
+    /// or ".img.gz" if the former wasn't found. Whether a file is
+    /// compressed is decided from its first bytes, not from its
+    /// name (see `NiftiHeader::from_file` for the supported formats).
     ///
     /// # Example
     ///
@@ -66,21 +78,85 @@ impl InMemNiftiObject {
     /// # }
     /// ```
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<InMemNiftiObject> {
-        let gz = is_gz_file(&path);
+        let mut stream = decompress(BufReader::new(File::open(&path)?))?;
+        let header = NiftiHeader::from_stream(&mut stream)?;
+        Self::from_file_2(path, header, stream)
+    }
 
-        let file = BufReader::new(File::open(&path)?);
-        if gz {
-            Self::from_file_2(path, GzDecoder::new(file))
-        } else {
-            Self::from_file_2(path, file)
+    /// Retrieve the full contents of a NIFTI object, accepting legacy
+    /// Analyze 7.5 file pairs (".hdr" and ".img") as well. The header of
+    /// an Analyze file pair is converted into a NIfTI-1 header, as in
+    /// `NiftiHeader::from_analyze_stream`. The volume file is looked up
+    /// as in `from_file`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use nifti::InMemNiftiObject;
+    /// # use nifti::error::Result;
+    ///
+    /// # fn run() -> Result<()> {
+    /// let obj = InMemNiftiObject::from_analyze_file("spm99.hdr")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_analyze_file<P: AsRef<Path>>(path: P) -> Result<InMemNiftiObject> {
+        let mut stream = decompress(BufReader::new(File::open(&path)?))?;
+        let header = NiftiHeader::from_analyze_stream(&mut stream)?;
+        Self::from_file_2(path, header, stream)
+    }
+
+    /// Build a NIFTI object from a header, a sequence of extensions and a
+    /// volume.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::IncompatibleLength` if the volume's dimensions or
+    ///   data type do not match those declared in the header.
+    pub fn from_parts(
+        header: NiftiHeader,
+        extensions: ExtensionSequence,
+        volume: InMemNiftiVolume,
+    ) -> Result<InMemNiftiObject> {
+        let ndim = header.dim[0] as usize;
+        if ndim > 7
+            || &header.dim[1..=ndim] != volume.dim()
+            || header.data_type()? != volume.data_type()
+        {
+            return Err(NiftiError::IncompatibleLength);
         }
+        Ok(InMemNiftiObject {
+            header,
+            extensions,
+            volume,
+        })
+    }
+
+    /// Retrieve the full contents of a NIFTI object, along with the
+    /// metadata in its BIDS sidecar files, if any (see
+    /// `bids::Sidecar::discover`). Requires the `bids` Cargo feature.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::InvalidSidecar` if a sidecar file is not a JSON
+    ///   object.
+    #[cfg(feature = "bids")]
+    pub fn from_file_with_sidecar<P: AsRef<Path>>(
+        path: P,
+    ) -> Result<(InMemNiftiObject, Option<Sidecar>)> {
+        let obj = Self::from_file(&path)?;
+        let sidecar = Sidecar::discover(path)?;
+        Ok((obj, sidecar))
     }
 
-    fn from_file_2<P: AsRef<Path>, S>(path: P, mut stream: S) -> Result<InMemNiftiObject>
+    fn from_file_2<P: AsRef<Path>, S>(
+        path: P,
+        header: NiftiHeader,
+        mut stream: S,
+    ) -> Result<InMemNiftiObject>
     where
         S: Read,
     {
-        let header = NiftiHeader::from_stream(&mut stream)?;
         let (volume, ext) = if &header.magic == MAGIC_CODE_NI1 {
             // extensions and volume are in another file
 
@@ -148,14 +224,8 @@ impl InMemNiftiObject {
         P: AsRef<Path>,
         Q: AsRef<Path>,
     {
-        let gz = is_gz_file(&hdr_path);
-
         let file = BufReader::new(File::open(&hdr_path)?);
-        if gz {
-            Self::from_file_pair_2(GzDecoder::new(file), vol_path)
-        } else {
-            Self::from_file_pair_2(file, vol_path)
-        }
+        Self::from_file_pair_2(decompress(file)?, vol_path)
     }
 
     fn from_file_pair_2<S, Q>(mut hdr_stream: S, vol_path: Q) -> Result<InMemNiftiObject>
@@ -182,7 +252,70 @@ impl InMemNiftiObject {
     ///
     /// - `NiftiError::NoVolumeData` if the source only contains (or claims to contain)
     /// a header.
-    pub fn new_from_stream<R: Read>(&self, mut source: R) -> Result<InMemNiftiObject> {
+    pub fn new_from_stream<R: Read>(&self, source: R) -> Result<InMemNiftiObject> {
+        Self::from_stream_2(source)
+    }
+
+    /// Retrieve a single-file NIFTI object from a reader, such as a network
+    /// stream or a message payload. As in `from_file`, whether the data is
+    /// compressed is decided from its first bytes.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::NoVolumeData` if the source only contains (or claims to contain)
+    ///   a header.
+    /// - `NiftiError::UnsupportedCompression` if the data is compressed with a
+    ///   format whose Cargo feature is not enabled.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use nifti::InMemNiftiObject;
+    /// use std::fs::File;
+    /// # use nifti::error::Result;
+    ///
+    /// # fn run() -> Result<()> {
+    /// let obj = InMemNiftiObject::from_reader(File::open("minimal.nii.gz")?)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_reader<R: Read>(source: R) -> Result<InMemNiftiObject> {
+        Self::from_stream_2(decompress(BufReader::new(source))?)
+    }
+
+    /// Retrieve a single-file NIFTI object from a byte buffer, which may be
+    /// compressed.
+    ///
+    /// # Errors
+    ///
+    /// - See `from_reader`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<InMemNiftiObject> {
+        Self::from_reader(bytes)
+    }
+
+    /// Retrieve a NIFTI object from two readers, one for the header and one
+    /// for the volume, as with `from_file_pair`. Either reader may provide
+    /// compressed data, which is detected from its first bytes.
+    pub fn from_reader_pair<H, V>(hdr_source: H, vol_source: V) -> Result<InMemNiftiObject>
+    where
+        H: Read,
+        V: Read,
+    {
+        let mut hdr_stream = decompress(BufReader::new(hdr_source))?;
+        let header = NiftiHeader::from_stream(&mut hdr_stream)?;
+        let extender = Extender::from_stream_optional(hdr_stream)?.unwrap_or_default();
+        let vol_stream = decompress(BufReader::new(vol_source))?;
+        let (volume, extensions) =
+            InMemNiftiVolume::from_stream_with_extensions(vol_stream, &header, extender)?;
+
+        Ok(InMemNiftiObject {
+            header,
+            extensions,
+            volume,
+        })
+    }
+
+    fn from_stream_2<R: Read>(mut source: R) -> Result<InMemNiftiObject> {
         let header = NiftiHeader::from_stream(&mut source)?;
         if &header.magic == MAGIC_CODE_NI1 {
             return Err(NiftiError::NoVolumeData);
@@ -209,6 +342,255 @@ impl InMemNiftiObject {
     }
 }
 
+impl InMemNiftiObject {
+    /// Obtain a cardinal-aligned version of this object. Each voxel axis is
+    /// snapped to the closest world axis (see `affine::cardinal`), and the
+    /// volume is resampled with the given interpolation method onto a grid
+    /// which fully covers the original field of view. The voxel spacing is
+    /// preserved. Both the qform and the sform of the resulting header
+    /// describe the new affine transformation.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::IncorrectVolumeDimensionality` if the volume has less
+    ///   than 3 dimensions.
+    /// - `NiftiError::InvalidAffine` if the object's affine is degenerate.
+    pub fn deoblique(&self, interpolation: Interpolation) -> Result<InMemNiftiObject> {
+        if self.header.dim[0] < 3 {
+            return Err(NiftiError::IncorrectVolumeDimensionality(
+                3,
+                self.header.dim[0],
+            ));
+        }
+
+        let source_affine = self.header.affine();
+        let cardinal = affine::cardinal(&source_affine);
+        let to_cardinal = affine::invert(&cardinal).ok_or(NiftiError::InvalidAffine)?;
+        let mapping = affine::multiply(&to_cardinal, &source_affine);
+
+        // bounding box of the original volume in the cardinal grid
+        let mut min = [f64::INFINITY; 3];
+        let mut max = [f64::NEG_INFINITY; 3];
+        for corner in 0..8 {
+            let mut p = [0.; 3];
+            for (axis, v) in p.iter_mut().enumerate() {
+                if corner & (1 << axis) != 0 {
+                    *v = f64::from(self.header.dim[axis + 1]) - 1.;
+                }
+            }
+            let p = affine::transform_point(&mapping, p);
+            for axis in 0..3 {
+                min[axis] = min[axis].min(p[axis]);
+                max[axis] = max[axis].max(p[axis]);
+            }
+        }
+
+        const EPS: f64 = 1e-4;
+        let mut header = self.header.clone();
+        let mut offset = affine::identity();
+        for axis in 0..3 {
+            let lo = (min[axis] + EPS).floor();
+            let hi = (max[axis] - EPS).ceil();
+            header.dim[axis + 1] = (hi - lo) as u16 + 1;
+            offset[axis][3] = lo;
+        }
+        header.set_affine(&affine::multiply(&cardinal, &offset));
+
+        let volume = resample(&self.volume, &self.header, &header, interpolation)?;
+        Ok(InMemNiftiObject {
+            header,
+            extensions: self.extensions.clone(),
+            volume,
+        })
+    }
+}
+
+impl InMemNiftiObject {
+    /// Warp this object with the displacement field held by another object
+    /// (see the `volume::warp` module). The resulting object lies on the
+    /// field's voxel grid, with the field's affine.
+    ///
+    /// # Errors
+    ///
+    /// - See `volume::warp::warp`.
+    pub fn warp(
+        &self,
+        field: &InMemNiftiObject,
+        convention: WarpConvention,
+        interpolation: Interpolation,
+    ) -> Result<InMemNiftiObject> {
+        let volume = warp::warp(
+            &self.volume,
+            &self.header,
+            &field.volume,
+            &field.header,
+            convention,
+            interpolation,
+        )?;
+        let mut header = self.header.clone();
+        header.dim[1..4].copy_from_slice(&field.header.dim[1..4]);
+        header.set_affine(&field.header.affine());
+        Ok(InMemNiftiObject {
+            header,
+            extensions: self.extensions.clone(),
+            volume,
+        })
+    }
+}
+
+impl InMemNiftiObject {
+    /// Obtain a cropped copy of this object, keeping only the voxels within
+    /// the given range of indices of each axis. The translation components
+    /// of the qform and sform are shifted so that each remaining voxel keeps
+    /// its world coordinates.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::IncorrectVolumeDimensionality` if the number of ranges
+    ///   does not match the volume's dimensionality.
+    /// - `NiftiError::OutOfBounds` if a range is empty or exceeds the
+    ///   volume's boundaries.
+    pub fn crop(&self, ranges: &[Range<u16>]) -> Result<InMemNiftiObject> {
+        let volume = self.volume.crop(ranges)?;
+        let offset: Vec<f64> = ranges.iter().map(|r| f64::from(r.start)).collect();
+        Ok(self.reframed(volume, &offset))
+    }
+
+    /// Obtain a copy of this object cropped to the bounding box of its
+    /// non-zero voxels (see `InMemNiftiVolume::bounding_box`). If all voxels
+    /// are zero, the object is returned unchanged.
+    pub fn crop_to_bounding_box(&self) -> Result<InMemNiftiObject> {
+        match self.volume.bounding_box() {
+            Some(bbox) => self.crop(&bbox),
+            None => Ok(self.clone()),
+        }
+    }
+
+    /// Obtain a padded copy of this object, with the given number of
+    /// zero-valued voxels added before and after the existing ones in each
+    /// axis. The translation components of the qform and sform are shifted
+    /// so that the existing voxels keep their world coordinates.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::IncorrectVolumeDimensionality` if the number of
+    ///   padding amounts does not match the volume's dimensionality.
+    /// - `NiftiError::OutOfBounds` if the padded volume would be too large.
+    pub fn pad(&self, before: &[u16], after: &[u16]) -> Result<InMemNiftiObject> {
+        let volume = self.volume.pad(before, after)?;
+        let offset: Vec<f64> = before.iter().map(|b| -f64::from(*b)).collect();
+        Ok(self.reframed(volume, &offset))
+    }
+
+    /// Obtain a copy of this object padded to the given shape, keeping the
+    /// existing voxels at the center. When the amount of padding in an axis
+    /// is odd, the extra voxel is added at the end.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::IncorrectVolumeDimensionality` if the shape does not
+    ///   match the volume's dimensionality.
+    /// - `NiftiError::OutOfBounds` if the shape is smaller than the volume
+    ///   in any axis.
+    pub fn pad_to(&self, shape: &[u16]) -> Result<InMemNiftiObject> {
+        let dim = self.volume.dim();
+        if shape.len() != dim.len() {
+            return Err(NiftiError::IncorrectVolumeDimensionality(
+                dim.len() as u16,
+                shape.len() as u16,
+            ));
+        }
+        if shape.iter().zip(dim).any(|(s, d)| s < d) {
+            return Err(NiftiError::OutOfBounds(shape.to_vec()));
+        }
+        let before: Vec<u16> = shape.iter().zip(dim).map(|(s, d)| (s - d) / 2).collect();
+        let after: Vec<u16> = shape
+            .iter()
+            .zip(dim)
+            .zip(&before)
+            .map(|((s, d), b)| s - d - b)
+            .collect();
+        self.pad(&before, &after)
+    }
+
+    /// Convert a statistical map (such as a t, F or z statistic image) into
+    /// a map of p-values, `-log10(p)` values or equivalent z-scores, as
+    /// described by `output`. The distribution of the voxel values is given
+    /// by the header's intent code and parameters (see `stats::pvalue`). The
+    /// resulting object holds 32-bit floating point values, and its intent
+    /// is set to `Intent::Pval`, `Intent::Log10pval` or `Intent::Zscore`.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::InvalidCode` if the intent code is invalid.
+    /// - `NiftiError::UnsupportedIntent` if the intent is not a supported
+    ///   statistic.
+    /// - `NiftiError::InvalidIntentParameters` if the intent parameters are
+    ///   not valid for the statistic's distribution.
+    pub fn convert_stat(&self, output: StatOutput) -> Result<InMemNiftiObject> {
+        let intent = self.header.intent()?;
+        if !intent.is_statcode() {
+            return Err(NiftiError::UnsupportedIntent(intent));
+        }
+        let params = [
+            f64::from(self.header.intent_p1),
+            f64::from(self.header.intent_p2),
+            f64::from(self.header.intent_p3),
+        ];
+        // fail early on invalid parameters
+        let _ = stats::convert(0., intent, params, output)?;
+
+        let volume = self.volume.map_values(NiftiType::Float32, |v| {
+            stats::convert(v, intent, params, output).unwrap_or(f64::NAN)
+        })?;
+
+        let mut header = self.header.clone();
+        header.intent_code = output.intent() as i16;
+        header.intent_p1 = 0.;
+        header.intent_p2 = 0.;
+        header.intent_p3 = 0.;
+        header.datatype = NiftiType::Float32 as i16;
+        header.bitpix = 32;
+        header.scl_slope = 0.;
+        header.scl_inter = 0.;
+        header.cal_min = 0.;
+        header.cal_max = 0.;
+
+        Ok(InMemNiftiObject {
+            header,
+            extensions: self.extensions.clone(),
+            volume,
+        })
+    }
+
+    /// Build an object with a new voxel grid, where the voxel at index 0
+    /// corresponds to the index `offset` in this object's grid.
+    fn reframed(&self, volume: InMemNiftiVolume, offset: &[f64]) -> InMemNiftiObject {
+        let mut header = self.header.clone();
+        let ndim = volume.dimensionality();
+        header.dim[1..=ndim].copy_from_slice(volume.dim());
+
+        let mut o = [0.; 3];
+        for (v, off) in o.iter_mut().zip(offset) {
+            *v = *off;
+        }
+        let q = affine::transform_point(&self.header.qform_affine(), o);
+        header.quatern_x = q[0] as f32;
+        header.quatern_y = q[1] as f32;
+        header.quatern_z = q[2] as f32;
+        let s = affine::transform_point(&self.header.sform_affine(), o);
+        header.srow_x[3] = s[0] as f32;
+        header.srow_y[3] = s[1] as f32;
+        header.srow_z[3] = s[2] as f32;
+
+        InMemNiftiObject {
+            header,
+            extensions: self.extensions.clone(),
+            volume,
+        }
+    }
+}
+
 impl NiftiObject for InMemNiftiObject {
     type Volume = InMemNiftiVolume;
 
@@ -224,6 +606,10 @@ impl NiftiObject for InMemNiftiObject {
         &self.extensions
     }
 
+    fn extensions_mut(&mut self) -> &mut ExtensionSequence {
+        &mut self.extensions
+    }
+
     fn volume(&self) -> &Self::Volume {
         &self.volume
     }
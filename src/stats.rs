@@ -0,0 +1,392 @@
+//! This module provides conversions of statistical maps, such as
+//! `zstat1.nii.gz` or t-statistic images, into p-values and equivalent
+//! z-scores.
+//!
+//! The statistical distribution of each voxel value is defined by the
+//! header's intent code (see `Intent::is_statcode`), and its parameters
+//! (such as degrees of freedom) by `intent_p1`, `intent_p2` and `intent_p3`.
+//! All p-values are one-sided, describing the probability of obtaining a
+//! value at least as large as the voxel's (upper tail). The cumulative
+//! distribution functions are implemented in this crate, so no external
+//! dependencies are needed.
+
+use error::{NiftiError, Result};
+use std::f64::consts::{LN_10, PI, SQRT_2};
+use typedef::Intent;
+
+/// The kind of value to produce when converting a statistical map.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum StatOutput {
+    /// The p-value of the statistic (`Intent::Pval`).
+    Pvalue,
+    /// The negated base-10 logarithm of the p-value (`Intent::Log10pval`).
+    Log10Pvalue,
+    /// The z-score with the same p-value as the statistic (`Intent::Zscore`).
+    Zscore,
+}
+
+impl StatOutput {
+    /// The intent code describing the output values.
+    pub fn intent(self) -> Intent {
+        match self {
+            StatOutput::Pvalue => Intent::Pval,
+            StatOutput::Log10Pvalue => Intent::Log10pval,
+            StatOutput::Zscore => Intent::Zscore,
+        }
+    }
+
+    /// Produce the output value from a p-value.
+    pub fn from_pvalue(self, p: f64) -> f64 {
+        match self {
+            StatOutput::Pvalue => p,
+            StatOutput::Log10Pvalue => -p.log10(),
+            StatOutput::Zscore => zscore_from_pvalue(p),
+        }
+    }
+}
+
+/// Convert a statistic into the given kind of output value, where the
+/// statistic follows the distribution described by `intent` and the intent
+/// parameters `params` (`intent_p1`, `intent_p2` and `intent_p3`).
+///
+/// # Errors
+///
+/// See `pvalue`.
+pub fn convert(value: f64, intent: Intent, params: [f64; 3], output: StatOutput) -> Result<f64> {
+    match (intent, output) {
+        (Intent::Zscore, StatOutput::Zscore) => Ok(value),
+        _ => pvalue(value, intent, params).map(|p| output.from_pvalue(p)),
+    }
+}
+
+/// Calculate the one-sided (upper tail) p-value of a statistic, where the
+/// statistic follows the distribution described by `intent` and the intent
+/// parameters `params` (`intent_p1`, `intent_p2` and `intent_p3`). Values
+/// which are already p-values (`Pval`, `Logpval` and `Log10pval`) are
+/// converted back to a plain p-value.
+///
+/// # Errors
+///
+/// - `NiftiError::UnsupportedIntent` if the intent is not a statistic, or
+///   if the distribution is not supported.
+/// - `NiftiError::InvalidIntentParameters` if the parameters are not valid
+///   for the distribution (e.g. non-positive degrees of freedom).
+pub fn pvalue(value: f64, intent: Intent, params: [f64; 3]) -> Result<f64> {
+    let [p1, p2, _] = params;
+    let positive = |v: f64| {
+        if v > 0. && v.is_finite() {
+            Ok(v)
+        } else {
+            Err(NiftiError::InvalidIntentParameters(intent))
+        }
+    };
+
+    let p = match intent {
+        Intent::Correl => {
+            let dof = positive(p1)?;
+            if value >= 1. {
+                0.
+            } else if value <= -1. {
+                1.
+            } else {
+                t_sf(value * (dof / (1. - value * value)).sqrt(), dof)
+            }
+        }
+        Intent::Ttest => t_sf(value, positive(p1)?),
+        Intent::Ftest => f_sf(value, positive(p1)?, positive(p2)?),
+        Intent::Zscore => normal_sf(value),
+        Intent::Chisq => chisq_sf(value, positive(p1)?),
+        Intent::Beta => {
+            let (a, b) = (positive(p1)?, positive(p2)?);
+            if value <= 0. {
+                1.
+            } else if value >= 1. {
+                0.
+            } else {
+                beta_inc(b, a, 1. - value)
+            }
+        }
+        Intent::Gamma => {
+            let (shape, rate) = (positive(p1)?, positive(p2)?);
+            if value <= 0. {
+                1.
+            } else {
+                gamma_q(shape, rate * value)
+            }
+        }
+        Intent::Normal => normal_sf((value - p1) / positive(p2)?),
+        Intent::Logistic => 1. / (1. + ((value - p1) / positive(p2)?).exp()),
+        Intent::Laplace => {
+            let x = (value - p1) / positive(p2)?;
+            if x >= 0. {
+                0.5 * (-x).exp()
+            } else {
+                1. - 0.5 * x.exp()
+            }
+        }
+        Intent::Uniform => {
+            if p2 <= p1 {
+                return Err(NiftiError::InvalidIntentParameters(intent));
+            }
+            ((p2 - value) / (p2 - p1)).clamp(0., 1.)
+        }
+        Intent::Chi => {
+            let dof = positive(p1)?;
+            if value <= 0. {
+                1.
+            } else {
+                gamma_q(dof / 2., value * value / 2.)
+            }
+        }
+        Intent::Extval => {
+            let x = (value - p1) / positive(p2)?;
+            -(-(-x).exp()).exp_m1()
+        }
+        Intent::Pval => value,
+        Intent::Logpval => (-value.abs()).exp(),
+        Intent::Log10pval => (-value.abs() * LN_10).exp(),
+        _ => return Err(NiftiError::UnsupportedIntent(intent)),
+    };
+    Ok(p.clamp(0., 1.))
+}
+
+/// Calculate the z-score which has the given one-sided (upper tail)
+/// p-value, so that `pvalue(z, Intent::Zscore, _) == p`.
+pub fn zscore_from_pvalue(p: f64) -> f64 {
+    if p <= 0. {
+        return f64::INFINITY;
+    }
+    if p >= 1. {
+        return f64::NEG_INFINITY;
+    }
+    -normal_quantile(p)
+}
+
+/// Survival function (upper tail) of the standard normal distribution.
+fn normal_sf(z: f64) -> f64 {
+    0.5 * erfc(z / SQRT_2)
+}
+
+/// Survival function (upper tail) of Student's t distribution.
+fn t_sf(t: f64, dof: f64) -> f64 {
+    let tail = 0.5 * beta_inc(dof / 2., 0.5, dof / (dof + t * t));
+    if t > 0. {
+        tail
+    } else {
+        1. - tail
+    }
+}
+
+/// Survival function (upper tail) of the F distribution.
+fn f_sf(f: f64, dof_num: f64, dof_den: f64) -> f64 {
+    if f <= 0. {
+        return 1.;
+    }
+    beta_inc(dof_den / 2., dof_num / 2., dof_den / (dof_den + dof_num * f))
+}
+
+/// Survival function (upper tail) of the chi-squared distribution.
+fn chisq_sf(x: f64, dof: f64) -> f64 {
+    if x <= 0. {
+        return 1.;
+    }
+    gamma_q(dof / 2., x / 2.)
+}
+
+/// Complementary error function.
+fn erfc(x: f64) -> f64 {
+    if x >= 0. {
+        gamma_q(0.5, x * x)
+    } else {
+        2. - gamma_q(0.5, x * x)
+    }
+}
+
+/// Quantile function (inverse CDF) of the standard normal distribution,
+/// using Acklam's rational approximation refined by a Halley step.
+fn normal_quantile(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969_683_028_665_376e1,
+        2.209_460_984_245_205e2,
+        -2.759_285_104_469_687e2,
+        1.383_577_518_672_69e2,
+        -3.066_479_806_614_716e1,
+        2.506_628_277_459_239,
+    ];
+    const B: [f64; 5] = [
+        -5.447_609_879_822_406e1,
+        1.615_858_368_580_409e2,
+        -1.556_989_798_598_866e2,
+        6.680_131_188_771_972e1,
+        -1.328_068_155_288_572e1,
+    ];
+    const C: [f64; 6] = [
+        -7.784_894_002_430_293e-3,
+        -3.223_964_580_411_365e-1,
+        -2.400_758_277_161_838,
+        -2.549_732_539_343_734,
+        4.374_664_141_464_968,
+        2.938_163_982_698_783,
+    ];
+    const D: [f64; 4] = [
+        7.784_695_709_041_462e-3,
+        3.224_671_290_700_398e-1,
+        2.445_134_137_142_996,
+        3.754_408_661_907_416,
+    ];
+    const P_LOW: f64 = 0.024_25;
+
+    let tail = |q: f64| {
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.)
+    };
+    let x = if p < P_LOW {
+        tail((-2. * p.ln()).sqrt())
+    } else if p <= 1. - P_LOW {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.)
+    } else {
+        -tail((-2. * (1. - p).ln()).sqrt())
+    };
+
+    // refine with one step of Halley's method
+    let e = 0.5 * erfc(-x / SQRT_2) - p;
+    let u = e * (2. * PI).sqrt() * (x * x / 2.).exp();
+    x - u / (1. + x * u / 2.)
+}
+
+/// Natural logarithm of the gamma function (Lanczos approximation).
+fn ln_gamma(x: f64) -> f64 {
+    const COEFS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        // reflection formula
+        return (PI / (PI * x).sin()).ln() - ln_gamma(1. - x);
+    }
+    let x = x - 1.;
+    let t = x + 7.5;
+    let sum = COEFS[1..]
+        .iter()
+        .enumerate()
+        .fold(COEFS[0], |acc, (i, c)| acc + c / (x + i as f64 + 1.));
+    0.5 * (2. * PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
+const MAX_ITERATIONS: usize = 500;
+const EPSILON: f64 = 1e-15;
+const TINY: f64 = 1e-300;
+
+/// Regularized upper incomplete gamma function Q(a, x).
+fn gamma_q(a: f64, x: f64) -> f64 {
+    if x <= 0. {
+        return 1.;
+    }
+    if x < a + 1. {
+        return 1. - gamma_p_series(a, x);
+    }
+
+    // continued fraction (modified Lentz's method)
+    let mut b = x + 1. - a;
+    let mut c = 1. / TINY;
+    let mut d = 1. / b;
+    let mut h = d;
+    for i in 1..MAX_ITERATIONS {
+        let an = -(i as f64) * (i as f64 - a);
+        b += 2.;
+        d = an * d + b;
+        if d.abs() < TINY {
+            d = TINY;
+        }
+        c = b + an / c;
+        if c.abs() < TINY {
+            c = TINY;
+        }
+        d = 1. / d;
+        let delta = d * c;
+        h *= delta;
+        if (delta - 1.).abs() < EPSILON {
+            break;
+        }
+    }
+    (-x + a * x.ln() - ln_gamma(a)).exp() * h
+}
+
+/// Regularized lower incomplete gamma function P(a, x), by series
+/// expansion (converges quickly for `x < a + 1`).
+fn gamma_p_series(a: f64, x: f64) -> f64 {
+    let mut ap = a;
+    let mut del = 1. / a;
+    let mut sum = del;
+    for _ in 0..MAX_ITERATIONS {
+        ap += 1.;
+        del *= x / ap;
+        sum += del;
+        if del.abs() < sum.abs() * EPSILON {
+            break;
+        }
+    }
+    sum * (-x + a * x.ln() - ln_gamma(a)).exp()
+}
+
+/// Regularized incomplete beta function I_x(a, b).
+fn beta_inc(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0. {
+        return 0.;
+    }
+    if x >= 1. {
+        return 1.;
+    }
+    let front = (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1. - x).ln()).exp();
+    if x < (a + 1.) / (a + b + 2.) {
+        front * beta_cf(a, b, x) / a
+    } else {
+        1. - front * beta_cf(b, a, 1. - x) / b
+    }
+}
+
+/// Continued fraction for the incomplete beta function (modified Lentz's
+/// method).
+fn beta_cf(a: f64, b: f64, x: f64) -> f64 {
+    let (qab, qap, qam) = (a + b, a + 1., a - 1.);
+    let mut c = 1.;
+    let mut d = 1. - qab * x / qap;
+    if d.abs() < TINY {
+        d = TINY;
+    }
+    d = 1. / d;
+    let mut h = d;
+    for m in 1..MAX_ITERATIONS {
+        let m = m as f64;
+        let m2 = 2. * m;
+        for aa in &[
+            m * (b - m) * x / ((qam + m2) * (a + m2)),
+            -(a + m) * (qab + m) * x / ((a + m2) * (qap + m2)),
+        ] {
+            d = 1. + aa * d;
+            if d.abs() < TINY {
+                d = TINY;
+            }
+            c = 1. + aa / c;
+            if c.abs() < TINY {
+                c = TINY;
+            }
+            d = 1. / d;
+            h *= d * c;
+        }
+        if (d * c - 1.).abs() < EPSILON {
+            break;
+        }
+    }
+    h
+}
@@ -183,6 +183,42 @@ pub enum Unit {
     Rads = 48,
 }
 
+impl Unit {
+    /// Whether this is a unit of space (or unknown).
+    pub fn is_space(self) -> bool {
+        (self as u8) < 8
+    }
+
+    /// Whether this is a unit of time, including spectral units (or unknown).
+    pub fn is_time(self) -> bool {
+        self == Unit::Unknown || (self as u8) >= 8
+    }
+
+    /// Obtain the number of millimeters in one of this unit. Unknown units
+    /// are assumed to be millimeters. Returns `None` for units which are not
+    /// spatial.
+    pub fn millimeters(self) -> Option<f64> {
+        match self {
+            Unit::Meter => Some(1e3),
+            Unit::Mm | Unit::Unknown => Some(1.),
+            Unit::Micron => Some(1e-3),
+            _ => None,
+        }
+    }
+
+    /// Obtain the number of seconds in one of this unit. Unknown units are
+    /// assumed to be seconds. Returns `None` for units which are not
+    /// temporal (including spectral units).
+    pub fn seconds(self) -> Option<f64> {
+        match self {
+            Unit::Sec | Unit::Unknown => Some(1.),
+            Unit::Msec => Some(1e-3),
+            Unit::Usec => Some(1e-6),
+            _ => None,
+        }
+    }
+}
+
 /// An enum type for representing a NIFTI intent code.
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, FromPrimitive)]
 #[repr(u16)]
@@ -382,6 +418,58 @@ impl Intent {
     }
 }
 
+/// An enum type for representing the registered NIfTI extension codes
+/// (`ecode` field), which identify the format of an extension's data.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, FromPrimitive)]
+#[repr(i32)]
+pub enum ExtensionCode {
+    /// NIFTI_ECODE_IGNORE: extension to be ignored.
+    Ignore = 0,
+    /// NIFTI_ECODE_DICOM: DICOM attributes, in binary form.
+    Dicom = 2,
+    /// NIFTI_ECODE_AFNI: AFNI header attributes, in XML.
+    Afni = 4,
+    /// NIFTI_ECODE_COMMENT: plain text comment.
+    Comment = 6,
+    /// NIFTI_ECODE_XCEDE: XCEDE metadata, in XML.
+    Xcede = 8,
+    /// NIFTI_ECODE_JIMDIMINFO: dimensional information for the JIM
+    /// software.
+    JimDimInfo = 10,
+    /// NIFTI_ECODE_WORKFLOW_FWDS: Fiswidgets workflow.
+    WorkflowFwds = 12,
+    /// NIFTI_ECODE_FREESURFER: FreeSurfer data.
+    Freesurfer = 14,
+    /// NIFTI_ECODE_PYPICKLE: pickled Python objects.
+    PyPickle = 16,
+    /// NIFTI_ECODE_MIND_IDENT: MiND data set identifier.
+    MindIdent = 18,
+    /// NIFTI_ECODE_B_VALUE: diffusion b-value.
+    BValue = 20,
+    /// NIFTI_ECODE_SPHERICAL_DIRECTION: diffusion gradient direction.
+    SphericalDirection = 22,
+    /// NIFTI_ECODE_DT_COMPONENT: diffusion tensor component.
+    DtComponent = 24,
+    /// NIFTI_ECODE_SHC_DEGREEORDER: spherical harmonic degree and order.
+    ShcDegreeOrder = 26,
+    /// NIFTI_ECODE_VOXBO: VoxBo data.
+    Voxbo = 28,
+    /// NIFTI_ECODE_CARET: Caret metadata, in XML.
+    Caret = 30,
+    /// NIFTI_ECODE_CIFTI: CIFTI-2 metadata, in XML.
+    Cifti = 32,
+    /// NIFTI_ECODE_VARIABLE_FRAME_TIMING: timing of each volume.
+    VariableFrameTiming = 34,
+    /// NIFTI_ECODE_EVAL: Eindhoven University of Technology data.
+    Eval = 38,
+    /// NIFTI_ECODE_MATLAB: MATLAB data.
+    Matlab = 40,
+    /// NIFTI_ECODE_QUANTIPHYSE: Quantiphyse metadata.
+    Quantiphyse = 42,
+    /// NIFTI_ECODE_MRS: NIfTI-MRS header, in JSON.
+    Mrs = 44,
+}
+
 /// An enum type for representing a NIFTI XForm.
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, FromPrimitive)]
 #[repr(u16)]
@@ -419,3 +507,84 @@ pub enum SliceOrder {
     /// NIFTI_SLICE_ALT_DEC2
     AltDec2 = 6,
 }
+
+impl SliceOrder {
+    /// Obtain the acquisition order of `n` consecutive slices, where the
+    /// element at position `k` is the (zero-based) index of the `k`-th
+    /// acquired slice. Returns `None` if the order is unknown.
+    pub fn acquisition_order(self, n: u16) -> Option<Vec<u16>> {
+        let even = (0..n).step_by(2);
+        let odd = (1..n).step_by(2);
+        let order: Vec<u16> = match self {
+            SliceOrder::Unknown => return None,
+            SliceOrder::SeqInc => (0..n).collect(),
+            SliceOrder::SeqDec => (0..n).rev().collect(),
+            SliceOrder::AltInc => even.chain(odd).collect(),
+            SliceOrder::AltInc2 => odd.chain(even).collect(),
+            SliceOrder::AltDec => {
+                let order: Vec<u16> = SliceOrder::AltInc.acquisition_order(n)?;
+                order.into_iter().map(|i| n - 1 - i).collect()
+            }
+            SliceOrder::AltDec2 => {
+                let order: Vec<u16> = SliceOrder::AltInc2.acquisition_order(n)?;
+                order.into_iter().map(|i| n - 1 - i).collect()
+            }
+        };
+        Some(order)
+    }
+
+    /// Obtain the acquisition time of each of `n` consecutive slices, in the
+    /// same unit as `duration` (the time to acquire one slice). The first
+    /// acquired slice has a time of zero. Returns `None` if the order is
+    /// unknown.
+    pub fn slice_times(self, n: u16, duration: f64) -> Option<Vec<f64>> {
+        let order = self.acquisition_order(n)?;
+        let mut times = vec![0.; n as usize];
+        for (k, i) in order.into_iter().enumerate() {
+            times[i as usize] = k as f64 * duration;
+        }
+        Some(times)
+    }
+
+    /// Infer the slice order and slice duration from the acquisition time
+    /// of each slice, such as the `SliceTiming` field of a BIDS sidecar.
+    /// Returns `None` if the times do not follow any of the known orders
+    /// with a constant slice duration (as in multiband acquisitions).
+    pub fn from_slice_times(times: &[f64]) -> Option<(SliceOrder, f64)> {
+        let n = times.len();
+        if n < 2 || n > usize::from(u16::MAX) {
+            return None;
+        }
+        let min = times.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = times.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let duration = (max - min) / (n - 1) as f64;
+        if duration.is_nan() || duration <= 0. {
+            return None;
+        }
+
+        // rank of each slice in the acquisition
+        let mut ranks = Vec::with_capacity(n);
+        for t in times {
+            let k = (t - min) / duration;
+            if (k - k.round()).abs() > 0.1 {
+                return None;
+            }
+            ranks.push(k.round() as usize);
+        }
+
+        [
+            SliceOrder::SeqInc,
+            SliceOrder::SeqDec,
+            SliceOrder::AltInc,
+            SliceOrder::AltDec,
+            SliceOrder::AltInc2,
+            SliceOrder::AltDec2,
+        ]
+            .iter()
+            .find(|order| {
+                let expected = order.acquisition_order(n as u16).unwrap();
+                expected.iter().enumerate().all(|(k, i)| ranks[*i as usize] == k)
+            })
+            .map(|order| (*order, duration))
+    }
+}
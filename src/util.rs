@@ -1,11 +1,14 @@
 //! Private utility module
-use std::io::{Read, Result as IoResult, Seek};
+use std::io::{BufRead, Chain, Cursor, Read, Result as IoResult, Seek};
 use std::mem;
 use std::path::{Path, PathBuf};
-use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
+use std::io::Write;
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt, WriteBytesExt};
 
+use flate2::bufread::GzDecoder;
 use safe_transmute::{guarded_transmute_pod_vec_permissive, PodTransmutable};
 
+use error::{NiftiError, Result};
 use NiftiHeader;
 
 /// A trait that is both Read and Seek.
@@ -130,6 +133,94 @@ impl Endianness {
             Endianness::BE => src.read_f64::<BigEndian>(),
         }
     }
+
+    /// Write a primitive value with this endianness to the given destination.
+    pub fn write_i16<S>(&self, mut dst: S, value: i16) -> IoResult<()>
+    where
+        S: Write,
+    {
+        match *self {
+            Endianness::LE => dst.write_i16::<LittleEndian>(value),
+            Endianness::BE => dst.write_i16::<BigEndian>(value),
+        }
+    }
+
+    /// Write a primitive value with this endianness to the given destination.
+    pub fn write_u16<S>(&self, mut dst: S, value: u16) -> IoResult<()>
+    where
+        S: Write,
+    {
+        match *self {
+            Endianness::LE => dst.write_u16::<LittleEndian>(value),
+            Endianness::BE => dst.write_u16::<BigEndian>(value),
+        }
+    }
+
+    /// Write a primitive value with this endianness to the given destination.
+    pub fn write_i32<S>(&self, mut dst: S, value: i32) -> IoResult<()>
+    where
+        S: Write,
+    {
+        match *self {
+            Endianness::LE => dst.write_i32::<LittleEndian>(value),
+            Endianness::BE => dst.write_i32::<BigEndian>(value),
+        }
+    }
+
+    /// Write a primitive value with this endianness to the given destination.
+    pub fn write_u32<S>(&self, mut dst: S, value: u32) -> IoResult<()>
+    where
+        S: Write,
+    {
+        match *self {
+            Endianness::LE => dst.write_u32::<LittleEndian>(value),
+            Endianness::BE => dst.write_u32::<BigEndian>(value),
+        }
+    }
+
+    /// Write a primitive value with this endianness to the given destination.
+    pub fn write_i64<S>(&self, mut dst: S, value: i64) -> IoResult<()>
+    where
+        S: Write,
+    {
+        match *self {
+            Endianness::LE => dst.write_i64::<LittleEndian>(value),
+            Endianness::BE => dst.write_i64::<BigEndian>(value),
+        }
+    }
+
+    /// Write a primitive value with this endianness to the given destination.
+    pub fn write_u64<S>(&self, mut dst: S, value: u64) -> IoResult<()>
+    where
+        S: Write,
+    {
+        match *self {
+            Endianness::LE => dst.write_u64::<LittleEndian>(value),
+            Endianness::BE => dst.write_u64::<BigEndian>(value),
+        }
+    }
+
+    /// Write a primitive value with this endianness to the given destination.
+    pub fn write_f32<S>(&self, mut dst: S, value: f32) -> IoResult<()>
+    where
+        S: Write,
+    {
+        match *self {
+            Endianness::LE => dst.write_f32::<LittleEndian>(value),
+            Endianness::BE => dst.write_f32::<BigEndian>(value),
+        }
+    }
+
+    /// Write a primitive value with this endianness to the given destination.
+    pub fn write_f64<S>(&self, mut dst: S, value: f64) -> IoResult<()>
+    where
+        S: Write,
+    {
+        match *self {
+            Endianness::LE => dst.write_f64::<LittleEndian>(value),
+            Endianness::BE => dst.write_f64::<BigEndian>(value),
+        }
+    }
 }
 
 /// Defines the serialization that is opposite to system native-endian.
@@ -180,6 +271,85 @@ pub fn is_gz_file<P: AsRef<Path>>(path: P) -> bool {
         .unwrap_or(false)
 }
 
+/// A reader which yields a few bytes read ahead, then the rest of a stream.
+pub type Peeked<R> = Chain<Cursor<Vec<u8>>, R>;
+
+/// The compression formats which NIfTI files can be stored with. Formats
+/// other than Gzip require the Cargo feature of the same name.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum CompressionFormat {
+    /// No compression
+    None,
+    /// Gzip (".gz")
+    Gzip,
+    /// Zstandard (".zst")
+    Zstd,
+    /// Bzip2 (".bz2")
+    Bzip2,
+    /// XZ (".xz")
+    Xz,
+}
+
+impl CompressionFormat {
+    /// Obtain the compression format of a stream from its first bytes.
+    pub fn from_magic(magic: &[u8]) -> CompressionFormat {
+        if magic.starts_with(&[0x1f, 0x8b]) {
+            CompressionFormat::Gzip
+        } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            CompressionFormat::Zstd
+        } else if magic.starts_with(b"BZh") {
+            CompressionFormat::Bzip2
+        } else if magic.starts_with(b"\xfd7zXZ\0") {
+            CompressionFormat::Xz
+        } else {
+            CompressionFormat::None
+        }
+    }
+
+    /// The Cargo feature required to support this format.
+    pub fn feature(self) -> &'static str {
+        match self {
+            CompressionFormat::None | CompressionFormat::Gzip => "",
+            CompressionFormat::Zstd => "zstd",
+            CompressionFormat::Bzip2 => "bzip2",
+            CompressionFormat::Xz => "xz2",
+        }
+    }
+}
+
+/// Peek at the first bytes of a stream for the magic number of a
+/// compression format. Returns the format, along with a reader which
+/// yields the full stream, including the bytes already consumed.
+pub fn sniff_compression<R: Read>(mut source: R) -> IoResult<(CompressionFormat, Peeked<R>)> {
+    let mut magic = Vec::with_capacity(6);
+    let _ = source.by_ref().take(6).read_to_end(&mut magic)?;
+    let format = CompressionFormat::from_magic(&magic);
+    Ok((format, Cursor::new(magic).chain(source)))
+}
+
+/// Wrap a stream with the decoder of the compression format detected from
+/// its first bytes, if any.
+///
+/// # Errors
+///
+/// - `NiftiError::UnsupportedCompression` if the stream is compressed with a
+///   format whose Cargo feature is not enabled.
+pub fn decompress<'a, R: BufRead + 'a>(source: R) -> Result<Box<dyn Read + 'a>> {
+    let (format, stream) = sniff_compression(source)?;
+    Ok(match format {
+        CompressionFormat::None => Box::new(stream),
+        CompressionFormat::Gzip => Box::new(GzDecoder::new(stream)),
+        #[cfg(feature = "zstd")]
+        CompressionFormat::Zstd => Box::new(::zstd::stream::read::Decoder::with_buffer(stream)?),
+        #[cfg(feature = "bzip2")]
+        CompressionFormat::Bzip2 => Box::new(::bzip2::bufread::BzDecoder::new(stream)),
+        #[cfg(feature = "xz2")]
+        CompressionFormat::Xz => Box::new(::xz2::bufread::XzDecoder::new(stream)),
+        #[allow(unreachable_patterns)]
+        f => return Err(NiftiError::UnsupportedCompression(f.feature())),
+    })
+}
+
 /// Convert a file path to a header file (.hdr or .hdr.gz) to
 /// the respective volume file with GZip compression (.img.gz).
 ///
@@ -200,6 +370,34 @@ pub fn into_img_file_gz(mut path: PathBuf) -> PathBuf {
     path
 }
 
+/// Reorder the voxels of column-major data, so that the axes come in the
+/// given order.
+pub fn reorder_axes(data: &[u8], sizes: &[usize], order: &[usize], voxel_size: usize) -> Vec<u8> {
+    let mut strides = Vec::with_capacity(sizes.len());
+    let mut stride = voxel_size;
+    for s in sizes {
+        strides.push(stride);
+        stride *= s;
+    }
+    let new_sizes: Vec<usize> = order.iter().map(|&a| sizes[a]).collect();
+    let new_strides: Vec<usize> = order.iter().map(|&a| strides[a]).collect();
+
+    let mut out = Vec::with_capacity(data.len());
+    let mut index = vec![0; sizes.len()];
+    for _ in 0..data.len() / voxel_size {
+        let offset: usize = index.iter().zip(&new_strides).map(|(i, s)| i * s).sum();
+        out.extend_from_slice(&data[offset..offset + voxel_size]);
+        for (i, &s) in index.iter_mut().zip(&new_sizes) {
+            *i += 1;
+            if *i < s {
+                break;
+            }
+            *i = 0;
+        }
+    }
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::Endianness;
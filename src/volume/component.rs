@@ -0,0 +1,298 @@
+//! Module for volumes holding multiple components per voxel, such as
+//! vector fields (e.g. displacement fields) or matrices (e.g. diffusion
+//! tensors).
+//!
+//! As described by the standard, these components are stored along the 5th
+//! dimension (`dim[5]`), leaving the first four dimensions for space and
+//! time. How the components are interpreted is declared by the header's
+//! intent (see `IntentSpec`). Symmetric matrices only hold their lower
+//! triangle, row by row, which is unpacked into the full matrix by the
+//! accessors here.
+
+use error::{NiftiError, Result};
+use header::NiftiHeader;
+use intent::IntentSpec;
+use volume::NiftiVolume;
+
+#[cfg(feature = "ndarray_volumes")]
+use ndarray::{Array, IxDyn, ShapeBuilder};
+#[cfg(feature = "ndarray_volumes")]
+use num_traits::AsPrimitive;
+#[cfg(feature = "ndarray_volumes")]
+use std::ops::{Add, Mul};
+#[cfg(feature = "ndarray_volumes")]
+use volume::element::DataElement;
+#[cfg(feature = "ndarray_volumes")]
+use volume::ndarray::IntoNdArray;
+
+/// The layout of the components stored at each voxel.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum ComponentLayout {
+    /// A vector with the given number of components.
+    Vector(u16),
+    /// A general matrix, stored in row-major order.
+    Matrix {
+        /// Number of rows
+        rows: u16,
+        /// Number of columns
+        cols: u16,
+    },
+    /// An `n` x `n` symmetric matrix, of which only the lower triangle is
+    /// stored, row by row.
+    SymMatrix(u16),
+}
+
+impl ComponentLayout {
+    /// Obtain the component layout declared by a header. `Genmatrix` and
+    /// `Symmatrix` intents are read as matrices, while `Vector`,
+    /// `Dispvect`, `Pointset`, `Triangle`, `Quaternion`, `RgbVector` and
+    /// `RgbaVector` are read as vectors.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::IncorrectVolumeDimensionality` if the header does not
+    ///   declare exactly 5 dimensions.
+    /// - `NiftiError::UnsupportedIntent` if the intent does not describe
+    ///   multiple components per voxel.
+    /// - `NiftiError::InvalidIntentParameters` if the matrix dimensions in
+    ///   the intent parameters do not match `dim[5]`.
+    pub fn from_header(header: &NiftiHeader) -> Result<ComponentLayout> {
+        if header.dim[0] != 5 {
+            return Err(NiftiError::IncorrectVolumeDimensionality(5, header.dim[0]));
+        }
+        let ncomp = header.dim[5];
+        let spec = header.intent_spec()?;
+        let layout = match spec {
+            IntentSpec::Genmatrix { rows, cols } => ComponentLayout::Matrix { rows, cols },
+            IntentSpec::Symmatrix { n } => ComponentLayout::SymMatrix(n),
+            IntentSpec::Vector
+            | IntentSpec::Dispvect
+            | IntentSpec::Pointset { .. }
+            | IntentSpec::Triangle
+            | IntentSpec::Quaternion
+            | IntentSpec::RgbVector
+            | IntentSpec::RgbaVector => ComponentLayout::Vector(ncomp),
+            _ => return Err(NiftiError::UnsupportedIntent(spec.intent())),
+        };
+        if layout.num_components() != ncomp as usize {
+            return Err(NiftiError::InvalidIntentParameters(spec.intent()));
+        }
+        Ok(layout)
+    }
+
+    /// The number of components stored at each voxel.
+    pub fn num_components(self) -> usize {
+        match self {
+            ComponentLayout::Vector(n) => n as usize,
+            ComponentLayout::Matrix { rows, cols } => rows as usize * cols as usize,
+            ComponentLayout::SymMatrix(n) => n as usize * (n as usize + 1) / 2,
+        }
+    }
+
+    /// The shape of each voxel's value: `[n]` for vectors and
+    /// `[rows, cols]` for matrices.
+    pub fn shape(self) -> Vec<usize> {
+        match self {
+            ComponentLayout::Vector(n) => vec![n as usize],
+            ComponentLayout::Matrix { rows, cols } => vec![rows as usize, cols as usize],
+            ComponentLayout::SymMatrix(n) => vec![n as usize, n as usize],
+        }
+    }
+
+    /// The matrix shape as `(rows, cols)`, where a vector is a single
+    /// column.
+    fn matrix_shape(self) -> (usize, usize) {
+        match self {
+            ComponentLayout::Vector(n) => (n as usize, 1),
+            ComponentLayout::Matrix { rows, cols } => (rows as usize, cols as usize),
+            ComponentLayout::SymMatrix(n) => (n as usize, n as usize),
+        }
+    }
+
+    /// The index of the stored component holding the matrix element at
+    /// the given row and column.
+    fn component_index(self, row: usize, col: usize) -> usize {
+        match self {
+            ComponentLayout::Vector(_) => row,
+            ComponentLayout::Matrix { cols, .. } => row * cols as usize + col,
+            ComponentLayout::SymMatrix(_) => {
+                let (i, j) = if row >= col { (row, col) } else { (col, row) };
+                i * (i + 1) / 2 + j
+            }
+        }
+    }
+}
+
+/// A volume with multiple components per voxel. The underlying volume
+/// must have exactly 5 dimensions, the last one being the components.
+///
+/// # Examples
+///
+/// ```no_run
+/// use nifti::{ComponentVolume, InMemNiftiObject, NiftiObject};
+/// # use nifti::Result;
+///
+/// # fn run() -> Result<()> {
+/// let obj = InMemNiftiObject::from_file("tensor.nii.gz")?;
+/// let tensors = ComponentVolume::new(obj.volume(), obj.header())?;
+/// let d = tensors.get_matrix(&[32, 32, 20, 0])?;
+/// println!("Dxy = {}", d[0][1]);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub struct ComponentVolume<V> {
+    volume: V,
+    layout: ComponentLayout,
+}
+
+impl<V> ComponentVolume<V>
+where
+    V: NiftiVolume,
+{
+    /// Wrap a volume, with the component layout declared by its header.
+    ///
+    /// # Errors
+    ///
+    /// - See `ComponentLayout::from_header`.
+    /// - `NiftiError::IncorrectVolumeDimensionality` if the volume's
+    ///   dimensions do not match the layout.
+    pub fn new(volume: V, header: &NiftiHeader) -> Result<Self> {
+        let layout = ComponentLayout::from_header(header)?;
+        Self::with_layout(volume, layout)
+    }
+
+    /// Wrap a volume, with the given component layout.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::IncorrectVolumeDimensionality` if the volume does not
+    ///   have 5 dimensions, or if the size of the 5th dimension does not
+    ///   match the layout's number of components.
+    pub fn with_layout(volume: V, layout: ComponentLayout) -> Result<Self> {
+        let dim = volume.dim();
+        if dim.len() != 5 {
+            return Err(NiftiError::IncorrectVolumeDimensionality(5, dim.len() as u16));
+        }
+        if dim[4] as usize != layout.num_components() {
+            return Err(NiftiError::IncorrectVolumeDimensionality(
+                layout.num_components() as u16,
+                dim[4],
+            ));
+        }
+        Ok(ComponentVolume { volume, layout })
+    }
+
+    /// Get the component layout.
+    pub fn layout(&self) -> ComponentLayout {
+        self.layout
+    }
+
+    /// Get the voxel dimensions, excluding the components (`[x, y, z, t]`).
+    pub fn dim(&self) -> &[u16] {
+        &self.volume.dim()[..4]
+    }
+
+    /// Get a reference to the underlying volume.
+    pub fn volume(&self) -> &V {
+        &self.volume
+    }
+
+    /// Consume the wrapper, returning the underlying volume.
+    pub fn into_volume(self) -> V {
+        self.volume
+    }
+
+    /// Fetch the components stored at the given voxel (`[x, y, z, t]`), in
+    /// storage order. Symmetric matrices are not unpacked.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::IncorrectVolumeDimensionality` if the coordinates do
+    ///   not have 4 elements.
+    /// - `NiftiError::OutOfBounds` if the coordinates surpass the volume's
+    ///   boundaries.
+    pub fn get_components(&self, coords: &[u16]) -> Result<Vec<f64>> {
+        let mut coords = self.component_coords(coords)?;
+        (0..self.layout.num_components())
+            .map(|c| {
+                coords[4] = c as u16;
+                self.volume.get_f64(&coords)
+            })
+            .collect()
+    }
+
+    /// Fetch the matrix stored at the given voxel (`[x, y, z, t]`), as a
+    /// list of rows. Symmetric matrices are unpacked into the full matrix,
+    /// and vectors are returned as a single column.
+    ///
+    /// # Errors
+    ///
+    /// - See `get_components`.
+    pub fn get_matrix(&self, coords: &[u16]) -> Result<Vec<Vec<f64>>> {
+        let components = self.get_components(coords)?;
+        let (rows, cols) = self.layout.matrix_shape();
+        Ok((0..rows)
+            .map(|r| {
+                (0..cols)
+                    .map(|c| components[self.layout.component_index(r, c)])
+                    .collect()
+            })
+            .collect())
+    }
+
+    fn component_coords(&self, coords: &[u16]) -> Result<[u16; 5]> {
+        if coords.len() != 4 {
+            return Err(NiftiError::IncorrectVolumeDimensionality(4, coords.len() as u16));
+        }
+        let dim = self.dim();
+        if coords.iter().zip(dim).any(|(c, d)| c >= d) {
+            return Err(NiftiError::OutOfBounds(coords.to_vec()));
+        }
+        Ok([coords[0], coords[1], coords[2], coords[3], 0])
+    }
+}
+
+#[cfg(feature = "ndarray_volumes")]
+impl<V> ComponentVolume<V>
+where
+    V: NiftiVolume + IntoNdArray,
+{
+    /// Consume the volume into an ndarray with the given element type `T`.
+    /// Vectors are shaped `[x, y, z, t, n]` and matrices are shaped
+    /// `[x, y, z, t, rows, cols]`, with symmetric matrices unpacked into the
+    /// full matrix. The time axis is kept even if the volume has a single
+    /// time point (use `index_axis(Axis(3), 0)` to drop it).
+    pub fn into_ndarray<T>(self) -> Result<Array<T, IxDyn>>
+    where
+        T: Mul<Output = T>,
+        T: Add<Output = T>,
+        T: DataElement,
+        u8: AsPrimitive<T>,
+        i8: AsPrimitive<T>,
+        u16: AsPrimitive<T>,
+        i16: AsPrimitive<T>,
+        u32: AsPrimitive<T>,
+        i32: AsPrimitive<T>,
+        u64: AsPrimitive<T>,
+        i64: AsPrimitive<T>,
+        f32: AsPrimitive<T>,
+        f64: AsPrimitive<T>,
+    {
+        let layout = self.layout;
+        let data = self.volume.into_ndarray::<T>()?;
+        if let ComponentLayout::Vector(_) = layout {
+            return Ok(data);
+        }
+
+        let (rows, cols) = layout.matrix_shape();
+        let mut shape = data.shape()[..4].to_vec();
+        shape.push(rows);
+        shape.push(cols);
+
+        Ok(Array::from_shape_fn(IxDyn(&shape).f(), |idx| {
+            let c = layout.component_index(idx[4], idx[5]);
+            data[[idx[0], idx[1], idx[2], idx[3], c].as_ref()]
+        }))
+    }
+}
@@ -1,18 +1,17 @@
 //! Module holding an in-memory implementation of a NIfTI volume.
 
 use super::NiftiVolume;
-use super::util::coords_to_index;
+use super::util::{coords_to_index, encode_value};
 use std::io::{BufReader, Read};
 use std::fs::File;
 use std::path::Path;
-use std::ops::{Add, Mul};
+use std::ops::{Add, Mul, Range};
 use header::NiftiHeader;
 use extension::{Extender, ExtensionSequence};
 use error::{NiftiError, Result};
 use volume::element::DataElement;
-use util::{Endianness, nb_bytes_for_data};
+use util::{decompress, Endianness, nb_bytes_for_data};
 use byteorder::{BigEndian, LittleEndian};
-use flate2::bufread::GzDecoder;
 use typedef::NiftiType;
 use num_traits::{AsPrimitive, Num};
 
@@ -111,27 +110,21 @@ impl InMemNiftiVolume {
     }
 
     /// Read a NIFTI volume from an image file. NIFTI-1 volume files usually have the
-    /// extension ".img" or ".img.gz". In the latter case, the file is automatically
-    /// decoded as a Gzip stream.
+    /// extension ".img" or ".img.gz". Compressed files are detected from their first
+    /// bytes and automatically decoded, regardless of their extension. Gzip is always
+    /// supported, while Zstandard, Bzip2 and XZ require the `zstd`, `bzip2` and `xz2`
+    /// features respectively.
     pub fn from_file<P: AsRef<Path>>(
         path: P,
         header: &NiftiHeader,
     ) -> Result<Self> {
-        let gz = path.as_ref()
-            .extension()
-            .map(|a| a.to_string_lossy() == "gz")
-            .unwrap_or(false);
         let file = BufReader::new(File::open(path)?);
-        if gz {
-            InMemNiftiVolume::from_stream(GzDecoder::new(file), &header)
-        } else {
-            InMemNiftiVolume::from_stream(file, &header)
-        }
+        InMemNiftiVolume::from_stream(decompress(file)?, &header)
     }
 
     /// Read a NIFTI volume, along with the extensions, from an image file. NIFTI-1 volume
-    /// files usually have the extension ".img" or ".img.gz". In the latter case, the file
-    /// is automatically decoded as a Gzip stream.
+    /// files usually have the extension ".img" or ".img.gz". Compressed files are
+    /// automatically decoded, as in `from_file`.
     pub fn from_file_with_extensions<P>(
         path: P,
         header: &NiftiHeader,
@@ -140,21 +133,8 @@ impl InMemNiftiVolume {
     where
         P: AsRef<Path>,
     {
-        let gz = path.as_ref()
-            .extension()
-            .map(|a| a.to_string_lossy() == "gz")
-            .unwrap_or(false);
         let stream = BufReader::new(File::open(path)?);
-
-        if gz {
-            InMemNiftiVolume::from_stream_with_extensions(
-                GzDecoder::new(stream),
-                &header,
-                extender,
-            )
-        } else {
-            InMemNiftiVolume::from_stream_with_extensions(stream, &header, extender)
-        }
+        InMemNiftiVolume::from_stream_with_extensions(decompress(stream)?, &header, extender)
     }
 
     /// Retrieve the raw data, consuming the volume.
@@ -190,6 +170,192 @@ impl InMemNiftiVolume {
         &mut self.raw_data
     }
 
+    /// Get the byte order of the raw data.
+    pub fn endianness(&self) -> Endianness {
+        self.endianness
+    }
+
+    /// Create a new volume of the given data type by applying a function to
+    /// the value of each voxel, after scaling. The new volume is not scaled
+    /// (`scl_slope` is zero), and keeps the byte order of this volume.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::UnsupportedDataType` if either data type cannot be
+    ///   converted from or to `f64`.
+    pub fn map_values<F>(&self, datatype: NiftiType, mut f: F) -> Result<Self>
+    where
+        F: FnMut(f64) -> f64,
+    {
+        let voxel_size = self.datatype.size_of();
+        let mut raw_data = Vec::with_capacity(self.raw_data.len() / voxel_size * datatype.size_of());
+        for voxel in self.raw_data.chunks(voxel_size) {
+            let value: f64 = self.datatype.read_primitive_value(
+                voxel,
+                self.endianness,
+                self.scl_slope,
+                self.scl_inter,
+            )?;
+            encode_value(f(value), datatype, self.endianness, 0., 0., &mut raw_data)?;
+        }
+
+        Ok(InMemNiftiVolume {
+            dim: self.dim,
+            datatype,
+            scl_slope: 0.,
+            scl_inter: 0.,
+            raw_data,
+            endianness: self.endianness,
+        })
+    }
+
+    /// Obtain the bounding box of all voxels with a non-zero stored value,
+    /// as one range of indices per axis. Returns `None` if all voxels are
+    /// zero. Since the raw data is inspected, this works for all data types,
+    /// but the scaling parameters (`scl_slope` and `scl_inter`) are ignored.
+    pub fn bounding_box(&self) -> Option<Vec<Range<u16>>> {
+        let dim = self.dim();
+        let voxel_size = self.datatype.size_of();
+        let mut bbox: Option<Vec<Range<u16>>> = None;
+        let mut coords = vec![0u16; dim.len()];
+        for voxel in self.raw_data.chunks(voxel_size) {
+            if voxel.iter().any(|b| *b != 0) {
+                let bbox = bbox.get_or_insert_with(|| coords.iter().map(|c| *c..*c + 1).collect());
+                for (r, c) in bbox.iter_mut().zip(&coords) {
+                    r.start = r.start.min(*c);
+                    r.end = r.end.max(*c + 1);
+                }
+            }
+            // advance coordinates in column major order
+            for (c, d) in coords.iter_mut().zip(dim) {
+                *c += 1;
+                if *c < *d {
+                    break;
+                }
+                *c = 0;
+            }
+        }
+        bbox
+    }
+
+    /// Obtain a cropped copy of this volume, keeping only the voxels within
+    /// the given range of indices of each axis.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::IncorrectVolumeDimensionality` if the number of ranges
+    ///   does not match the volume's dimensionality.
+    /// - `NiftiError::OutOfBounds` if a range is empty or exceeds the
+    ///   volume's boundaries.
+    pub fn crop(&self, ranges: &[Range<u16>]) -> Result<Self> {
+        let dim = self.dim();
+        if ranges.len() != dim.len() {
+            return Err(NiftiError::IncorrectVolumeDimensionality(
+                dim.len() as u16,
+                ranges.len() as u16,
+            ));
+        }
+        if ranges.iter().zip(dim).any(|(r, d)| r.start >= r.end || r.end > *d) {
+            return Err(NiftiError::OutOfBounds(
+                ranges.iter().map(|r| r.end).collect(),
+            ));
+        }
+
+        let origin: Vec<i64> = ranges.iter().map(|r| i64::from(r.start)).collect();
+        let new_dim: Vec<u16> = ranges.iter().map(|r| r.end - r.start).collect();
+        Ok(self.reframe(&origin, &new_dim))
+    }
+
+    /// Obtain a padded copy of this volume, with the given number of
+    /// zero-valued voxels added before and after the existing ones in each
+    /// axis.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::IncorrectVolumeDimensionality` if the number of
+    ///   padding amounts does not match the volume's dimensionality.
+    /// - `NiftiError::OutOfBounds` if the padded volume would be too large.
+    pub fn pad(&self, before: &[u16], after: &[u16]) -> Result<Self> {
+        let dim = self.dim();
+        if before.len() != dim.len() || after.len() != dim.len() {
+            return Err(NiftiError::IncorrectVolumeDimensionality(
+                dim.len() as u16,
+                before.len().max(after.len()) as u16,
+            ));
+        }
+
+        let new_dim: Vec<u32> = dim
+            .iter()
+            .zip(before.iter().zip(after))
+            .map(|(d, (b, a))| u32::from(*d) + u32::from(*b) + u32::from(*a))
+            .collect();
+        if new_dim.iter().any(|d| *d > u32::from(u16::MAX)) {
+            return Err(NiftiError::OutOfBounds(
+                new_dim.iter().map(|d| (*d).min(0xFFFF) as u16).collect(),
+            ));
+        }
+
+        let origin: Vec<i64> = before.iter().map(|b| -i64::from(*b)).collect();
+        let new_dim: Vec<u16> = new_dim.into_iter().map(|d| d as u16).collect();
+        Ok(self.reframe(&origin, &new_dim))
+    }
+
+    /// Create a volume with the given dimensions, in which the voxel at
+    /// index `i` takes the value of this volume's voxel at `i + origin`, or
+    /// zero if that voxel does not exist.
+    fn reframe(&self, origin: &[i64], new_dim: &[u16]) -> Self {
+        let old_dim = self.dim();
+        let voxel_size = self.datatype.size_of();
+        let len: usize = new_dim.iter().map(|d| *d as usize).product();
+        let mut raw_data = vec![0u8; len * voxel_size];
+
+        // copy one row (along the first axis) at a time
+        let row_len = new_dim[0] as usize;
+        let x_start = origin[0].max(0);
+        let x_end = (origin[0] + row_len as i64).min(i64::from(old_dim[0]));
+        let mut coords = vec![0u16; new_dim.len()];
+        for row in raw_data.chunks_mut(row_len * voxel_size) {
+            let mut src_index = 0i64;
+            let mut stride = 1i64;
+            let mut inside = x_start < x_end;
+            for axis in 1..new_dim.len() {
+                stride *= i64::from(old_dim[axis - 1]);
+                let c = i64::from(coords[axis]) + origin[axis];
+                if c < 0 || c >= i64::from(old_dim[axis]) {
+                    inside = false;
+                    break;
+                }
+                src_index += c * stride;
+            }
+
+            if inside {
+                let src = ((src_index + x_start) as usize) * voxel_size;
+                let dst = ((x_start - origin[0]) as usize) * voxel_size;
+                let n = (x_end - x_start) as usize * voxel_size;
+                row[dst..dst + n].copy_from_slice(&self.raw_data[src..src + n]);
+            }
+
+            for (c, d) in coords.iter_mut().zip(new_dim).skip(1) {
+                *c += 1;
+                if *c < *d {
+                    break;
+                }
+                *c = 0;
+            }
+        }
+
+        let mut dim = self.dim;
+        dim[1..=new_dim.len()].copy_from_slice(new_dim);
+        InMemNiftiVolume {
+            dim,
+            datatype: self.datatype,
+            scl_slope: self.scl_slope,
+            scl_inter: self.scl_inter,
+            raw_data,
+            endianness: self.endianness,
+        }
+    }
+
     fn get_prim<T>(&self, coords: &[u16]) -> Result<T>
     where
         T: DataElement,
@@ -444,6 +610,36 @@ mod tests {
         assert_eq!(v, 39.);
     }
 
+    #[test]
+    fn test_crop_and_pad() {
+        let data: Vec<u8> = (0..24).collect();
+        let vol = InMemNiftiVolume {
+            dim: [3, 4, 3, 2, 0, 0, 0, 0],
+            datatype: NiftiType::Uint8,
+            scl_slope: 0.,
+            scl_inter: 0.,
+            raw_data: data,
+            endianness: Endianness::LE,
+        };
+
+        let cropped = vol.crop(&[1..3, 1..3, 1..2]).unwrap();
+        assert_eq!(cropped.dim(), &[2, 2, 1]);
+        assert_eq!(cropped.raw_data(), &[17, 18, 21, 22]);
+
+        let padded = cropped.pad(&[1, 0, 0], &[0, 1, 1]).unwrap();
+        assert_eq!(padded.dim(), &[3, 3, 2]);
+        assert_eq!(
+            padded.raw_data(),
+            &[0, 17, 18, 0, 21, 22, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(padded.bounding_box(), Some(vec![1..3, 0..2, 0..1]));
+        assert_eq!(padded.crop(&[1..3, 0..2, 0..1]).unwrap(), cropped);
+
+        assert!(vol.crop(&[0..5, 0..3, 0..2]).is_err());
+        assert!(vol.crop(&[0..4, 0..3]).is_err());
+        assert!(vol.crop(&[2..2, 0..3, 0..2]).is_err());
+    }
+
     #[test]
     fn test_false_4d() {
         let (w, h, d) = (5, 5, 5);
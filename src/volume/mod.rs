@@ -6,7 +6,10 @@
 //! to this crate.
 
 pub mod inmem;
+pub mod component;
 pub mod element;
+pub mod resample;
+pub mod warp;
 pub use self::inmem::*;
 
 mod util;
@@ -0,0 +1,205 @@
+//! Module for resampling volumes into a different voxel grid.
+//!
+//! Resampling works over the three spatial axes of a volume: any additional
+//! axes (such as time) are carried over to the output as is. The output is
+//! always an in-memory volume with the data type, scaling and byte order
+//! declared by the target header.
+
+use affine::{self, Affine};
+use error::{NiftiError, Result};
+use header::NiftiHeader;
+use volume::util::encode_value;
+use volume::{InMemNiftiVolume, NiftiVolume};
+
+/// Method of interpolation used when sampling a volume at non-integer voxel
+/// coordinates. Positions outside of the volume are sampled as zero.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Interpolation {
+    /// Take the value of the nearest voxel.
+    Nearest,
+    /// Trilinear interpolation between the eight surrounding voxels.
+    Linear,
+}
+
+impl Interpolation {
+    /// Sample a volume at the given spatial voxel coordinates. `rest`
+    /// contains the indices for the volume's axes beyond the third one,
+    /// and must be empty if the volume has at most three dimensions.
+    ///
+    /// # Errors
+    ///
+    /// - `NiftiError::IncorrectVolumeDimensionality` if `rest` does not
+    ///   match the volume's dimensionality.
+    pub fn sample<V>(self, volume: &V, point: [f64; 3], rest: &[u16]) -> Result<f64>
+    where
+        V: NiftiVolume,
+    {
+        let dim = volume.dim();
+        let nspatial = dim.len().min(3);
+        if dim.len() != nspatial + rest.len() {
+            return Err(NiftiError::IncorrectVolumeDimensionality(
+                dim.len() as u16,
+                (3 + rest.len()) as u16,
+            ));
+        }
+        let size = |axis: usize| if axis < nspatial { dim[axis] } else { 1 };
+
+        let mut coords = vec![0u16; dim.len()];
+        coords[nspatial..].copy_from_slice(rest);
+
+        match self {
+            Interpolation::Nearest => {
+                for (axis, p) in point.iter().enumerate() {
+                    let i = p.round();
+                    if i < 0. || i >= f64::from(size(axis)) {
+                        return Ok(0.);
+                    }
+                    if axis < nspatial {
+                        coords[axis] = i as u16;
+                    }
+                }
+                volume.get_f64(&coords)
+            }
+            Interpolation::Linear => {
+                const EPS: f64 = 1e-6;
+                let mut base = [0u16; 3];
+                let mut frac = [0f64; 3];
+                for (axis, p) in point.iter().enumerate() {
+                    let max = f64::from(size(axis)) - 1.;
+                    if *p < -EPS || *p > max + EPS {
+                        return Ok(0.);
+                    }
+                    let p = p.max(0.).min(max);
+                    let i = p.floor();
+                    base[axis] = i as u16;
+                    frac[axis] = p - i;
+                }
+
+                let mut value = 0.;
+                for corner in 0..8 {
+                    let mut weight = 1.;
+                    for axis in 0..3 {
+                        let upper = corner & (1 << axis) != 0;
+                        let w = if upper { frac[axis] } else { 1. - frac[axis] };
+                        weight *= w;
+                        if axis < nspatial {
+                            coords[axis] = base[axis] + upper as u16;
+                        }
+                    }
+                    if weight > 0. {
+                        value += weight * volume.get_f64(&coords)?;
+                    }
+                }
+                Ok(value)
+            }
+        }
+    }
+}
+
+/// Resample a volume onto the voxel grid of a target header. The affine
+/// transformations of the source and target headers (see
+/// `NiftiHeader::affine`) are used to map each target voxel to the
+/// respective position in the source volume.
+///
+/// # Errors
+///
+/// - `NiftiError::InvalidAffine` if the source affine cannot be inverted.
+/// - See `resample_with`.
+pub fn resample<V>(
+    volume: &V,
+    source: &NiftiHeader,
+    target: &NiftiHeader,
+    interpolation: Interpolation,
+) -> Result<InMemNiftiVolume>
+where
+    V: NiftiVolume,
+{
+    let to_source = affine::invert(&source.affine()).ok_or(NiftiError::InvalidAffine)?;
+    let mapping: Affine = affine::multiply(&to_source, &target.affine());
+    resample_with(volume, target, interpolation, |ijk| {
+        let p = [f64::from(ijk[0]), f64::from(ijk[1]), f64::from(ijk[2])];
+        Ok(Some(affine::transform_point(&mapping, p)))
+    })
+}
+
+/// Resample a volume onto the voxel grid of a target header, using a custom
+/// mapping from target voxel indices to source voxel coordinates. Target
+/// voxels mapped to `None` are filled with zero.
+///
+/// # Errors
+///
+/// - `NiftiError::IncorrectVolumeDimensionality` if the target header does
+///   not declare as many dimensions as the source volume has.
+/// - `NiftiError::IncompatibleLength` if the target's non-spatial
+///   dimensions do not match the source's.
+pub fn resample_with<V, F>(
+    volume: &V,
+    target: &NiftiHeader,
+    interpolation: Interpolation,
+    mut mapping: F,
+) -> Result<InMemNiftiVolume>
+where
+    V: NiftiVolume,
+    F: FnMut([u16; 3]) -> Result<Option<[f64; 3]>>,
+{
+    let ndim = target.dim[0] as usize;
+    let sdim = volume.dim();
+    if ndim > 7 || sdim.len() != ndim {
+        return Err(NiftiError::IncorrectVolumeDimensionality(
+            sdim.len() as u16,
+            ndim as u16,
+        ));
+    }
+    let tdim = &target.dim[1..=ndim];
+    if tdim.iter().skip(3).ne(sdim.iter().skip(3)) {
+        return Err(NiftiError::IncompatibleLength);
+    }
+
+    let size = |axis: usize| if axis < ndim { tdim[axis] } else { 1 };
+    let (nx, ny, nz) = (size(0), size(1), size(2));
+    let rest_dim = if ndim > 3 { &tdim[3..] } else { &[][..] };
+    let nrest: usize = rest_dim.iter().map(|d| *d as usize).product();
+
+    let datatype = target.data_type()?;
+    let mut raw_data = Vec::with_capacity(
+        nrest * nx as usize * ny as usize * nz as usize * datatype.size_of(),
+    );
+
+    // source positions are the same for every volume over the extra axes
+    let mut positions = Vec::with_capacity(nx as usize * ny as usize * nz as usize);
+    for k in 0..nz {
+        for j in 0..ny {
+            for i in 0..nx {
+                positions.push(mapping([i, j, k])?);
+            }
+        }
+    }
+
+    let mut rest = vec![0u16; rest_dim.len()];
+    for _ in 0..nrest {
+        for p in &positions {
+            let value = match *p {
+                Some(p) => interpolation.sample(volume, p, &rest)?,
+                None => 0.,
+            };
+            encode_value(
+                value,
+                datatype,
+                target.endianness,
+                target.scl_slope,
+                target.scl_inter,
+                &mut raw_data,
+            )?;
+        }
+        // advance the extra axes in column major order
+        for (r, d) in rest.iter_mut().zip(rest_dim) {
+            *r += 1;
+            if *r < *d {
+                break;
+            }
+            *r = 0;
+        }
+    }
+
+    InMemNiftiVolume::from_raw_data(target, raw_data)
+}
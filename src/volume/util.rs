@@ -1,6 +1,8 @@
 //! Miscellaneous volume-related functions
 use error::{NiftiError, Result};
 use num_traits::Zero;
+use typedef::NiftiType;
+use util::Endianness;
 
 pub fn hot_vector<T>(dim: usize, axis: usize, value: T) -> Vec<T>
 where
@@ -33,6 +35,38 @@ pub fn coords_to_index(coords: &[u16], dim: &[u16]) -> Result<usize> {
     Ok(index)
 }
 
+/// Encode a voxel value into raw bytes of the given data type, reverting
+/// the linear transformation described by `slope` and `inter`. Values are
+/// rounded and saturated when the data type is an integer type.
+pub fn encode_value(
+    value: f64,
+    datatype: NiftiType,
+    endianness: Endianness,
+    slope: f32,
+    inter: f32,
+    out: &mut Vec<u8>,
+) -> Result<()> {
+    let raw = if slope == 0. {
+        value
+    } else {
+        (value - f64::from(inter)) / f64::from(slope)
+    };
+    match datatype {
+        NiftiType::Uint8 => out.push(raw.round() as u8),
+        NiftiType::Int8 => out.push(raw.round() as i8 as u8),
+        NiftiType::Uint16 => endianness.write_u16(out, raw.round() as u16)?,
+        NiftiType::Int16 => endianness.write_i16(out, raw.round() as i16)?,
+        NiftiType::Uint32 => endianness.write_u32(out, raw.round() as u32)?,
+        NiftiType::Int32 => endianness.write_i32(out, raw.round() as i32)?,
+        NiftiType::Uint64 => endianness.write_u64(out, raw.round() as u64)?,
+        NiftiType::Int64 => endianness.write_i64(out, raw.round() as i64)?,
+        NiftiType::Float32 => endianness.write_f32(out, raw as f32)?,
+        NiftiType::Float64 => endianness.write_f64(out, raw)?,
+        _ => return Err(NiftiError::UnsupportedDataType(datatype)),
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::coords_to_index;
@@ -0,0 +1,179 @@
+//! Module for warping volumes with displacement fields, such as those
+//! produced by non-linear registration tools (FSL FNIRT, ANTs, ITK).
+//!
+//! A displacement field holds a 3-component vector at each voxel, either
+//! along the 5th dimension (`dim = [5, x, y, z, 1, 3]`, the standard layout
+//! with `Intent::Dispvect` or `Intent::Vector`, written by ANTs and ITK), or
+//! along the 4th dimension (`dim = [4, x, y, z, 3]`, written by FSL). The
+//! warped volume lies on the voxel grid of the field, and each of its voxels
+//! is sampled from the moving volume at the position given by the field.
+//! How vectors are to be read is described by a `WarpConvention`.
+
+use affine::{self, Affine};
+use error::{NiftiError, Result};
+use header::NiftiHeader;
+use volume::resample::{resample_with, Interpolation};
+use volume::{InMemNiftiVolume, NiftiVolume};
+
+/// The coordinate space of the vectors in a displacement field.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum WarpSpace {
+    /// World coordinates in millimeters, with axes oriented as in the
+    /// header affines (RAS+), as used by SPM and nibabel.
+    Ras,
+    /// World coordinates in millimeters with the x and y axes negated
+    /// (LPS+), as used by ITK and ANTs.
+    Lps,
+    /// FSL's scaled voxel coordinates in millimeters: voxel indices
+    /// multiplied by the voxel size, with the first axis flipped if the
+    /// image affine has a positive determinant. Each image uses its own
+    /// coordinates, regardless of the translation in its affine.
+    Fsl,
+    /// Voxel indices, of the field's grid for relative displacements, or of
+    /// the moving volume's grid for absolute positions.
+    Voxel,
+}
+
+/// Whether each vector in a displacement field is relative to the position
+/// of its voxel, or an absolute position.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum WarpMode {
+    /// The vector is a displacement from the voxel's position.
+    Relative,
+    /// The vector is the position to sample from.
+    Absolute,
+}
+
+/// The convention followed by a displacement field.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct WarpConvention {
+    /// The coordinate space of the vectors
+    pub space: WarpSpace,
+    /// Whether the vectors are relative or absolute
+    pub mode: WarpMode,
+}
+
+impl WarpConvention {
+    /// The convention of ITK and ANTs displacement fields: relative
+    /// displacements in LPS+ world coordinates.
+    pub fn itk() -> Self {
+        WarpConvention {
+            space: WarpSpace::Lps,
+            mode: WarpMode::Relative,
+        }
+    }
+
+    /// The convention of FSL warp fields, such as the output of
+    /// `fnirtfileutils` or `convertwarp` (`--relout` or `--absout`).
+    pub fn fsl(mode: WarpMode) -> Self {
+        WarpConvention {
+            space: WarpSpace::Fsl,
+            mode,
+        }
+    }
+}
+
+/// Warp a volume with a displacement field. The output volume lies on the
+/// voxel grid of the field (the spatial dimensions of `field_header` and
+/// its affine), while keeping the data type, scaling and extra axes (such
+/// as time) of the moving volume.
+///
+/// # Errors
+///
+/// - `NiftiError::InvalidDisplacementField` if the field does not hold 3
+///   components per voxel.
+/// - `NiftiError::IncorrectVolumeDimensionality` if the moving volume has
+///   less than 3 dimensions.
+/// - `NiftiError::InvalidAffine` if an affine cannot be inverted.
+pub fn warp<V, W>(
+    volume: &V,
+    header: &NiftiHeader,
+    field: &W,
+    field_header: &NiftiHeader,
+    convention: WarpConvention,
+    interpolation: Interpolation,
+) -> Result<InMemNiftiVolume>
+where
+    V: NiftiVolume,
+    W: NiftiVolume,
+{
+    let fdim = field.dim();
+    let component_axis = match fdim.len() {
+        4 if fdim[3] == 3 => 3,
+        5 if fdim[3] == 1 && fdim[4] == 3 => 4,
+        _ => return Err(NiftiError::InvalidDisplacementField(fdim.to_vec())),
+    };
+    if header.dim[0] < 3 {
+        return Err(NiftiError::IncorrectVolumeDimensionality(3, header.dim[0]));
+    }
+
+    let mut target = header.clone();
+    target.dim[1..4].copy_from_slice(&fdim[..3]);
+    target.set_affine(&field_header.affine());
+
+    let field_affine = field_header.affine();
+    let moving_affine = header.affine();
+    let to_moving = affine::invert(&moving_affine).ok_or(NiftiError::InvalidAffine)?;
+    let moving_fsl = fsl_to_voxel(header)?;
+    let field_from_fsl =
+        affine::invert(&fsl_to_voxel(field_header)?).ok_or(NiftiError::InvalidAffine)?;
+
+    let mut coords = vec![0u16; fdim.len()];
+    resample_with(volume, &target, interpolation, |ijk| {
+        coords[..3].copy_from_slice(&ijk);
+        let mut d = [0.; 3];
+        for (c, v) in d.iter_mut().enumerate() {
+            coords[component_axis] = c as u16;
+            *v = field.get_f64(&coords)?;
+        }
+        if d.iter().any(|v| !v.is_finite()) {
+            return Ok(None);
+        }
+        if convention.space == WarpSpace::Lps {
+            d[0] = -d[0];
+            d[1] = -d[1];
+        }
+        let p = [f64::from(ijk[0]), f64::from(ijk[1]), f64::from(ijk[2])];
+
+        let source = match (convention.space, convention.mode) {
+            (WarpSpace::Ras, WarpMode::Absolute) | (WarpSpace::Lps, WarpMode::Absolute) => {
+                affine::transform_point(&to_moving, d)
+            }
+            (WarpSpace::Ras, WarpMode::Relative) | (WarpSpace::Lps, WarpMode::Relative) => {
+                let w = affine::transform_point(&field_affine, p);
+                affine::transform_point(&to_moving, [w[0] + d[0], w[1] + d[1], w[2] + d[2]])
+            }
+            (WarpSpace::Fsl, WarpMode::Absolute) => affine::transform_point(&moving_fsl, d),
+            (WarpSpace::Fsl, WarpMode::Relative) => {
+                let f = affine::transform_point(&field_from_fsl, p);
+                affine::transform_point(&moving_fsl, [f[0] + d[0], f[1] + d[1], f[2] + d[2]])
+            }
+            (WarpSpace::Voxel, WarpMode::Absolute) => d,
+            (WarpSpace::Voxel, WarpMode::Relative) => {
+                let moved = [p[0] + d[0], p[1] + d[1], p[2] + d[2]];
+                affine::transform_point(&to_moving, affine::transform_point(&field_affine, moved))
+            }
+        };
+        Ok(Some(source))
+    })
+}
+
+/// Build the transformation from FSL's scaled voxel coordinates of an image
+/// to its voxel indices.
+fn fsl_to_voxel(header: &NiftiHeader) -> Result<Affine> {
+    let mut m = affine::identity();
+    for (axis, row) in m.iter_mut().enumerate().take(3) {
+        let size = f64::from(header.pixdim[axis + 1]).abs();
+        if size == 0. || !size.is_finite() {
+            return Err(NiftiError::InvalidAffine);
+        }
+        row[axis] = 1. / size;
+    }
+    // FSL uses radiological voxel order, flipping the first axis of images
+    // stored in neurological order
+    if affine::determinant(&header.affine()) > 0. {
+        m[0][0] = -m[0][0];
+        m[0][3] = f64::from(header.dim[1]) - 1.;
+    }
+    Ok(m)
+}
@@ -1,28 +1,58 @@
 //! Utility functions to write nifti images.
 
 use std::fs::File;
-use std::io::BufWriter;
+use std::io::{self, BufWriter, Write};
 use std::ops::{Div, Sub};
 use std::path::Path;
+use std::thread;
 
 use byteorder::{LittleEndian, WriteBytesExt};
 use flate2::write::GzEncoder;
-use flate2::Compression;
+use flate2::{Compress, Compression, Crc, FlushCompress, Status};
 use ndarray::{ArrayBase, ArrayView, Axis, Data, Dimension, RemoveAxis, ScalarOperand};
 use num_traits::FromPrimitive;
 use safe_transmute::{guarded_transmute_to_bytes_pod_many, PodTransmutable};
 
 use {
-    header::MAGIC_CODE_NIP1, util::is_gz_file, volume::element::DataElement, NiftiHeader,
-    NiftiType, Result,
+    header::MAGIC_CODE_NIP1, util::CompressionFormat, volume::element::DataElement,
+    ExtensionSequence, NiftiError, NiftiHeader, NiftiType, Result,
 };
 
 // TODO make this configurable. The Nifti standard does not specify a specific field for endianness,
 // but it is encoded in `dim[0]`. "if dim[0] is outside range 1..7, then swap".
 type B = LittleEndian;
 
+/// Size of the blocks which are compressed independently when writing Gzip output with
+/// several threads, as in pigz.
+const PARALLEL_GZ_BLOCK_SIZE: usize = 128 * 1024;
+
+/// Options for writing nifti files, see `write_nifti_with_options`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WriterOptions {
+    /// The Gzip compression level, from 0 (stored blocks, no compression) to 9 (best
+    /// compression). Higher levels are treated as 9. Defaults to 1, the fastest level.
+    pub gzip_level: u32,
+    /// The number of threads used for Gzip compression. With more than one thread, the data is
+    /// split into blocks which are compressed in parallel, then written as a single Gzip stream.
+    /// Defaults to 1.
+    pub gzip_threads: usize,
+}
+
+impl Default for WriterOptions {
+    fn default() -> Self {
+        WriterOptions {
+            gzip_level: 1,
+            gzip_threads: 1,
+        }
+    }
+}
+
 /// Write a nifti file (.nii or .nii.gz) in Little Endian.
 ///
+/// The file is compressed according to its extension: ".gz" for Gzip, and with the respective
+/// Cargo features enabled, ".zst" for Zstandard (`zstd`), ".bz2" for Bzip2 (`bzip2`) and ".xz"
+/// for XZ (`xz2`).
+///
 /// If a `reference` is given, it will be used to fill most of the header's fields. The voxels
 /// intensity will be subtracted by `scl_slope` and divided by `scl_inter`. If `reference` is not
 /// given, a default `NiftiHeader` will be built and written.
@@ -46,26 +76,68 @@ where
     A: Sub<Output = A>,
     D: Dimension + RemoveAxis,
 {
-    let header = build_header(data, reference, A::DATA_TYPE);
+    write_nifti_with_options(path, data, reference, &WriterOptions::default())
+}
+
+/// Write a nifti file as in `write_nifti`, with the given Gzip compression options.
+pub fn write_nifti_with_options<P, A, S, D>(
+    path: P,
+    data: &ArrayBase<S, D>,
+    reference: Option<&NiftiHeader>,
+    options: &WriterOptions,
+) -> Result<()>
+where
+    P: AsRef<Path>,
+    S: Data<Elem = A>,
+    A: Copy,
+    A: DataElement,
+    A: Div<Output = A>,
+    A: FromPrimitive,
+    A: PodTransmutable,
+    A: ScalarOperand,
+    A: Sub<Output = A>,
+    D: Dimension + RemoveAxis,
+{
+    let extensions = ExtensionSequence::new();
+    write_nifti_with_extensions(path, data, reference, &extensions, options)
+}
+
+/// Write a nifti file as in `write_nifti_with_options`, followed by the given extensions. The
+/// extender flag and `vox_offset` are set according to the extensions, which are written as they
+/// are (extensions added with `ExtensionSequence::push` are already padded to a multiple of 16
+/// bytes).
+pub fn write_nifti_with_extensions<P, A, S, D>(
+    path: P,
+    data: &ArrayBase<S, D>,
+    reference: Option<&NiftiHeader>,
+    extensions: &ExtensionSequence,
+    options: &WriterOptions,
+) -> Result<()>
+where
+    P: AsRef<Path>,
+    S: Data<Elem = A>,
+    A: Copy,
+    A: DataElement,
+    A: Div<Output = A>,
+    A: FromPrimitive,
+    A: PodTransmutable,
+    A: ScalarOperand,
+    A: Sub<Output = A>,
+    D: Dimension + RemoveAxis,
+{
+    let header = build_header(data, reference, extensions, A::DATA_TYPE);
 
     // Need the transpose for fortran ordering used in nifti file format.
     let data = data.t();
 
-    let f = File::create(&path)?;
-    let mut writer = BufWriter::new(f);
-    if is_gz_file(&path) {
-        let mut e = GzEncoder::new(writer, Compression::fast());
-        write_header(&mut e, &header)?;
-        write_data(&mut e, &header, data)?;
-        let _ = e.finish()?; // Must use result
-    } else {
-        write_header(&mut writer, &header)?;
-        write_data(&mut writer, &header, data)?;
-    }
-    Ok(())
+    write_file(path, options, |mut writer| {
+        write_header(&mut writer, &header, extensions)?;
+        write_data(&mut writer, &header, data)
+    })
 }
 
-/// Write a RGB nifti file (.nii or .nii.gz) in Little Endian.
+/// Write a RGB nifti file (.nii or .nii.gz) in Little Endian. The file is compressed as in
+/// `write_nifti`.
 ///
 /// If a `reference` is given, it will be used to fill most of the header's fields, except those
 /// necessary to be recognized as a RGB image. `scl_slope` will be set to 1.0 and `scl_inter` to
@@ -75,36 +147,137 @@ pub fn write_rgb_nifti<P, S, D>(
     data: &ArrayBase<S, D>,
     reference: Option<&NiftiHeader>,
 ) -> Result<()>
+where
+    P: AsRef<Path>,
+    S: Data<Elem = [u8; 3]>,
+    D: Dimension + RemoveAxis,
+{
+    write_rgb_nifti_with_options(path, data, reference, &WriterOptions::default())
+}
+
+/// Write a RGB nifti file as in `write_rgb_nifti`, with the given Gzip compression options.
+pub fn write_rgb_nifti_with_options<P, S, D>(
+    path: P,
+    data: &ArrayBase<S, D>,
+    reference: Option<&NiftiHeader>,
+    options: &WriterOptions,
+) -> Result<()>
+where
+    P: AsRef<Path>,
+    S: Data<Elem = [u8; 3]>,
+    D: Dimension + RemoveAxis,
+{
+    let extensions = ExtensionSequence::new();
+    write_rgb_nifti_with_extensions(path, data, reference, &extensions, options)
+}
+
+/// Write a RGB nifti file as in `write_rgb_nifti_with_options`, followed by the given
+/// extensions, as in `write_nifti_with_extensions`.
+pub fn write_rgb_nifti_with_extensions<P, S, D>(
+    path: P,
+    data: &ArrayBase<S, D>,
+    reference: Option<&NiftiHeader>,
+    extensions: &ExtensionSequence,
+    options: &WriterOptions,
+) -> Result<()>
 where
     P: AsRef<Path>,
     S: Data<Elem = [u8; 3]>,
     D: Dimension + RemoveAxis,
 {
     // The `scl_slope` and `scl_inter` fields are ignored on the Rgb24 type.
-    let mut header = build_header(data, reference, NiftiType::Rgb24);
+    let mut header = build_header(data, reference, extensions, NiftiType::Rgb24);
     header.scl_slope = 1.0;
     header.scl_inter = 0.0;
 
     // Need the transpose for fortran used in nifti file format.
     let data = data.t();
 
-    let f = File::create(&path)?;
-    let mut writer = BufWriter::new(f);
-    if is_gz_file(&path) {
-        let mut e = GzEncoder::new(writer, Compression::fast());
-        write_header(&mut e, &header)?;
-        write_slices(&mut e, data)?;
-        let _ = e.finish()?; // Must use result
-    } else {
-        write_header(&mut writer, &header)?;
-        write_slices(&mut writer, data)?;
+    write_file(path, options, |mut writer| {
+        write_header(&mut writer, &header, extensions)?;
+        write_slices(&mut writer, data)
+    })
+}
+
+/// Create the file at `path`, compressed according to its extension, and
+/// let `write` fill it.
+fn write_file<P, F>(path: P, options: &WriterOptions, write: F) -> Result<()>
+where
+    P: AsRef<Path>,
+    F: FnOnce(&mut dyn Write) -> Result<()>,
+{
+    let format = compression_from_path(&path);
+    if !is_supported(format) {
+        return Err(NiftiError::UnsupportedCompression(format.feature()));
+    }
+
+    let mut writer = BufWriter::new(File::create(&path)?);
+    match format {
+        CompressionFormat::None => {
+            write(&mut writer)?;
+            writer.flush()?;
+        }
+        CompressionFormat::Gzip if options.gzip_threads > 1 => {
+            let level = Compression::new(options.gzip_level.min(9));
+            let mut e = ParallelGzEncoder::new(writer, level, options.gzip_threads)?;
+            write(&mut e)?;
+            let _ = e.finish()?;
+        }
+        CompressionFormat::Gzip => {
+            let level = Compression::new(options.gzip_level.min(9));
+            let mut e = GzEncoder::new(writer, level);
+            write(&mut e)?;
+            let _ = e.finish()?; // Must use result
+        }
+        #[cfg(feature = "zstd")]
+        CompressionFormat::Zstd => {
+            let mut e = ::zstd::stream::write::Encoder::new(writer, 0)?;
+            write(&mut e)?;
+            let _ = e.finish()?;
+        }
+        #[cfg(feature = "bzip2")]
+        CompressionFormat::Bzip2 => {
+            let mut e = ::bzip2::write::BzEncoder::new(writer, ::bzip2::Compression::Default);
+            write(&mut e)?;
+            let _ = e.finish()?;
+        }
+        #[cfg(feature = "xz2")]
+        CompressionFormat::Xz => {
+            let mut e = ::xz2::write::XzEncoder::new(writer, 6);
+            write(&mut e)?;
+            let _ = e.finish()?;
+        }
+        #[allow(unreachable_patterns)]
+        _ => unreachable!("unsupported compression format"),
     }
     Ok(())
 }
 
+/// Obtain the compression format of a file from its extension.
+fn compression_from_path<P: AsRef<Path>>(path: P) -> CompressionFormat {
+    match path.as_ref().extension().and_then(|e| e.to_str()) {
+        Some("gz") => CompressionFormat::Gzip,
+        Some("zst") => CompressionFormat::Zstd,
+        Some("bz2") => CompressionFormat::Bzip2,
+        Some("xz") => CompressionFormat::Xz,
+        _ => CompressionFormat::None,
+    }
+}
+
+/// Whether writing with the given compression format is enabled.
+fn is_supported(format: CompressionFormat) -> bool {
+    match format {
+        CompressionFormat::None | CompressionFormat::Gzip => true,
+        CompressionFormat::Zstd => cfg!(feature = "zstd"),
+        CompressionFormat::Bzip2 => cfg!(feature = "bzip2"),
+        CompressionFormat::Xz => cfg!(feature = "xz2"),
+    }
+}
+
 fn build_header<T, D>(
     data: &ArrayBase<T, D>,
     reference: Option<&NiftiHeader>,
+    extensions: &ExtensionSequence,
     datatype: NiftiType,
 ) -> NiftiHeader
 where
@@ -136,14 +309,18 @@ where
         sizeof_hdr: 348,
         datatype: datatype as i16,
         bitpix: (datatype.size_of() * 8) as i16,
-        vox_offset: 352.0,
+        vox_offset: (352 + extensions.iter().map(|e| e.size()).sum::<i32>()) as f32,
         magic: *MAGIC_CODE_NIP1,
         // All other fields are copied from reference header
         ..reference
     }
 }
 
-fn write_header<W>(writer: &mut W, header: &NiftiHeader) -> Result<()>
+fn write_header<W>(
+    writer: &mut W,
+    header: &NiftiHeader,
+    extensions: &ExtensionSequence,
+) -> Result<()>
 where
     W: WriteBytesExt,
 {
@@ -205,9 +382,14 @@ where
     writer.write_all(&header.intent_name)?;
     writer.write_all(&header.magic)?;
 
-    // Empty 4 bytes after the header
-    // TODO Support writing extension data.
-    writer.write_u32::<B>(0)?;
+    // The extender, followed by the extensions
+    let has_extensions = !extensions.is_empty();
+    writer.write_all(&[has_extensions as u8, 0, 0, 0])?;
+    for extension in extensions {
+        writer.write_i32::<B>(extension.size())?;
+        writer.write_i32::<B>(extension.code())?;
+        writer.write_all(extension.data())?;
+    }
 
     Ok(())
 }
@@ -279,3 +461,118 @@ where
     writer.write_all(guarded_transmute_to_bytes_pod_many(slice))?;
     Ok(())
 }
+
+/// A Gzip encoder compressing blocks of data in parallel, in the manner of pigz. Each block is
+/// compressed into raw deflate data ending with a sync flush, so that the concatenation of all
+/// blocks, the last of which ends the deflate stream, forms a single valid Gzip stream. Since
+/// blocks do not share a dictionary, the output is slightly larger than that of `GzEncoder`.
+struct ParallelGzEncoder<W: Write> {
+    writer: W,
+    level: Compression,
+    threads: usize,
+    buffer: Vec<u8>,
+    crc: Crc,
+}
+
+impl<W: Write> ParallelGzEncoder<W> {
+    fn new(mut writer: W, level: Compression, threads: usize) -> io::Result<Self> {
+        // magic, deflate method, no flags, no modification time, no extra flags, unknown OS
+        writer.write_all(&[0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255])?;
+        Ok(ParallelGzEncoder {
+            writer,
+            level,
+            threads,
+            buffer: Vec::with_capacity(threads * PARALLEL_GZ_BLOCK_SIZE),
+            crc: Crc::new(),
+        })
+    }
+
+    /// Compress and write the buffered data, ending the stream if `last` is set.
+    fn compress_buffer(&mut self, last: bool) -> io::Result<()> {
+        let level = self.level;
+        let mut blocks: Vec<&[u8]> = self.buffer.chunks(PARALLEL_GZ_BLOCK_SIZE).collect();
+        if blocks.is_empty() && last {
+            blocks.push(&[]);
+        }
+        let nblocks = blocks.len();
+        let compressed: Vec<_> = thread::scope(|scope| {
+            let handles: Vec<_> = blocks
+                .iter()
+                .enumerate()
+                .map(|(i, &block)| {
+                    let end = last && i + 1 == nblocks;
+                    scope.spawn(move || deflate_block(block, level, end))
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().expect("compression thread panicked"))
+                .collect()
+        });
+        for output in compressed {
+            let (data, crc) = output?;
+            self.writer.write_all(&data)?;
+            self.crc.combine(&crc);
+        }
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// Finish the Gzip stream, returning the underlying writer.
+    fn finish(mut self) -> io::Result<W> {
+        self.compress_buffer(true)?;
+        let crc = self.crc.sum();
+        let len = self.crc.amount();
+        self.writer.write_u32::<LittleEndian>(crc)?;
+        self.writer.write_u32::<LittleEndian>(len)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write> Write for ParallelGzEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let capacity = self.threads * PARALLEL_GZ_BLOCK_SIZE;
+        // keep a full buffer until more data comes, so that `finish` ends the stream with it
+        if self.buffer.len() == capacity && !buf.is_empty() {
+            self.compress_buffer(false)?;
+        }
+        let len = buf.len().min(capacity - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Compress a block of data into raw deflate data, returning it along with the block's CRC.
+fn deflate_block(block: &[u8], level: Compression, last: bool) -> io::Result<(Vec<u8>, Crc)> {
+    let mut crc = Crc::new();
+    crc.update(block);
+
+    let mut compress = Compress::new(level, false);
+    let flush = if last {
+        FlushCompress::Finish
+    } else {
+        FlushCompress::Sync
+    };
+    let mut output = Vec::with_capacity(block.len() + block.len() / 8 + 64);
+    loop {
+        let input = &block[compress.total_in() as usize..];
+        let status = compress
+            .compress_vec(input, &mut output, flush)
+            .map_err(io::Error::other)?;
+        let done = match status {
+            Status::StreamEnd => true,
+            _ if last => false,
+            // the sync flush is complete once the output has room to spare
+            _ => compress.total_in() as usize == block.len() && output.len() < output.capacity(),
+        };
+        if done {
+            return Ok((output, crc));
+        }
+        output.reserve(PARALLEL_GZ_BLOCK_SIZE);
+    }
+}
@@ -0,0 +1,105 @@
+#[cfg(feature = "afni")]
+extern crate nifti;
+#[cfg(feature = "afni")]
+#[macro_use]
+extern crate pretty_assertions;
+
+#[cfg(feature = "afni")]
+mod tests {
+    use nifti::afni::{AfniAttributes, AfniValue};
+    use nifti::{Extension, ExtensionCode, ExtensionSequence};
+
+    const AFNI_XML: &str = "<?xml version='1.0' ?>
+<AFNI_attributes
+  self_idcode=\"XYZ_ABCDEFGHIJKLMNOPQRSTUV\"
+  NIfTI_nums=\"64,64,10,2,1,16\"
+  ni_form=\"ni_group\" >
+<AFNI_atr
+  ni_type=\"String\"
+  ni_dimen=\"1\"
+  atr_name=\"HISTORY_NOTE\" >
+ \"[user@host: Mon Jan  1 00:00:00 2018] 3dDeconvolve -input &quot;epi.nii&quot;\"
+</AFNI_atr>
+<AFNI_atr
+  ni_type=\"String\"
+  ni_dimen=\"1\"
+  atr_name=\"BRICK_LABS\" >
+ \"Coef~Tstat\"
+</AFNI_atr>
+<AFNI_atr
+  ni_type=\"float\"
+  ni_dimen=\"3\"
+  atr_name=\"BRICK_STATAUX\" >
+ 1 3 1 150
+</AFNI_atr>
+<AFNI_atr
+  ni_type=\"int\"
+  ni_dimen=\"2\"
+  atr_name=\"BRICK_TYPES\" >
+ 3 3
+</AFNI_atr>
+</AFNI_attributes>
+\0\0\0";
+
+    #[test]
+    fn afni_attributes() {
+        let attributes = AfniAttributes::from_xml(AFNI_XML).unwrap();
+        assert_eq!(attributes.len(), 4);
+        assert_eq!(
+            attributes.history(),
+            Some("[user@host: Mon Jan  1 00:00:00 2018] 3dDeconvolve -input \"epi.nii\"")
+        );
+        assert_eq!(attributes.brick_labels(), Some(vec!["Coef", "Tstat"]));
+        assert_eq!(
+            attributes.get("BRICK_STATAUX"),
+            Some(&AfniValue::Float(vec![1., 3., 1., 150.]))
+        );
+        assert_eq!(
+            attributes.get("BRICK_TYPES").and_then(AfniValue::as_ints),
+            Some(&[3, 3][..])
+        );
+        assert_eq!(attributes.get("IDCODE_DATE"), None);
+    }
+
+    #[test]
+    fn update_afni_attributes() {
+        let mut extensions = ExtensionSequence::new();
+        extensions.push(Extension::from_data(
+            ExtensionCode::Afni as i32,
+            AFNI_XML.into(),
+        ));
+        extensions.push(Extension::from_data(
+            ExtensionCode::Comment as i32,
+            b"hello".to_vec(),
+        ));
+
+        let mut attributes = AfniAttributes::from_extensions(&extensions)
+            .unwrap()
+            .unwrap();
+        attributes.set_brick_labels(&["Coef <a&b>", "Tstat"]);
+        assert_eq!(
+            attributes.set("BRICK_FLOAT_FACS", AfniValue::Float(vec![0., 0.])),
+            None
+        );
+        assert!(attributes.remove("BRICK_TYPES").is_some());
+        attributes.store(&mut extensions);
+
+        assert_eq!(extensions.len(), 2);
+        assert_eq!(extensions.comments(), vec!["hello"]);
+        let reparsed = AfniAttributes::from_extensions(&extensions)
+            .unwrap()
+            .unwrap();
+        assert_eq!(reparsed, attributes);
+        assert_eq!(reparsed.brick_labels(), Some(vec!["Coef <a&b>", "Tstat"]));
+        assert!(reparsed
+            .to_xml()
+            .contains("self_idcode=\"XYZ_ABCDEFGHIJKLMNOPQRSTUV\""));
+    }
+
+    #[test]
+    fn invalid_afni_attributes() {
+        assert!(AfniAttributes::from_xml("<AFNI_attributes>").is_err());
+        let bad = AFNI_XML.replace("1 3 1 150", "1 x 1 150");
+        assert!(AfniAttributes::from_xml(&bad).is_err());
+    }
+}
@@ -0,0 +1,154 @@
+#[cfg(feature = "bids")]
+extern crate nifti;
+#[cfg(feature = "bids")]
+#[macro_use]
+extern crate pretty_assertions;
+#[cfg(feature = "bids")]
+extern crate tempfile;
+
+#[cfg(feature = "bids")]
+mod tests {
+    use nifti::bids::{Inconsistency, Sidecar};
+    use nifti::{InMemNiftiObject, NiftiHeader, NiftiObject, SliceOrder, Unit};
+    use std::fs;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    fn write(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn sidecar_inheritance() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        write(
+            &root.join("dataset_description.json"),
+            r#"{"Name": "test"}"#,
+        );
+        write(
+            &root.join("task-rest_bold.json"),
+            r#"{"RepetitionTime": 2.0, "TaskName": "rest", "SliceTiming": [0, 1]}"#,
+        );
+        write(
+            &root.join("task-nback_bold.json"),
+            r#"{"TaskName": "nback"}"#,
+        );
+        write(
+            &root.join("sub-01/sub-01_bold.json"),
+            r#"{"EchoTime": 0.03}"#,
+        );
+        write(
+            &root.join("sub-01/func/sub-01_task-rest_bold.json"),
+            r#"{"RepetitionTime": 2.5}"#,
+        );
+        write(
+            &root.join("sub-01/func/sub-01_task-rest_physio.json"),
+            r#"{"SamplingFrequency": 100}"#,
+        );
+        let image = root.join("sub-01/func/sub-01_task-rest_bold.nii");
+        fs::copy("resources/minimal.nii", &image).unwrap();
+
+        let (obj, sidecar) = InMemNiftiObject::from_file_with_sidecar(&image).unwrap();
+        assert_eq!(obj.header().dim[1], 64);
+        let sidecar = sidecar.unwrap();
+        assert_eq!(sidecar.sources().len(), 3);
+        assert_eq!(sidecar.repetition_time(), Some(2.5));
+        assert_eq!(sidecar.get("TaskName").unwrap(), "rest");
+        assert_eq!(sidecar.get("EchoTime").unwrap(), 0.03);
+        assert_eq!(sidecar.get("SamplingFrequency"), None);
+        assert_eq!(sidecar.slice_timing(), Some(vec![0., 1.]));
+
+        let other = root.join("sub-01/anat/sub-01_T1w.nii");
+        write(&other, "");
+        assert_eq!(Sidecar::discover(&other).unwrap(), None);
+    }
+
+    #[test]
+    fn sidecar_outside_dataset() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        write(&root.join("task-rest_bold.json"), r#"{"TaskName": "rest"}"#);
+        write(
+            &root.join("func/sub-01_task-rest_bold.json"),
+            r#"{"RepetitionTime": 2.5}"#,
+        );
+        let image = root.join("func/sub-01_task-rest_bold.nii");
+        write(&image, "");
+
+        let sidecar = Sidecar::discover(&image).unwrap().unwrap();
+        assert_eq!(sidecar.sources().len(), 1);
+        assert_eq!(sidecar.repetition_time(), Some(2.5));
+        assert_eq!(sidecar.get("TaskName"), None);
+    }
+
+    #[test]
+    fn invalid_sidecar() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("sub-01_bold.json");
+        write(&path, "[1, 2]");
+        assert!(Sidecar::from_file(&path).is_err());
+        write(&path, "{");
+        assert!(Sidecar::from_file(&path).is_err());
+    }
+
+    fn bold_header() -> NiftiHeader {
+        let mut header = NiftiHeader {
+            dim: [4, 64, 64, 4, 100, 1, 1, 1],
+            pixdim: [1., 3., 3., 3., 2., 0., 0., 0.],
+            ..NiftiHeader::default()
+        };
+        header.set_xyzt_units(Unit::Mm, Unit::Sec).unwrap();
+        header
+    }
+
+    #[test]
+    fn reconcile_sidecar() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("sub-01_task-rest_bold.json");
+        write(
+            &path,
+            r#"{"RepetitionTime": 2.0, "SliceTiming": [0.0, 1.0, 0.5, 1.5]}"#,
+        );
+        let sidecar = Sidecar::from_file(&path).unwrap();
+
+        let mut header = bold_header();
+        assert_eq!(
+            sidecar.reconcile(&header),
+            vec![Inconsistency::MissingSliceTiming]
+        );
+
+        sidecar.apply(&mut header).unwrap();
+        assert_eq!(header.slice_dim(), Some(2));
+        assert_eq!(header.slice_order().unwrap(), SliceOrder::AltInc);
+        assert_eq!(sidecar.reconcile(&header), vec![]);
+
+        header.pixdim[4] = 2.5;
+        header.slice_code = SliceOrder::SeqInc as u8;
+        let inconsistencies = sidecar.reconcile(&header);
+        assert_eq!(inconsistencies.len(), 2);
+        assert_eq!(
+            inconsistencies[0],
+            Inconsistency::RepetitionTime {
+                sidecar: 2.0,
+                header: 2.5,
+            }
+        );
+        match inconsistencies[1] {
+            Inconsistency::SliceTiming { ref header, .. } => {
+                assert_eq!(header, &vec![Some(0.), Some(0.5), Some(1.), Some(1.5)]);
+            }
+            ref i => panic!("unexpected inconsistency {:?}", i),
+        }
+
+        header.dim[3] = 5;
+        assert_eq!(
+            sidecar.reconcile(&header)[1],
+            Inconsistency::SliceCount {
+                sidecar: 4,
+                header: 5,
+            }
+        );
+    }
+}
@@ -0,0 +1,283 @@
+#[cfg(feature = "cifti")]
+extern crate byteorder;
+#[cfg(feature = "cifti")]
+extern crate flate2;
+#[cfg(feature = "cifti")]
+extern crate nifti;
+#[cfg(feature = "cifti")]
+#[macro_use]
+extern crate pretty_assertions;
+
+#[cfg(feature = "cifti")]
+mod tests {
+    use byteorder::{LittleEndian, WriteBytesExt};
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use nifti::cifti::{Cifti, CiftiObject, Grayordinate, IndicesMap, ModelType, SeriesUnit};
+    use nifti::{Extension, ExtensionCode, NiftiType};
+    use std::io::Write;
+
+    const DTSERIES_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<CIFTI Version="2">
+  <Matrix>
+    <MetaData>
+      <MD><Name>Provenance</Name><Value>test</Value></MD>
+    </MetaData>
+    <MatrixIndicesMap AppliesToMatrixDimension="0"
+        IndicesMapToDataType="CIFTI_INDEX_TYPE_SERIES" NumberOfSeriesPoints="3"
+        SeriesExponent="0" SeriesStart="0.0" SeriesStep="0.72" SeriesUnit="SECOND"/>
+    <MatrixIndicesMap AppliesToMatrixDimension="1"
+        IndicesMapToDataType="CIFTI_INDEX_TYPE_BRAIN_MODELS">
+      <Volume VolumeDimensions="91,109,91">
+        <TransformationMatrixVoxelIndicesIJKtoXYZ MeterExponent="-3">
+          -2 0 0 90
+          0 2 0 -126
+          0 0 2 -72
+          0 0 0 1
+        </TransformationMatrixVoxelIndicesIJKtoXYZ>
+      </Volume>
+      <BrainModel IndexOffset="0" IndexCount="2" ModelType="CIFTI_MODEL_TYPE_SURFACE"
+          BrainStructure="CIFTI_STRUCTURE_CORTEX_LEFT" SurfaceNumberOfVertices="32492">
+        <VertexIndices>5 8</VertexIndices>
+      </BrainModel>
+      <BrainModel IndexOffset="2" IndexCount="2" ModelType="CIFTI_MODEL_TYPE_VOXELS"
+          BrainStructure="CIFTI_STRUCTURE_THALAMUS_LEFT">
+        <VoxelIndicesIJK>
+          50 60 40
+          51 60 40
+        </VoxelIndicesIJK>
+      </BrainModel>
+    </MatrixIndicesMap>
+  </Matrix>
+</CIFTI>
+"#;
+
+    const DLABEL_XML: &str = r#"<CIFTI Version="2">
+  <Matrix>
+    <MatrixIndicesMap AppliesToMatrixDimension="0"
+        IndicesMapToDataType="CIFTI_INDEX_TYPE_LABELS">
+      <NamedMap>
+        <MapName>aparc</MapName>
+        <LabelTable>
+          <Label Key="0" Red="1" Green="1" Blue="1" Alpha="0">???</Label>
+          <Label Key="1" Red="0.1" Green="0.4" Blue="0.2" Alpha="1">L_bankssts</Label>
+        </LabelTable>
+      </NamedMap>
+    </MatrixIndicesMap>
+    <MatrixIndicesMap AppliesToMatrixDimension="1"
+        IndicesMapToDataType="CIFTI_INDEX_TYPE_PARCELS">
+      <Surface BrainStructure="CIFTI_STRUCTURE_CORTEX_LEFT" SurfaceNumberOfVertices="32492"/>
+      <Parcel Name="L_V1">
+        <Vertices BrainStructure="CIFTI_STRUCTURE_CORTEX_LEFT">1 2 3</Vertices>
+      </Parcel>
+    </MatrixIndicesMap>
+  </Matrix>
+</CIFTI>"#;
+
+    #[test]
+    fn dtseries_xml() {
+        let cifti = Cifti::from_xml(DTSERIES_XML).unwrap();
+        assert_eq!(cifti.version, "2");
+        assert_eq!(
+            cifti.metadata,
+            vec![("Provenance".to_string(), "test".to_string())]
+        );
+
+        let series = cifti.map(0).unwrap();
+        assert_eq!(series.len(), 3);
+        match series.map {
+            IndicesMap::Series(ref s) => {
+                assert_eq!(s.unit, SeriesUnit::Second);
+                assert!((s.value(2) - 1.44).abs() < 1e-9);
+            }
+            ref m => panic!("unexpected map {:?}", m),
+        }
+
+        let models = cifti.map(1).unwrap();
+        assert_eq!(models.len(), 4);
+        assert_eq!(
+            models.grayordinate(1),
+            Some(Grayordinate::Vertex {
+                structure: "CIFTI_STRUCTURE_CORTEX_LEFT",
+                vertex: 8,
+            })
+        );
+        assert_eq!(
+            models.grayordinate(3),
+            Some(Grayordinate::Voxel {
+                structure: "CIFTI_STRUCTURE_THALAMUS_LEFT",
+                ijk: [51, 60, 40],
+            })
+        );
+        assert_eq!(models.grayordinate(4), None);
+        assert_eq!(
+            models.find_vertex("CIFTI_STRUCTURE_CORTEX_LEFT", 5),
+            Some(0)
+        );
+        assert_eq!(models.find_vertex("CIFTI_STRUCTURE_CORTEX_RIGHT", 5), None);
+        assert_eq!(models.find_voxel([50, 60, 40]), Some(2));
+
+        match models.map {
+            IndicesMap::BrainModels {
+                volume: Some(ref volume),
+                ref models,
+            } => {
+                assert_eq!(volume.dimensions, [91, 109, 91]);
+                assert_eq!(volume.transform[0], [-2., 0., 0., 90.]);
+                assert_eq!(models[0].model_type, ModelType::Surface);
+                assert_eq!(models[0].surface_number_of_vertices, Some(32492));
+            }
+            ref m => panic!("unexpected map {:?}", m),
+        }
+    }
+
+    #[test]
+    fn dlabel_xml() {
+        let cifti = Cifti::from_xml(DLABEL_XML).unwrap();
+        let labels = cifti.map(0).unwrap();
+        let map = labels.named_map(0).unwrap();
+        assert_eq!(map.name, "aparc");
+        let table = map.label_table.as_ref().unwrap();
+        assert_eq!(table.len(), 2);
+        let label = table.find("L_bankssts").unwrap();
+        assert_eq!(label.value, 1);
+        assert_eq!(label.color, [26, 102, 51, 255]);
+
+        let parcels = cifti.map(1).unwrap();
+        let parcel = parcels.parcel(0).unwrap();
+        assert_eq!(parcel.name, "L_V1");
+        assert_eq!(
+            parcel.vertices,
+            vec![("CIFTI_STRUCTURE_CORTEX_LEFT".to_string(), vec![1, 2, 3])]
+        );
+        assert_eq!(parcels.grayordinate(0), None);
+    }
+
+    #[test]
+    fn invalid_xml() {
+        assert!(Cifti::from_xml("<CIFTI Version=\"2\">").is_err());
+        assert!(Cifti::from_xml("<NotCifti/>").is_err());
+        let bad_count = DTSERIES_XML.replace(
+            "IndexCount=\"2\" ModelType=\"CIFTI_MODEL_TYPE_SURFACE\"",
+            "IndexCount=\"3\" ModelType=\"CIFTI_MODEL_TYPE_SURFACE\"",
+        );
+        assert!(Cifti::from_xml(&bad_count).is_err());
+    }
+
+    /// Build the header and the extensions of a little endian NIfTI-2
+    /// file, up to the data matrix.
+    fn cifti_header(datatype: NiftiType, dim: &[i64; 8], xml: &str) -> Vec<u8> {
+        let extension = Extension::from_data(ExtensionCode::Cifti as i32, xml.into());
+        let vox_offset = 544 + extension.size() as i64;
+
+        let mut header = Vec::new();
+        header.write_i32::<LittleEndian>(540).unwrap();
+        header.extend_from_slice(b"n+2\0\r\n\x1a\n");
+        header.write_i16::<LittleEndian>(datatype as i16).unwrap();
+        header
+            .write_i16::<LittleEndian>(datatype.size_of() as i16 * 8)
+            .unwrap();
+        for d in dim {
+            header.write_i64::<LittleEndian>(*d).unwrap();
+        }
+        header.resize(168, 0);
+        header.write_i64::<LittleEndian>(vox_offset).unwrap();
+        header.write_f64::<LittleEndian>(1.).unwrap();
+        header.write_f64::<LittleEndian>(0.).unwrap();
+        header.resize(540, 0);
+
+        header.extend_from_slice(&[1, 0, 0, 0]);
+        header.write_i32::<LittleEndian>(extension.size()).unwrap();
+        header.write_i32::<LittleEndian>(extension.code()).unwrap();
+        header.extend_from_slice(extension.data());
+        header
+    }
+
+    /// Build a little endian NIfTI-2 file holding a float32 matrix of
+    /// 3 time points by 4 grayordinates.
+    fn dtseries_file() -> Vec<u8> {
+        let mut file = cifti_header(NiftiType::Float32, &[6, 1, 1, 1, 1, 3, 4, 1], DTSERIES_XML);
+        for i in 0..12 {
+            file.write_f32::<LittleEndian>(i as f32).unwrap();
+        }
+        file
+    }
+
+    #[test]
+    fn dtseries_object() {
+        let obj = CiftiObject::from_stream(dtseries_file().as_slice()).unwrap();
+        assert_eq!(obj.dim(), &[3, 4]);
+        assert_eq!(obj.data_type(), NiftiType::Float32);
+        assert_eq!(obj.raw_data().len(), 12 * 4);
+        assert_eq!(obj.cifti().maps.len(), 2);
+        assert_eq!(obj.extensions().len(), 1);
+        assert_eq!(obj.get(&[1, 2]), Some(7.));
+        assert_eq!(obj.get(&[3, 0]), None);
+        assert_eq!(obj.get(&[1]), None);
+        assert_eq!(obj.slice(0, 1).unwrap(), vec![1., 4., 7., 10.]);
+        assert_eq!(obj.slice(1, 3).unwrap(), vec![9., 10., 11.]);
+        assert!(obj.slice(2, 0).is_err());
+        assert!(obj.slice(0, 3).is_err());
+    }
+
+    #[test]
+    fn large_dscalar_object() {
+        // a single map over more grayordinates than fit in 16 bits
+        let xml = r#"<CIFTI Version="2">
+  <Matrix>
+    <MatrixIndicesMap AppliesToMatrixDimension="0"
+        IndicesMapToDataType="CIFTI_INDEX_TYPE_SCALARS">
+      <NamedMap><MapName>thickness</MapName></NamedMap>
+    </MatrixIndicesMap>
+  </Matrix>
+</CIFTI>"#;
+        let mut file = cifti_header(NiftiType::Uint8, &[6, 1, 1, 1, 1, 1, 70000, 1], xml);
+        file.extend((0..70000).map(|i| (i % 251) as u8));
+
+        let obj = CiftiObject::from_stream(file.as_slice()).unwrap();
+        assert_eq!(obj.dim(), &[1, 70000]);
+        assert_eq!(obj.get(&[0, 69999]), Some((69999 % 251) as f64));
+        assert_eq!(obj.get(&[0, 70000]), None);
+        let values = obj.slice(0, 0).unwrap();
+        assert_eq!(values.len(), 70000);
+        assert_eq!(values[65536], (65536 % 251) as f64);
+        assert_eq!(obj.slice(1, 65536).unwrap(), vec![(65536 % 251) as f64]);
+    }
+
+    #[test]
+    fn corrupt_dtseries() {
+        // negative data offset
+        let mut file = dtseries_file();
+        file[168..176].copy_from_slice(&(-1i64).to_le_bytes());
+        assert!(CiftiObject::from_stream(file.as_slice()).is_err());
+
+        // huge matrix in a small file
+        let mut file = dtseries_file();
+        for i in 5..8 {
+            file[16 + 8 * i..24 + 8 * i].copy_from_slice(&65535i64.to_le_bytes());
+        }
+        file[16..24].copy_from_slice(&7i64.to_le_bytes());
+        assert!(CiftiObject::from_stream(file.as_slice()).is_err());
+
+        // truncated data
+        let file = dtseries_file();
+        assert!(CiftiObject::from_stream(&file[..file.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn misnamed_gz_dtseries() {
+        let mut e = GzEncoder::new(Vec::new(), Compression::fast());
+        e.write_all(&dtseries_file()).unwrap();
+        let path = ::std::env::temp_dir().join("nifti_cifti_misnamed.dtseries.nii");
+        ::std::fs::write(&path, e.finish().unwrap()).unwrap();
+        let obj = CiftiObject::from_file(&path).unwrap();
+        ::std::fs::remove_file(&path).unwrap();
+        assert_eq!(obj.slice(0, 2).unwrap(), vec![2., 5., 8., 11.]);
+    }
+
+    #[test]
+    fn not_nifti2() {
+        let err = CiftiObject::from_file("resources/minimal.nii");
+        assert!(err.is_err());
+    }
+}
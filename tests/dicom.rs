@@ -0,0 +1,122 @@
+extern crate byteorder;
+extern crate nifti;
+#[macro_use]
+extern crate pretty_assertions;
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use nifti::dicom::{DicomDataSet, DicomTag, DicomValue, ECHO_TIME, REPETITION_TIME};
+use nifti::{Extension, ExtensionCode, ExtensionSequence};
+
+fn explicit(out: &mut Vec<u8>, tag: (u16, u16), vr: &[u8; 2], value: &[u8]) {
+    out.write_u16::<LittleEndian>(tag.0).unwrap();
+    out.write_u16::<LittleEndian>(tag.1).unwrap();
+    out.extend_from_slice(vr);
+    out.write_u16::<LittleEndian>(value.len() as u16).unwrap();
+    out.extend_from_slice(value);
+}
+
+fn implicit(out: &mut Vec<u8>, tag: (u16, u16), value: &[u8]) {
+    out.write_u16::<LittleEndian>(tag.0).unwrap();
+    out.write_u16::<LittleEndian>(tag.1).unwrap();
+    out.write_u32::<LittleEndian>(value.len() as u32).unwrap();
+    out.extend_from_slice(value);
+}
+
+fn explicit_data_set() -> Vec<u8> {
+    let mut data = Vec::new();
+    explicit(&mut data, (0x0002, 0x0010), b"UI", b"1.2.840.10008.1.2.1\0");
+    explicit(&mut data, (0x0008, 0x103E), b"LO", b"T1 MPRAGE ");
+    // a sequence of undefined length, with an item of undefined length
+    data.extend_from_slice(&[0x08, 0x00, 0x15, 0x11, b'S', b'Q', 0, 0]);
+    data.write_u32::<LittleEndian>(0xFFFF_FFFF).unwrap();
+    data.extend_from_slice(&[0xFE, 0xFF, 0x00, 0xE0]);
+    data.write_u32::<LittleEndian>(0xFFFF_FFFF).unwrap();
+    explicit(&mut data, (0x0028, 0x0010), b"US", &[64, 0]);
+    data.extend_from_slice(&[0xFE, 0xFF, 0x0D, 0xE0, 0, 0, 0, 0]);
+    data.extend_from_slice(&[0xFE, 0xFF, 0xDD, 0xE0, 0, 0, 0, 0]);
+    explicit(&mut data, (0x0018, 0x0080), b"DS", b"2300");
+    explicit(&mut data, (0x0018, 0x0081), b"DS", b"2.98");
+    explicit(&mut data, (0x0020, 0x0037), b"DS", b"1\\0\\0\\0\\0\\-1 ");
+    data
+}
+
+#[test]
+fn explicit_vr() {
+    let dicom = DicomDataSet::from_bytes(&explicit_data_set()).unwrap();
+    assert_eq!(dicom.len(), 6);
+    assert_eq!(dicom.series_description(), Some("T1 MPRAGE".to_string()));
+    assert_eq!(dicom.repetition_time(), Some(2300.));
+    assert_eq!(dicom.echo_time(), Some(2.98));
+    assert_eq!(
+        dicom.image_orientation_patient(),
+        Some([1., 0., 0., 0., 0., -1.])
+    );
+
+    let sequence = dicom.get(DicomTag(0x0008, 0x1115)).unwrap();
+    assert_eq!(&sequence.vr, b"SQ");
+    match sequence.value {
+        DicomValue::Sequence(ref items) => {
+            assert_eq!(items.len(), 1);
+            assert_eq!(items[0].number(DicomTag(0x0028, 0x0010)), Some(64.));
+        }
+        ref v => panic!("unexpected value {:?}", v),
+    }
+}
+
+#[test]
+fn implicit_vr() {
+    let mut data = vec![0u8; 128];
+    data.extend_from_slice(b"DICM");
+    explicit(&mut data, (0x0002, 0x0010), b"UI", b"1.2.840.10008.1.2\0");
+    implicit(&mut data, (0x0008, 0x103E), b"rest");
+    implicit(&mut data, (0x0018, 0x0080), b"800 ");
+    implicit(&mut data, (0x0019, 0x100A), &[1, 2, 3, 4]);
+
+    let dicom = DicomDataSet::from_bytes(&data).unwrap();
+    assert_eq!(dicom.series_description(), Some("rest".to_string()));
+    assert_eq!(dicom.number(REPETITION_TIME), Some(800.));
+    assert_eq!(dicom.number(ECHO_TIME), None);
+    let private = dicom.get(DicomTag(0x0019, 0x100A)).unwrap();
+    assert_eq!(&private.vr, b"UN");
+    assert_eq!(private.bytes(), Some(&[1, 2, 3, 4][..]));
+}
+
+#[test]
+fn dicom_extension() {
+    let mut extensions = ExtensionSequence::new();
+    extensions.push(Extension::from_data(
+        ExtensionCode::Dicom as i32,
+        explicit_data_set(),
+    ));
+    let dicom = DicomDataSet::from_extensions(&extensions).unwrap().unwrap();
+    assert_eq!(dicom.len(), 6);
+    assert_eq!(dicom.echo_time(), Some(2.98));
+}
+
+#[test]
+fn truncated_dicom() {
+    let data = explicit_data_set();
+    assert!(DicomDataSet::from_bytes(&data[..data.len() - 3]).is_err());
+}
+
+#[test]
+fn padded_dicom() {
+    let mut data = explicit_data_set();
+    data.extend_from_slice(&[0, 0]);
+    assert_eq!(DicomDataSet::from_bytes(&data).unwrap().len(), 6);
+    data.extend_from_slice(&[0, 0, 0, 0, 1, 0]);
+    assert!(DicomDataSet::from_bytes(&data).is_err());
+}
+
+#[test]
+fn deeply_nested_dicom() {
+    let mut data = Vec::new();
+    explicit(&mut data, (0x0002, 0x0010), b"UI", b"1.2.840.10008.1.2.1\0");
+    for _ in 0..10_000 {
+        data.extend_from_slice(&[0x08, 0x00, 0x15, 0x11, b'S', b'Q', 0, 0]);
+        data.write_u32::<LittleEndian>(0xFFFF_FFFF).unwrap();
+        data.extend_from_slice(&[0xFE, 0xFF, 0x00, 0xE0]);
+        data.write_u32::<LittleEndian>(0xFFFF_FFFF).unwrap();
+    }
+    assert!(DicomDataSet::from_bytes(&data).is_err());
+}
@@ -0,0 +1,116 @@
+extern crate byteorder;
+extern crate nifti;
+#[macro_use]
+extern crate pretty_assertions;
+
+use byteorder::LittleEndian;
+use nifti::{Extender, Extension, ExtensionCode, ExtensionSequence, InMemNiftiObject,
+            NiftiObject};
+
+fn extension_bytes(ecode: i32, text: &str) -> Vec<u8> {
+    let mut data = text.as_bytes().to_vec();
+    let size = (data.len() + 8).div_ceil(16) * 16;
+    data.resize(size - 8, 0);
+    let mut bytes = Vec::new();
+    bytes.extend(&(size as i32).to_le_bytes());
+    bytes.extend(&ecode.to_le_bytes());
+    bytes.extend(data);
+    bytes
+}
+
+#[test]
+fn typed_extensions() {
+    let mut bytes = Vec::new();
+    bytes.extend(extension_bytes(6, "first comment"));
+    bytes.extend(extension_bytes(4, "<?xml version='1.0' ?><AFNI_attributes/>"));
+    bytes.extend(extension_bytes(6, "second comment"));
+    bytes.extend(extension_bytes(44, "{\"SpectrometerFrequency\": [123.2]}"));
+    bytes.extend(extension_bytes(1234, "unregistered"));
+    let len = bytes.len();
+    let extensions = ExtensionSequence::from_stream::<LittleEndian, _>(
+        Extender::from([1, 0, 0, 0]),
+        bytes.as_slice(),
+        len,
+    )
+    .unwrap();
+    assert_eq!(extensions.len(), 5);
+
+    let codes: Vec<_> = extensions.iter().map(Extension::extension_code).collect();
+    assert_eq!(
+        codes,
+        vec![
+            Some(ExtensionCode::Comment),
+            Some(ExtensionCode::Afni),
+            Some(ExtensionCode::Comment),
+            Some(ExtensionCode::Mrs),
+            None,
+        ]
+    );
+
+    assert_eq!(extensions.comments(), vec!["first comment", "second comment"]);
+    assert_eq!(
+        extensions.afni_xml().unwrap(),
+        "<?xml version='1.0' ?><AFNI_attributes/>"
+    );
+    assert_eq!(
+        extensions.json().unwrap(),
+        "{\"SpectrometerFrequency\": [123.2]}"
+    );
+    assert_eq!(extensions.cifti_xml(), None);
+    assert_eq!(extensions.with_code(ExtensionCode::Comment).count(), 2);
+    assert_eq!(
+        extensions.find(ExtensionCode::Afni).unwrap().size() % 16,
+        0
+    );
+}
+
+#[test]
+fn padded_extension() {
+    let extension = Extension::from_data(6, b"hello".to_vec());
+    assert_eq!(extension.size(), 16);
+    assert_eq!(extension.data().len(), 8);
+    assert_eq!(extension.text(), "hello");
+
+    let extension = Extension::from_data(6, vec![b'a'; 8]);
+    assert_eq!(extension.size(), 16);
+    let extension = Extension::from_data(6, vec![b'a'; 9]);
+    assert_eq!(extension.size(), 32);
+    assert_eq!(Extension::from_data(0, vec![]).size(), 16);
+}
+
+#[test]
+fn mutate_extensions() {
+    let mut obj = InMemNiftiObject::from_file("resources/minimal.nii").unwrap();
+    assert!(obj.extensions().is_empty());
+    assert!(!obj.extensions().extender().has_extensions());
+
+    {
+        let extensions = obj.extensions_mut();
+        extensions.push(Extension::from_data(6, b"first".to_vec()));
+        extensions.push(Extension::from_data(4, b"<AFNI_attributes/>".to_vec()));
+        extensions.push(Extension::from_data(6, b"second".to_vec()));
+    }
+    assert_eq!(obj.extensions().len(), 3);
+    assert!(obj.extensions().extender().has_extensions());
+    assert_eq!(obj.extensions().comments(), vec!["first", "second"]);
+
+    let removed = obj.extensions_mut().remove(0);
+    assert_eq!(removed.text(), "first");
+    assert_eq!(obj.extensions().comments(), vec!["second"]);
+
+    obj.extensions_mut()
+        .retain(|e| e.extension_code() == Some(ExtensionCode::Afni));
+    assert_eq!(obj.extensions().len(), 1);
+    assert!(obj.extensions().extender().has_extensions());
+
+    obj.extensions_mut().push(Extension::new(13, 6, b"third".to_vec()));
+    let pushed = obj.extensions().iter().nth(1).unwrap();
+    assert_eq!(pushed.size(), 16);
+    assert_eq!(pushed.data().len(), 8);
+    assert_eq!(pushed.text(), "third");
+
+    obj.extensions_mut().retain(|_| false);
+    assert!(obj.extensions().is_empty());
+    assert!(!obj.extensions().extender().has_extensions());
+    assert_eq!(obj.extensions(), &ExtensionSequence::new());
+}
@@ -0,0 +1,166 @@
+#[cfg(feature = "gifti")]
+extern crate base64;
+#[cfg(feature = "gifti")]
+extern crate flate2;
+#[cfg(feature = "gifti")]
+extern crate nifti;
+#[cfg(feature = "gifti")]
+#[macro_use]
+extern crate pretty_assertions;
+
+#[cfg(feature = "gifti")]
+mod tests {
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use nifti::gifti::{ArrayIndexingOrder, Gifti, GiftiData};
+    use nifti::{Intent, NiftiError, NiftiType, XForm};
+    use std::io::Write;
+
+    const SURF_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE GIFTI SYSTEM "http://www.nitrc.org/frs/download.php/115/gifti.dtd">
+<GIFTI Version="1.0" NumberOfDataArrays="2">
+  <MetaData>
+    <MD><Name><![CDATA[AnatomicalStructurePrimary]]></Name><Value><![CDATA[CortexLeft]]></Value></MD>
+  </MetaData>
+  <DataArray Intent="NIFTI_INTENT_POINTSET" DataType="NIFTI_TYPE_FLOAT32"
+      ArrayIndexingOrder="RowMajorOrder" Dimensionality="2" Dim0="4" Dim1="3"
+      Encoding="ASCII" Endian="LittleEndian" ExternalFileName="" ExternalFileOffset="">
+    <MetaData>
+      <MD><Name>GeometricType</Name><Value>Anatomical</Value></MD>
+    </MetaData>
+    <CoordinateSystemTransformMatrix>
+      <DataSpace><![CDATA[NIFTI_XFORM_TALAIRACH]]></DataSpace>
+      <TransformedSpace><![CDATA[NIFTI_XFORM_TALAIRACH]]></TransformedSpace>
+      <MatrixData>
+        1 0 0 10
+        0 1 0 20
+        0 0 1 30
+        0 0 0 1
+      </MatrixData>
+    </CoordinateSystemTransformMatrix>
+    <Data>
+      0 0 0
+      1 0 0
+      0 1 0
+      0 0 1.5
+    </Data>
+  </DataArray>
+  <DataArray Intent="NIFTI_INTENT_TRIANGLE" DataType="NIFTI_TYPE_INT32"
+      ArrayIndexingOrder="ColumnMajorOrder" Dimensionality="2" Dim0="2" Dim1="3"
+      Encoding="ASCII" Endian="LittleEndian" ExternalFileName="" ExternalFileOffset="">
+    <Data>0 0 1 2 2 3</Data>
+  </DataArray>
+</GIFTI>
+"#;
+
+    fn shape_xml(encoding: &str, endian: &str, data: &str) -> String {
+        format!(
+            r#"<GIFTI Version="1.0" NumberOfDataArrays="1">
+  <DataArray Intent="NIFTI_INTENT_SHAPE" DataType="NIFTI_TYPE_FLOAT32"
+      ArrayIndexingOrder="RowMajorOrder" Dimensionality="1" Dim0="3"
+      Encoding="{}" Endian="{}" ExternalFileName="" ExternalFileOffset="">
+    <Data>{}</Data>
+  </DataArray>
+</GIFTI>"#,
+            encoding, endian, data
+        )
+    }
+
+    #[test]
+    fn ascii_surface() {
+        let gifti = Gifti::from_xml(SURF_XML).unwrap();
+        assert_eq!(gifti.version, "1.0");
+        assert_eq!(
+            gifti.metadata,
+            vec![(
+                "AnatomicalStructurePrimary".to_string(),
+                "CortexLeft".to_string()
+            )]
+        );
+        assert_eq!(gifti.arrays.len(), 2);
+
+        let points = &gifti.arrays[0];
+        assert_eq!(points.intent, Intent::Pointset);
+        assert_eq!(points.data.datatype(), NiftiType::Float32);
+        assert_eq!(points.dim, vec![4, 3]);
+        assert_eq!(points.metadata[0].1, "Anatomical");
+        assert_eq!(points.transforms.len(), 1);
+        assert_eq!(points.transforms[0].data_space, XForm::Talairach);
+        assert_eq!(points.transforms[0].matrix[1], [0., 1., 0., 20.]);
+        assert_eq!(points.get(&[3, 2]), Some(1.5));
+        assert_eq!(points.get(&[4, 0]), None);
+        assert_eq!(
+            gifti.pointset().unwrap(),
+            vec![[0., 0., 0.], [1., 0., 0.], [0., 1., 0.], [0., 0., 1.5]]
+        );
+
+        let triangles = &gifti.arrays[1];
+        assert_eq!(triangles.order, ArrayIndexingOrder::ColumnMajor);
+        assert_eq!(triangles.data, GiftiData::Int32(vec![0, 0, 1, 2, 2, 3]));
+        assert_eq!(gifti.triangles().unwrap(), vec![[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(gifti.arrays_with_intent(Intent::Shape).count(), 0);
+    }
+
+    #[test]
+    fn binary_arrays() {
+        let values = [1.5f32, -2., 1e6];
+        let le: Vec<u8> = values
+            .iter()
+            .flat_map(|v| v.to_le_bytes().to_vec())
+            .collect();
+        let be: Vec<u8> = values
+            .iter()
+            .flat_map(|v| v.to_be_bytes().to_vec())
+            .collect();
+        let mut e = ZlibEncoder::new(Vec::new(), Compression::default());
+        e.write_all(&le).unwrap();
+        let zlib = e.finish().unwrap();
+
+        for xml in &[
+            shape_xml("Base64Binary", "LittleEndian", &base64::encode(&le)),
+            shape_xml("Base64Binary", "BigEndian", &base64::encode(&be)),
+            shape_xml("GZipBase64Binary", "LittleEndian", &base64::encode(&zlib)),
+        ] {
+            let gifti = Gifti::from_xml(xml).unwrap();
+            assert_eq!(gifti.arrays[0].intent, Intent::Shape);
+            assert_eq!(gifti.arrays[0].data.as_f32().unwrap(), &values[..]);
+            assert_eq!(gifti.arrays[0].data.to_f64(), vec![1.5, -2., 1e6]);
+        }
+    }
+
+    #[test]
+    fn label_table() {
+        let xml = r#"<GIFTI Version="1.0" NumberOfDataArrays="1">
+  <LabelTable>
+    <Label Key="0" Red="1" Green="1" Blue="1" Alpha="0"><![CDATA[???]]></Label>
+    <Label Key="1" Red="1" Green="0" Blue="0" Alpha="1"><![CDATA[V1]]></Label>
+  </LabelTable>
+  <DataArray Intent="NIFTI_INTENT_LABEL" DataType="NIFTI_TYPE_INT32"
+      ArrayIndexingOrder="RowMajorOrder" Dimensionality="1" Dim0="3"
+      Encoding="ASCII" Endian="LittleEndian" ExternalFileName="" ExternalFileOffset="">
+    <Data>0 1 1</Data>
+  </DataArray>
+</GIFTI>"#;
+        let gifti = Gifti::from_xml(xml).unwrap();
+        let table = gifti.label_table.unwrap();
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.get(1).unwrap().name, "V1");
+        assert_eq!(table.get(1).unwrap().color, [255, 0, 0, 255]);
+        assert_eq!(gifti.arrays[0].data.as_i32().unwrap(), &[0, 1, 1]);
+    }
+
+    #[test]
+    fn invalid_gifti() {
+        match Gifti::from_xml("<CIFTI Version=\"2\"/>") {
+            Err(NiftiError::InvalidGifti(_)) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+        assert!(Gifti::from_xml("<GIFTI>").is_err());
+        assert!(Gifti::from_xml(&shape_xml("ASCII", "LittleEndian", "1 2")).is_err());
+        assert!(Gifti::from_xml(&shape_xml("ASCII", "LittleEndian", "1 2 x")).is_err());
+        assert!(Gifti::from_xml(&shape_xml("Base64Binary", "LittleEndian", "AAAA")).is_err());
+        assert!(Gifti::from_xml(&shape_xml("ExternalFileBinary", "LittleEndian", "")).is_err());
+        let xml = shape_xml("ASCII", "LittleEndian", "1 2 3").replace("FLOAT32", "FLOAT64");
+        assert!(Gifti::from_xml(&xml).is_err());
+    }
+}
@@ -2,7 +2,8 @@ extern crate nifti;
 #[macro_use]
 extern crate pretty_assertions;
 
-use nifti::{Endianness, Intent, NiftiHeader, NiftiType, SliceOrder, Unit, XForm};
+use nifti::{Endianness, Intent, IntentSpec, NiftiHeader, NiftiType, SliceInfo, SliceOrder, Unit,
+            XForm};
 use std::fs::File;
 
 #[test]
@@ -230,3 +231,311 @@ fn zstat1_nii_gz() {
     assert_eq!(header.qform().unwrap(), XForm::ScannerAnat);
     assert_eq!(header.sform().unwrap(), XForm::Unknown);
 }
+
+#[test]
+fn oblique_affine() {
+    let angle = 10f32.to_radians();
+    let mut header = NiftiHeader {
+        dim: [3, 16, 16, 8, 1, 1, 1, 1],
+        pixdim: [1., 2., 2., 3., 1., 1., 1., 1.],
+        sform_code: 1,
+        srow_x: [2. * angle.cos(), -2. * angle.sin(), 0., -10.],
+        srow_y: [2. * angle.sin(), 2. * angle.cos(), 0., 5.],
+        srow_z: [0., 0., 3., 0.],
+        ..Default::default()
+    };
+
+    assert!((header.obliquity() - 10.).abs() < 1e-4);
+    assert!(header.is_oblique());
+
+    let affine = header.affine();
+    header.set_affine(&affine);
+    assert_eq!(header.qform().unwrap(), XForm::AlignedAnat);
+    assert_eq!(header.sform().unwrap(), XForm::ScannerAnat);
+    let qform = header.qform_affine();
+    for (r1, r2) in qform.iter().zip(&affine) {
+        for (v1, v2) in r1.iter().zip(r2) {
+            assert!((v1 - v2).abs() < 1e-4, "{:?} != {:?}", qform, affine);
+        }
+    }
+}
+
+#[test]
+fn zstat1_not_oblique() {
+    const FILE_NAME: &str = "resources/zstat1.nii.gz";
+    let header = NiftiHeader::from_file(FILE_NAME).unwrap();
+
+    // qform is a 180 degree rotation around the y axis
+    let affine = header.affine();
+    assert_eq!(affine[0][0], -4.);
+    assert_eq!(affine[1][1], 4.);
+    assert_eq!(affine[2][2], 6.);
+    assert_eq!(header.obliquity(), 0.);
+    assert!(!header.is_oblique());
+}
+
+#[test]
+#[allow(non_snake_case)]
+fn avg152T1_LR_units() {
+    const FILE_NAME: &str = "resources/avg152T1_LR_nifti.hdr.gz";
+    let mut header = NiftiHeader::from_file(FILE_NAME).unwrap();
+
+    assert_eq!(header.voxel_size_mm().unwrap(), [2., 2., 2.]);
+    assert_eq!(header.voxel_volume_mm3().unwrap(), 8.);
+    assert_eq!(header.field_of_view_mm().unwrap(), [182., 218., 182.]);
+    assert_eq!(header.time_step_sec().unwrap(), 1.);
+
+    header.set_xyzt_units(Unit::Micron, Unit::Msec).unwrap();
+    assert_eq!(header.xyzt_units().unwrap(), (Unit::Micron, Unit::Msec));
+    assert_eq!(&header.pixdim[1..5], &[2000., 2000., 2000., 1000.]);
+    assert_eq!(header.srow_x, [-2000., 0., 0., 90000.]);
+    assert_eq!(header.voxel_size_mm().unwrap(), [2., 2., 2.]);
+    assert_eq!(header.time_step_sec().unwrap(), 1.);
+
+    header.set_xyzt_units(Unit::Mm, Unit::Sec).unwrap();
+    assert_eq!(header, NiftiHeader::from_file(FILE_NAME).unwrap());
+
+    assert!(header.set_xyzt_units(Unit::Sec, Unit::Sec).is_err());
+    assert!(header.set_xyzt_units(Unit::Mm, Unit::Hz).is_err());
+    header.xyzt_units = Unit::Mm as u8 | Unit::Ppm as u8;
+    assert!(header.time_step_sec().is_err());
+}
+
+#[test]
+fn dim_info_axes() {
+    let mut header = NiftiHeader {
+        dim: [4, 64, 64, 30, 100, 1, 1, 1],
+        // freq = 1 (x), phase = 2 (y), slice = 3 (z)
+        dim_info: 0b11_10_01,
+        slice_code: SliceOrder::AltInc as u8,
+        slice_start: 0,
+        slice_end: 29,
+        slice_duration: 66.,
+        xyzt_units: Unit::Mm as u8 | Unit::Msec as u8,
+        ..Default::default()
+    };
+
+    assert_eq!(header.freq_dim(), Some(0));
+    assert_eq!(header.phase_dim(), Some(1));
+    assert_eq!(header.slice_dim(), Some(2));
+    assert_eq!(
+        header.slice_info().unwrap(),
+        SliceInfo {
+            axis: 2,
+            order: SliceOrder::AltInc,
+            num_slices: 30,
+            start: 0,
+            end: 29,
+            duration: 0.066,
+        }
+    );
+
+    header.set_freq_dim(Some(1)).unwrap();
+    header.set_phase_dim(Some(0)).unwrap();
+    assert_eq!(header.dim_info, 0b11_01_10);
+    assert!(header.set_slice_dim(Some(3)).is_err());
+    header.set_slice_dim(None).unwrap();
+    assert_eq!(header.slice_dim(), None);
+    assert!(header.slice_info().is_err());
+
+    header.set_slice_dim(Some(2)).unwrap();
+    header.slice_end = 30;
+    assert!(header.slice_info().is_err());
+    header.slice_end = 0;
+    assert_eq!(header.slice_info().unwrap().end, 29);
+}
+
+#[test]
+fn slice_acquisition_order() {
+    let n = 5;
+    assert_eq!(SliceOrder::Unknown.acquisition_order(n), None);
+    assert_eq!(SliceOrder::SeqInc.acquisition_order(n).unwrap(), vec![0, 1, 2, 3, 4]);
+    assert_eq!(SliceOrder::SeqDec.acquisition_order(n).unwrap(), vec![4, 3, 2, 1, 0]);
+    assert_eq!(SliceOrder::AltInc.acquisition_order(n).unwrap(), vec![0, 2, 4, 1, 3]);
+    assert_eq!(SliceOrder::AltDec.acquisition_order(n).unwrap(), vec![4, 2, 0, 3, 1]);
+    assert_eq!(SliceOrder::AltInc2.acquisition_order(n).unwrap(), vec![1, 3, 0, 2, 4]);
+    assert_eq!(SliceOrder::AltDec2.acquisition_order(n).unwrap(), vec![3, 1, 4, 2, 0]);
+
+    for order in &[
+        SliceOrder::SeqInc,
+        SliceOrder::SeqDec,
+        SliceOrder::AltInc,
+        SliceOrder::AltDec,
+        SliceOrder::AltInc2,
+        SliceOrder::AltDec2,
+    ] {
+        let times = order.slice_times(6, 0.5).unwrap();
+        assert_eq!(SliceOrder::from_slice_times(&times), Some((*order, 0.5)));
+    }
+
+    // multiband acquisitions do not have a single slice order
+    assert_eq!(SliceOrder::from_slice_times(&[0., 0.5, 0., 0.5]), None);
+}
+
+#[test]
+fn slice_times() {
+    let mut header = NiftiHeader {
+        dim: [4, 64, 64, 6, 100, 1, 1, 1],
+        pixdim: [1., 3., 3., 3., 2., 0., 0., 0.],
+        dim_info: 0b11_00_00,
+        slice_code: SliceOrder::AltInc as u8,
+        slice_start: 1,
+        slice_end: 4,
+        xyzt_units: Unit::Mm as u8 | Unit::Sec as u8,
+        ..Default::default()
+    };
+
+    // no slice duration, so TR / 4 is used
+    assert_eq!(
+        header.slice_times().unwrap(),
+        vec![None, Some(0.), Some(1.), Some(0.5), Some(1.5), None]
+    );
+
+    // BIDS `SliceTiming`, in seconds
+    header.xyzt_units = Unit::Mm as u8 | Unit::Msec as u8;
+    header
+        .set_slice_times(&[0.9, 0.6, 0.3, 0., 0.75, 0.45])
+        .unwrap_err();
+    header
+        .set_slice_times(&[0.75, 0.3, 0.6, 0.15, 0.45, 0.])
+        .unwrap();
+    assert_eq!(header.slice_order().unwrap(), SliceOrder::AltDec);
+    assert_eq!((header.slice_start, header.slice_end), (0, 5));
+    assert!((header.slice_duration - 150.).abs() < 1e-3);
+    let times = header.slice_times().unwrap();
+    for (t, e) in times.iter().zip(&[0.75, 0.3, 0.6, 0.15, 0.45, 0.]) {
+        assert!((t.unwrap() - e).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn intent_spec() {
+    let header = NiftiHeader::from_file("resources/zstat1.nii.gz").unwrap();
+    assert_eq!(header.intent_spec().unwrap(), IntentSpec::Zscore);
+
+    let mut header = NiftiHeader::default();
+    header
+        .set_intent_spec(&IntentSpec::TtestNonc {
+            dof: 12.,
+            noncentrality: 1.5,
+        })
+        .unwrap();
+    assert_eq!(header.intent_code, Intent::TtestNonc as i16);
+    assert_eq!(
+        [header.intent_p1, header.intent_p2, header.intent_p3],
+        [12., 1.5, 0.]
+    );
+
+    header
+        .set_intent_spec(&IntentSpec::Estimate {
+            name: "T1".to_string(),
+        })
+        .unwrap();
+    assert_eq!(&header.intent_name[..3], b"T1\0");
+    assert_eq!(
+        [header.intent_p1, header.intent_p2, header.intent_p3],
+        [0.; 3]
+    );
+    assert_eq!(
+        header.intent_spec().unwrap(),
+        IntentSpec::Estimate {
+            name: "T1".to_string()
+        }
+    );
+
+    header.intent_code = Intent::Symmatrix as i16;
+    header.intent_p1 = 3.;
+    assert_eq!(header.intent_spec().unwrap(), IntentSpec::Symmatrix { n: 3 });
+    header.intent_p1 = 2.5;
+    assert!(header.intent_spec().is_err());
+
+    // out of range parameters are rejected, leaving the header untouched
+    let invalid = [
+        IntentSpec::Ftest {
+            dof_num: 0.,
+            dof_den: 10.,
+        },
+        IntentSpec::Binom {
+            trials: 10.,
+            prob: 1.5,
+        },
+        IntentSpec::Uniform {
+            lower: 1.,
+            upper: 1.,
+        },
+        IntentSpec::Normal {
+            mean: 0.,
+            std_dev: f32::NAN,
+        },
+        IntentSpec::Label {
+            name: "a very long label set name".to_string(),
+        },
+    ];
+    for spec in &invalid {
+        assert!(header.set_intent_spec(spec).is_err(), "{:?}", spec);
+    }
+    assert_eq!(header.intent_code, Intent::Symmatrix as i16);
+}
+
+/// Turn the minimal NIfTI-1 header (big endian) into an Analyze 7.5 header.
+fn minimal_analyze_hdr(orient: u8, originator: [i16; 3], funused1: f32) -> Vec<u8> {
+    let mut raw = std::fs::read("resources/minimal.hdr").unwrap();
+    raw.truncate(348);
+    raw[112..116].copy_from_slice(&funused1.to_bits().to_be_bytes());
+    raw[252] = orient;
+    for (i, o) in originator.iter().enumerate() {
+        raw[253 + 2 * i..255 + 2 * i].copy_from_slice(&o.to_be_bytes());
+    }
+    raw[344..348].copy_from_slice(&[0; 4]);
+    raw
+}
+
+#[test]
+fn analyze_hdr() {
+    let raw = minimal_analyze_hdr(0, [32, 33, 5], 2.);
+    assert!(NiftiHeader::from_stream(&raw[..]).is_err());
+
+    let header = NiftiHeader::from_analyze_stream(&raw[..]).unwrap();
+    assert_eq!(&header.magic, b"ni1\0");
+    assert_eq!(header.endianness, Endianness::BE);
+    assert_eq!(header.dim, [3, 64, 64, 10, 0, 0, 0, 0]);
+    assert_eq!(header.data_type().unwrap(), NiftiType::Uint8);
+    assert_eq!(header.scl_slope, 2.);
+    assert_eq!(header.xyzt_units().unwrap(), (Unit::Mm, Unit::Sec));
+    assert_eq!(header.qform().unwrap(), XForm::AlignedAnat);
+    assert_eq!(&header.pixdim[..4], &[-1., 3., 3., 3.]);
+    assert_eq!(
+        header.affine(),
+        [
+            [-3., 0., 0., 93.],
+            [0., 3., 0., -96.],
+            [0., 0., 3., -12.],
+            [0., 0., 0., 1.],
+        ]
+    );
+
+    // coronal, without an origin: the center of the volume is used
+    let raw = minimal_analyze_hdr(1, [0, 0, 0], 0.);
+    let header = NiftiHeader::from_analyze_stream(&raw[..]).unwrap();
+    assert_eq!(header.scl_slope, 0.);
+    assert_eq!(
+        header.affine(),
+        [
+            [-3., 0., 0., 93.],
+            [0., 0., 3., -12.],
+            [0., 3., 0., -93.],
+            [0., 0., 0., 1.],
+        ]
+    );
+
+    // NIfTI-1 headers are left as they are
+    let header = NiftiHeader::from_analyze_file("resources/minimal.hdr").unwrap();
+    assert_eq!(
+        header,
+        NiftiHeader::from_file("resources/minimal.hdr").unwrap()
+    );
+
+    let mut raw = minimal_analyze_hdr(0, [0, 0, 0], 0.);
+    raw[..4].copy_from_slice(&540i32.to_be_bytes());
+    assert!(NiftiHeader::from_analyze_stream(&raw[..]).is_err());
+}
@@ -0,0 +1,74 @@
+extern crate nifti;
+#[macro_use]
+extern crate pretty_assertions;
+
+use nifti::{InMemNiftiObject, Label, LabelTable, NiftiObject, NiftiType, NiftiVolume};
+
+const COLOR_LUT: &str = "\
+#$Id: FreeSurferColorLUT.txt $
+
+#No. Label Name:                            R   G   B   A
+
+0   Unknown                                 0   0   0   0
+2   Left-Cerebral-White-Matter            245 245 245   0
+5   Left-Lateral-Ventricle                120  18 134
+17  Left-Hippocampus                      220 216  20   0
+";
+
+#[test]
+fn color_lut() {
+    let table = LabelTable::from_color_lut(COLOR_LUT.as_bytes()).unwrap();
+    assert_eq!(table.len(), 4);
+    assert_eq!(
+        table.get(17),
+        Some(&Label {
+            value: 17,
+            name: "Left-Hippocampus".to_string(),
+            color: [220, 216, 20, 0],
+        })
+    );
+    assert_eq!(table.find("Left-Lateral-Ventricle").unwrap().value, 5);
+    assert_eq!(table.find("Right-Hippocampus"), None);
+    assert_eq!(
+        table.iter().map(|l| l.value).collect::<Vec<_>>(),
+        vec![0, 2, 5, 17]
+    );
+
+    let mut text = Vec::new();
+    table.to_color_lut(&mut text).unwrap();
+    assert_eq!(LabelTable::from_color_lut(text.as_slice()).unwrap(), table);
+
+    let extension = table.to_extension();
+    assert_eq!(extension.code(), 14);
+    assert_eq!(extension.size() % 16, 0);
+    assert_eq!(LabelTable::from_extension(&extension).unwrap(), table);
+
+    assert!(LabelTable::from_color_lut("1 Foo 1 2".as_bytes()).is_err());
+    assert!(LabelTable::from_color_lut("x Foo 1 2 3 0".as_bytes()).is_err());
+    assert!(LabelTable::from_color_lut("1 Foo 1 2 300 0".as_bytes()).is_err());
+}
+
+#[test]
+fn minimal_nii_labels() {
+    // voxel values are their `j` index, voxels are 3x3x3 mm
+    let obj = InMemNiftiObject::from_file("resources/minimal.nii").unwrap();
+    let table = LabelTable::from_color_lut(COLOR_LUT.as_bytes()).unwrap();
+
+    let summary = table.summary(obj.volume(), obj.header()).unwrap();
+    assert_eq!(summary.len(), 64);
+    for (j, s) in summary.iter().enumerate() {
+        assert_eq!(s.value, j as i32);
+        assert_eq!(s.voxels, 640);
+        assert_eq!(s.volume_mm3, 640. * 27.);
+    }
+    assert_eq!(summary[1].name, None);
+    assert_eq!(summary[5].name, Some("Left-Lateral-Ventricle".to_string()));
+
+    let mask = table.mask(obj.volume(), "Left-Hippocampus").unwrap();
+    assert_eq!(mask.data_type(), NiftiType::Uint8);
+    assert_eq!(mask.dim(), obj.volume().dim());
+    assert_eq!(mask.get_u8(&[30, 17, 4]).unwrap(), 1);
+    assert_eq!(mask.get_u8(&[30, 16, 4]).unwrap(), 0);
+    assert_eq!(mask.raw_data().iter().filter(|v| **v == 1).count(), 640);
+    assert!(table.mask(obj.volume(), "Right-Hippocampus").is_err());
+}
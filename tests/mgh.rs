@@ -0,0 +1,116 @@
+extern crate byteorder;
+extern crate nifti;
+#[macro_use]
+extern crate pretty_assertions;
+extern crate tempfile;
+
+use byteorder::{BigEndian, WriteBytesExt};
+use nifti::mgh::{self, MghHeader};
+use nifti::{InMemNiftiObject, NiftiError, NiftiObject, NiftiType, NiftiVolume, Unit, XForm};
+use tempfile::tempdir;
+
+/// A 4x4x2 volume of 16-bit integers with two frames, in LIA orientation.
+fn mgh_bytes() -> Vec<u8> {
+    let mut data = Vec::new();
+    for v in &[1, 4, 4, 2, 2, 4, 3] {
+        data.write_i32::<BigEndian>(*v).unwrap();
+    }
+    data.write_i16::<BigEndian>(1).unwrap();
+    for v in &[
+        1., 1., 1.5, -1., 0., 0., 0., 0., -1., 0., 1., 0., 1., 2., 3.,
+    ] {
+        data.write_f32::<BigEndian>(*v).unwrap();
+    }
+    data.resize(284, 0);
+    for v in 0..64 {
+        data.write_i16::<BigEndian>(v - 8).unwrap();
+    }
+    for v in &[2300., 0.157, 2.98, 900., 256.] {
+        data.write_f32::<BigEndian>(*v).unwrap();
+    }
+    data
+}
+
+#[test]
+fn read_mgh() {
+    let obj = mgh::read_stream(&mgh_bytes()[..]).unwrap();
+    let header = obj.header();
+    assert_eq!(header.dim, [4, 4, 4, 2, 2, 1, 1, 1]);
+    assert_eq!(header.data_type().unwrap(), NiftiType::Int16);
+    assert_eq!(header.xyzt_units().unwrap(), (Unit::Mm, Unit::Msec));
+    assert_eq!(&header.pixdim[1..5], &[1., 1., 1.5, 2300.]);
+    assert_eq!(header.sform().unwrap(), XForm::ScannerAnat);
+    assert_eq!(
+        header.affine(),
+        [
+            [-1., 0., 0., 3.],
+            [0., 0., 1.5, 0.5],
+            [0., -1., 0., 5.],
+            [0., 0., 0., 1.],
+        ]
+    );
+
+    let volume = obj.volume();
+    assert_eq!(volume.dim(), [4, 4, 2, 2].as_ref());
+    assert_eq!(volume.get_f64(&[0, 0, 0, 0]).unwrap(), -8.);
+    assert_eq!(volume.get_f64(&[1, 2, 1, 1]).unwrap(), 57. - 8.);
+
+    let mgh = MghHeader::from_nifti(header).unwrap();
+    assert_eq!(mgh.dim, [4, 4, 2, 2]);
+    assert_eq!(mgh.center, [1., 2., 3.]);
+    assert_eq!(mgh.tr, 2300.);
+    assert_eq!(mgh.flip_angle, 0.157);
+    assert_eq!(mgh.te, 2.98);
+    assert_eq!(mgh.ti, 900.);
+    assert_eq!(mgh.fov, 256.);
+    assert_eq!(mgh.dof, 3);
+}
+
+#[test]
+fn write_mgh() {
+    let bytes = mgh_bytes();
+    let obj = mgh::read_stream(&bytes[..]).unwrap();
+    let mut written = Vec::new();
+    mgh::write_stream(&mut written, &obj).unwrap();
+    assert_eq!(written, bytes);
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("volume.mgz");
+    mgh::write_file(&path, &obj).unwrap();
+    assert_eq!(mgh::read_file(&path).unwrap(), obj);
+}
+
+#[test]
+fn nifti_to_mgh() {
+    let obj = InMemNiftiObject::from_file("resources/minimal.nii").unwrap();
+    let mut written = Vec::new();
+    mgh::write_stream(&mut written, &obj).unwrap();
+
+    let mgh_obj = mgh::read_stream(&written[..]).unwrap();
+    assert_eq!(mgh_obj.volume().dim(), obj.volume().dim());
+    assert_eq!(mgh_obj.volume().data_type(), NiftiType::Uint8);
+    assert_eq!(mgh_obj.volume().raw_data(), obj.volume().raw_data());
+    assert_eq!(mgh_obj.header().affine(), obj.header().affine());
+}
+
+#[test]
+fn invalid_mgh() {
+    let mut bytes = mgh_bytes();
+    bytes[3] = 2;
+    match mgh::read_stream(&bytes[..]) {
+        Err(NiftiError::InvalidMgh(_)) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+    let bytes = mgh_bytes();
+    assert!(mgh::read_stream(&bytes[..300]).is_err());
+
+    // a surface overlay of the fsaverage subject
+    let mut bytes = mgh_bytes();
+    for (i, v) in [163842i32, 1, 1, 1].iter().enumerate() {
+        bytes[4 + 4 * i..8 + 4 * i].copy_from_slice(&v.to_be_bytes());
+    }
+    match mgh::read_stream(&bytes[..]) {
+        Err(NiftiError::UnsupportedMghDimensions(dim)) => assert_eq!(dim, [163842, 1, 1, 1]),
+        r => panic!("unexpected result {:?}", r),
+    }
+}
@@ -0,0 +1,109 @@
+extern crate nifti;
+#[macro_use]
+extern crate pretty_assertions;
+extern crate tempfile;
+
+use nifti::{
+    npy, Endianness, InMemNiftiObject, InMemNiftiVolume, NiftiError, NiftiHeader, NiftiObject,
+    NiftiType, NiftiVolume,
+};
+use tempfile::tempdir;
+
+/// Build a ".npy" file with the given header dictionary and data.
+fn npy_bytes(version: u8, dict: &str, data: &[u8]) -> Vec<u8> {
+    let mut bytes = b"\x93NUMPY".to_vec();
+    bytes.extend_from_slice(&[version, 0]);
+    if version == 1 {
+        bytes.extend_from_slice(&(dict.len() as u16).to_le_bytes());
+    } else {
+        bytes.extend_from_slice(&(dict.len() as u32).to_le_bytes());
+    }
+    bytes.extend_from_slice(dict.as_bytes());
+    bytes.extend_from_slice(data);
+    bytes
+}
+
+#[test]
+fn write_npy() {
+    let obj = InMemNiftiObject::from_file("resources/minimal.nii").unwrap();
+    let mut bytes = Vec::new();
+    npy::write_volume(&mut bytes, obj.volume()).unwrap();
+
+    assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+    let len = bytes[8] as usize + ((bytes[9] as usize) << 8);
+    assert_eq!((10 + len) % 64, 0);
+    let dict = String::from_utf8_lossy(&bytes[10..10 + len]);
+    assert!(dict.starts_with("{'descr': '|u1', 'fortran_order': True, 'shape': (64, 64, 10), }"));
+    assert!(dict.ends_with(" \n"));
+    assert_eq!(&bytes[10 + len..], obj.volume().raw_data());
+
+    let read = npy::read_volume(&bytes[..]).unwrap();
+    assert_eq!(read.dim(), obj.volume().dim());
+    assert_eq!(read.data_type(), NiftiType::Uint8);
+    assert_eq!(read.raw_data(), obj.volume().raw_data());
+}
+
+#[test]
+fn big_endian_npy() {
+    let header = NiftiHeader {
+        dim: [2, 2, 3, 1, 1, 1, 1, 1],
+        datatype: NiftiType::Int16 as i16,
+        bitpix: 16,
+        endianness: Endianness::BE,
+        ..Default::default()
+    };
+    let data = (0..6i16).flat_map(|v| v.to_be_bytes().to_vec()).collect();
+    let volume = InMemNiftiVolume::from_raw_data(&header, data).unwrap();
+
+    let mut bytes = Vec::new();
+    npy::write_volume(&mut bytes, &volume).unwrap();
+    assert!(String::from_utf8_lossy(&bytes).contains("'descr': '>i2'"));
+    let read = npy::read_volume(&bytes[..]).unwrap();
+    assert_eq!(read, volume);
+    assert_eq!(read.get_f64(&[1, 2]).unwrap(), 5.);
+}
+
+#[test]
+fn c_order_npy() {
+    let data: Vec<u8> = (0..6i16).flat_map(|v| v.to_le_bytes().to_vec()).collect();
+    let bytes = npy_bytes(
+        2,
+        "{'descr': '<i2', 'fortran_order': False, 'shape': (2, 3), }\n",
+        &data,
+    );
+    let volume = npy::read_volume(&bytes[..]).unwrap();
+    assert_eq!(volume.dim(), [2, 3].as_ref());
+    for i in 0..2 {
+        for j in 0..3 {
+            assert_eq!(volume.get_f64(&[i, j]).unwrap(), f64::from(i * 3 + j));
+        }
+    }
+}
+
+#[test]
+fn npy_with_affine() {
+    let obj = InMemNiftiObject::from_file("resources/minimal.nii").unwrap();
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("minimal.npy");
+    npy::write_file(&path, &obj).unwrap();
+    assert!(dir.path().join("minimal.affine.npy").is_file());
+
+    let read = npy::read_file(&path).unwrap();
+    assert_eq!(read.volume().dim(), obj.volume().dim());
+    assert_eq!(read.volume().raw_data(), obj.volume().raw_data());
+    assert_eq!(read.header().affine(), obj.header().affine());
+}
+
+#[test]
+fn invalid_npy() {
+    let dict = "{'descr': '<f2', 'fortran_order': True, 'shape': (2,), }\n";
+    match npy::read_volume(&npy_bytes(1, dict, &[0; 4])[..]) {
+        Err(NiftiError::InvalidNpy(_)) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+    let dict = "{'descr': '<u2', 'fortran_order': True, 'shape': (2,), }\n";
+    assert!(npy::read_volume(&npy_bytes(1, dict, &[0; 3])[..]).is_err());
+    assert!(npy::read_volume(&npy_bytes(1, dict, &[0; 4])[1..]).is_err());
+    let dict = "{'descr': '<u2', 'fortran_order': True, 'shape': (), }\n";
+    assert!(npy::read_volume(&npy_bytes(1, dict, &[0; 2])[..]).is_err());
+}
@@ -0,0 +1,185 @@
+extern crate flate2;
+extern crate nifti;
+#[macro_use]
+extern crate pretty_assertions;
+extern crate tempfile;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use nifti::nrrd::{self, NrrdEncoding};
+use nifti::{InMemNiftiObject, NiftiError, NiftiObject, NiftiType, NiftiVolume, XForm};
+use std::fs;
+use std::io::Write;
+use tempfile::tempdir;
+
+/// A 2x3x2 volume of little endian 16-bit integers, in LPS space.
+fn nrrd_bytes() -> Vec<u8> {
+    let mut data = b"NRRD0004
+# Complete NRRD file format specification at:
+# http://teem.sourceforge.net/nrrd/format.html
+type: short
+dimension: 3
+space: left-posterior-superior
+sizes: 2 3 2
+space directions: (2,0,0) (0, 1.5, 0) (0,0,3)
+kinds: domain domain domain
+endian: little
+encoding: raw
+space origin: (10,20,30)
+Segment0_Name:=brain
+
+"
+    .to_vec();
+    for v in 0..12i16 {
+        data.extend_from_slice(&(v * 100).to_le_bytes());
+    }
+    data
+}
+
+#[test]
+fn read_nrrd() {
+    let obj = nrrd::read_stream(&nrrd_bytes()[..]).unwrap();
+    let header = obj.header();
+    assert_eq!(header.dim, [3, 2, 3, 2, 1, 1, 1, 1]);
+    assert_eq!(header.data_type().unwrap(), NiftiType::Int16);
+    assert_eq!(header.sform().unwrap(), XForm::ScannerAnat);
+    assert_eq!(
+        header.affine(),
+        [
+            [-2., 0., 0., -10.],
+            [0., -1.5, 0., -20.],
+            [0., 0., 3., 30.],
+            [0., 0., 0., 1.],
+        ]
+    );
+    assert_eq!(obj.volume().get_f64(&[1, 2, 1]).unwrap(), 1100.);
+}
+
+#[test]
+fn write_nrrd() {
+    let obj = nrrd::read_stream(&nrrd_bytes()[..]).unwrap();
+    for &encoding in &[NrrdEncoding::Raw, NrrdEncoding::Gzip] {
+        let mut written = Vec::new();
+        nrrd::write_stream(&mut written, &obj, encoding).unwrap();
+        assert_eq!(nrrd::read_stream(&written[..]).unwrap(), obj);
+    }
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("volume.nhdr");
+    nrrd::write_file(&path, &obj, NrrdEncoding::Gzip).unwrap();
+    let header = fs::read_to_string(&path).unwrap();
+    assert!(header.contains("space directions: (2,0,0) (0,1.5,0) (0,0,3)\n"));
+    assert!(header.contains("data file: volume.raw.gz\n"));
+    assert!(dir.path().join("volume.raw.gz").is_file());
+    assert_eq!(nrrd::read_file(&path).unwrap(), obj);
+}
+
+#[test]
+fn nifti_to_nrrd() {
+    let obj = InMemNiftiObject::from_file("resources/minimal.nii").unwrap();
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("minimal.nrrd");
+    nrrd::write_file(&path, &obj, NrrdEncoding::Raw).unwrap();
+
+    let nrrd_obj = nrrd::read_file(&path).unwrap();
+    assert_eq!(nrrd_obj.volume().dim(), obj.volume().dim());
+    assert_eq!(nrrd_obj.volume().raw_data(), obj.volume().raw_data());
+    assert_eq!(nrrd_obj.header().affine(), obj.header().affine());
+}
+
+#[test]
+fn nrrd_list_axis() {
+    // three gradients of a 2x1x1 volume, with the gradient axis first
+    let data = b"NRRD0005
+type: uint8
+dimension: 4
+space: right-anterior-superior
+sizes: 3 2 1 1
+space directions: none (1,0,0) (0,1,0) (0,0,1)
+kinds: list domain domain domain
+encoding: gzip
+
+"
+    .to_vec();
+    let mut e = GzEncoder::new(data, Compression::default());
+    e.write_all(&[1, 2, 3, 4, 5, 6]).unwrap();
+    let data = e.finish().unwrap();
+    let obj = nrrd::read_stream(&data[..]).unwrap();
+    assert_eq!(obj.header().dim, [4, 2, 1, 1, 3, 1, 1, 1]);
+    assert_eq!(obj.volume().raw_data(), &[1, 4, 2, 5, 3, 6]);
+    assert_eq!(obj.header().affine()[0], [1., 0., 0., 0.]);
+}
+
+#[test]
+fn nrrd_byte_skip_end() {
+    let bytes = nrrd_bytes();
+    let header_len = bytes.len() - 24;
+    let mut data = String::from_utf8_lossy(&bytes[..header_len])
+        .replace("encoding: raw", "encoding: raw\nbyte skip: -1")
+        .into_bytes();
+    data.extend_from_slice(b"unused");
+    data.extend_from_slice(&bytes[header_len..]);
+    let obj = nrrd::read_stream(&data[..]).unwrap();
+    assert_eq!(obj, nrrd::read_stream(&bytes[..]).unwrap());
+
+    let gzip = String::from_utf8_lossy(&data).replace("encoding: raw", "encoding: gzip");
+    assert!(nrrd::read_stream(gzip.as_bytes()).is_err());
+}
+
+#[test]
+fn nrrd_line_skip() {
+    let bytes = nrrd_bytes();
+    let header_len = bytes.len() - 24;
+    let header = String::from_utf8_lossy(&bytes[..header_len]);
+    let mut data = header
+        .replace("encoding: raw", "encoding: raw\nline skip: 2\nbyte skip: 3")
+        .into_bytes();
+    data.extend_from_slice(b"first line\n\nabc");
+    data.extend_from_slice(&bytes[header_len..]);
+    let obj = nrrd::read_stream(&data[..]).unwrap();
+    assert_eq!(obj, nrrd::read_stream(&bytes[..]).unwrap());
+
+    // the lines are skipped before decompressing the data
+    let mut data = header
+        .replace("encoding: raw", "encoding: gzip\nlineskip: 1")
+        .into_bytes();
+    data.extend_from_slice(b"not compressed\n");
+    let mut e = GzEncoder::new(data, Compression::fast());
+    e.write_all(&bytes[header_len..]).unwrap();
+    let data = e.finish().unwrap();
+    let obj = nrrd::read_stream(&data[..]).unwrap();
+    assert_eq!(obj, nrrd::read_stream(&bytes[..]).unwrap());
+
+    let data = header
+        .replace("encoding: raw", "encoding: raw\nline skip: 1")
+        .into_bytes();
+    assert!(nrrd::read_stream(&data[..]).is_err());
+}
+
+#[test]
+fn nrrd_multiple_data_files() {
+    let bytes = nrrd_bytes();
+    let header = String::from_utf8_lossy(&bytes[..bytes.len() - 24]).into_owned();
+    let dir = tempdir().unwrap();
+    for data_file in &["LIST\nslice0.raw\nslice1.raw", "slice%d.raw 0 1 1", "LIST 2"] {
+        let path = dir.path().join("multi.nhdr");
+        let nhdr = header.trim_end().to_string() + "\ndata file: " + data_file + "\n";
+        fs::write(&path, nhdr).unwrap();
+        match nrrd::read_file(&path) {
+            Err(NiftiError::InvalidNrrd(_)) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+}
+
+#[test]
+fn invalid_nrrd() {
+    let bytes = nrrd_bytes();
+    assert!(nrrd::read_stream(&bytes[1..]).is_err());
+    let other = String::from_utf8_lossy(&bytes).replace("encoding: raw", "encoding: bzip2");
+    match nrrd::read_stream(other.as_bytes()) {
+        Err(NiftiError::InvalidNrrd(_)) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+    assert!(nrrd::read_stream(&bytes[..bytes.len() - 1]).is_err());
+}
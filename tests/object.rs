@@ -1,11 +1,21 @@
+#[cfg(feature = "bzip2")]
+extern crate bzip2;
 extern crate flate2;
 #[cfg(feature = "ndarray_volumes")]
 extern crate ndarray;
 extern crate nifti;
 #[macro_use]
 extern crate pretty_assertions;
+extern crate tempfile;
+#[cfg(feature = "xz2")]
+extern crate xz2;
+#[cfg(feature = "zstd")]
+extern crate zstd;
 
-use nifti::{Endianness, InMemNiftiObject, NiftiHeader, NiftiObject, NiftiType, NiftiVolume, XForm};
+use nifti::{Endianness, InMemNiftiObject, InMemNiftiVolume, Interpolation, NiftiError,
+            NiftiHeader, NiftiObject, NiftiType, NiftiVolume, XForm};
+use std::fs;
+use tempfile::tempdir;
 
 #[test]
 fn minimal_nii_gz() {
@@ -79,6 +89,34 @@ fn minimal_by_hdr() {
     assert_eq!(volume.dim(), [64, 64, 10].as_ref());
 }
 
+#[test]
+fn analyze_by_hdr() {
+    let dir = tempdir().unwrap();
+    let hdr_path = dir.path().join("analyze.hdr");
+    let mut raw = fs::read("resources/minimal.hdr").unwrap();
+    raw.truncate(348);
+    // no magic code, SPM origin at voxel (1, 1, 1)
+    raw[344..348].copy_from_slice(&[0; 4]);
+    raw[253..259].copy_from_slice(&[0, 1, 0, 1, 0, 1]);
+    fs::write(&hdr_path, &raw).unwrap();
+    fs::copy("resources/minimal.img", dir.path().join("analyze.img")).unwrap();
+
+    match InMemNiftiObject::from_file(&hdr_path) {
+        Err(NiftiError::InvalidFormat) => {}
+        r => panic!("unexpected result {:?}", r.map(|o| o.header().clone())),
+    }
+
+    let obj = InMemNiftiObject::from_analyze_file(&hdr_path).unwrap();
+    assert_eq!(&obj.header().magic, b"ni1\0");
+    assert_eq!(obj.header().sform().unwrap(), XForm::AlignedAnat);
+    assert_eq!(obj.header().affine()[0], [-3., 0., 0., 0.]);
+    let volume = obj.volume();
+    assert_eq!(volume.data_type(), NiftiType::Uint8);
+    assert_eq!(volume.dim(), [64, 64, 10].as_ref());
+    let expected = InMemNiftiObject::from_file("resources/minimal.hdr").unwrap();
+    assert_eq!(volume.raw_data(), expected.volume().raw_data());
+}
+
 #[test]
 fn minimal_by_hdr_and_img_gz() {
     let minimal_hdr = NiftiHeader {
@@ -153,6 +191,108 @@ fn minimal_by_pair() {
     assert_eq!(volume.dim(), [64, 64, 10].as_ref());
 }
 
+#[test]
+fn minimal_from_bytes() {
+    let expected = InMemNiftiObject::from_file("resources/minimal.nii").unwrap();
+    for path in &["resources/minimal.nii", "resources/minimal.nii.gz"] {
+        let bytes = fs::read(path).unwrap();
+        let obj = InMemNiftiObject::from_bytes(&bytes).unwrap();
+        assert_eq!(obj.header(), expected.header());
+        assert_eq!(obj.volume().raw_data(), expected.volume().raw_data());
+        let obj = InMemNiftiObject::from_reader(fs::File::open(path).unwrap()).unwrap();
+        assert_eq!(obj.volume().raw_data(), expected.volume().raw_data());
+    }
+
+    let bytes = fs::read("resources/minimal.hdr").unwrap();
+    match InMemNiftiObject::from_bytes(&bytes) {
+        Err(NiftiError::NoVolumeData) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+    assert!(InMemNiftiObject::from_bytes(&[0x1f]).is_err());
+}
+
+#[test]
+fn misnamed_gz_files() {
+    let expected = InMemNiftiObject::from_file("resources/minimal.nii").unwrap();
+    let dir = tempdir().unwrap();
+    let gz_as_nii = dir.path().join("gz.nii");
+    let nii_as_gz = dir.path().join("plain.nii.gz");
+    let _ = fs::copy("resources/minimal.nii.gz", &gz_as_nii).unwrap();
+    let _ = fs::copy("resources/minimal.nii", &nii_as_gz).unwrap();
+
+    for path in &[&gz_as_nii, &nii_as_gz] {
+        assert_eq!(&NiftiHeader::from_file(path).unwrap(), expected.header());
+        let obj = InMemNiftiObject::from_file(path).unwrap();
+        assert_eq!(obj.header(), expected.header());
+        assert_eq!(obj.volume().raw_data(), expected.volume().raw_data());
+    }
+
+    // a Gzip compressed volume file without the ".gz" extension
+    let img = dir.path().join("minimal.img");
+    let _ = fs::copy("resources/minimal.img.gz", &img).unwrap();
+    let header = NiftiHeader::from_file("resources/minimal.hdr").unwrap();
+    let volume = InMemNiftiVolume::from_file(&img, &header).unwrap();
+    assert_eq!(volume.raw_data(), expected.volume().raw_data());
+}
+
+#[test]
+fn compressed_files() {
+    let expected = InMemNiftiObject::from_file("resources/minimal.nii").unwrap();
+    #[cfg(any(feature = "zstd", feature = "bzip2", feature = "xz2"))]
+    let raw = fs::read("resources/minimal.nii").unwrap();
+    #[allow(unused_mut)]
+    let mut compressed: Vec<Vec<u8>> = Vec::new();
+    #[cfg(feature = "zstd")]
+    compressed.push(zstd::encode_all(&raw[..], 0).unwrap());
+    #[cfg(feature = "bzip2")]
+    {
+        use std::io::Write;
+        let mut e = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::Default);
+        e.write_all(&raw).unwrap();
+        compressed.push(e.finish().unwrap());
+    }
+    #[cfg(feature = "xz2")]
+    {
+        use std::io::Write;
+        let mut e = xz2::write::XzEncoder::new(Vec::new(), 6);
+        e.write_all(&raw).unwrap();
+        compressed.push(e.finish().unwrap());
+    }
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("minimal.nii");
+    for bytes in &compressed {
+        fs::write(&path, bytes).unwrap();
+        assert_eq!(&NiftiHeader::from_file(&path).unwrap(), expected.header());
+        let obj = InMemNiftiObject::from_file(&path).unwrap();
+        assert_eq!(obj.volume().raw_data(), expected.volume().raw_data());
+        let obj = InMemNiftiObject::from_bytes(bytes).unwrap();
+        assert_eq!(obj.volume().raw_data(), expected.volume().raw_data());
+    }
+
+    #[cfg(not(feature = "zstd"))]
+    match InMemNiftiObject::from_bytes(&[0x28, 0xb5, 0x2f, 0xfd, 0, 0]) {
+        Err(NiftiError::UnsupportedCompression("zstd")) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+}
+
+#[test]
+fn minimal_from_reader_pair() {
+    let expected = InMemNiftiObject::from_file("resources/minimal.hdr").unwrap();
+    for &(hdr, img) in &[
+        ("resources/minimal.hdr", "resources/minimal.img"),
+        ("resources/minimal.hdr.gz", "resources/minimal.img.gz"),
+        ("resources/minimal.hdr", "resources/minimal.img.gz"),
+    ] {
+        let hdr = fs::read(hdr).unwrap();
+        let img = fs::read(img).unwrap();
+        let obj = InMemNiftiObject::from_reader_pair(&hdr[..], &img[..]).unwrap();
+        assert_eq!(obj.header(), expected.header());
+        assert_eq!(obj.volume().raw_data(), expected.volume().raw_data());
+    }
+}
+
 #[test]
 fn f32_nii_gz() {
     let f32_hdr = NiftiHeader {
@@ -190,3 +330,95 @@ fn f32_nii_gz() {
     assert_eq!(volume.get_f32(&[5, 0, 4]).unwrap(), 0.4);
     assert_eq!(volume.get_f32(&[0, 8, 5]).unwrap(), 0.8);
 }
+
+#[test]
+fn f32_nii_gz_deoblique() {
+    const FILE_NAME: &str = "resources/f32.nii.gz";
+    let obj = InMemNiftiObject::from_file(FILE_NAME).unwrap();
+
+    // already cardinal, so nothing should change in the voxel grid
+    let deobliqued = obj.deoblique(Interpolation::Linear).unwrap();
+    assert_eq!(deobliqued.header().dim, obj.header().dim);
+    assert_eq!(deobliqued.header().affine(), obj.header().affine());
+    assert_eq!(deobliqued.header().obliquity(), 0.);
+    assert_eq!(deobliqued.volume(), obj.volume());
+}
+
+#[test]
+fn minimal_nii_deoblique() {
+    let angle = 30f32.to_radians();
+    let mut obj = InMemNiftiObject::from_file("resources/minimal.nii").unwrap();
+    {
+        let header = obj.header_mut();
+        header.sform_code = 1;
+        header.srow_x = [3. * angle.cos(), -3. * angle.sin(), 0., 0.];
+        header.srow_y = [3. * angle.sin(), 3. * angle.cos(), 0., 0.];
+        header.srow_z = [0., 0., 3., 0.];
+    }
+    assert!(obj.header().is_oblique());
+
+    let deobliqued = obj.deoblique(Interpolation::Nearest).unwrap();
+    let header = deobliqued.header();
+    assert!(!header.is_oblique());
+    assert_eq!(&header.pixdim[1..4], &[3., 3., 3.]);
+    // the rotated 64x64 plane is covered by a larger grid
+    assert!(header.dim[1] > 64);
+    assert!(header.dim[2] > 64);
+    assert_eq!(header.dim[3], 10);
+    assert_eq!(deobliqued.volume().dim(), &header.dim[1..4]);
+
+    // the voxel at the world origin is the same in both objects
+    let affine = header.affine();
+    let origin = [
+        (-affine[0][3] / affine[0][0]).round() as u16,
+        (-affine[1][3] / affine[1][1]).round() as u16,
+        0,
+    ];
+    assert_eq!(
+        deobliqued.volume().get_f32(&origin).unwrap(),
+        obj.volume().get_f32(&[0, 0, 0]).unwrap()
+    );
+}
+
+#[test]
+#[allow(non_snake_case)]
+fn avg152T1_LR_nii_gz_crop_and_pad() {
+    const FILE_NAME: &str = "resources/avg152T1_LR_nifti.nii.gz";
+    let obj = InMemNiftiObject::from_file(FILE_NAME).unwrap();
+
+    let cropped = obj.crop_to_bounding_box().unwrap();
+    let bbox = obj.volume().bounding_box().unwrap();
+    let bbox_dim: Vec<_> = bbox.iter().map(|r| r.end - r.start).collect();
+    assert_eq!(cropped.volume().dim(), bbox_dim.as_slice());
+    assert_eq!(&cropped.header().dim[1..4], cropped.volume().dim());
+
+    // world coordinates are preserved
+    let origin = [
+        f64::from(bbox[0].start),
+        f64::from(bbox[1].start),
+        f64::from(bbox[2].start),
+    ];
+    let a1 = obj.header().affine();
+    let a2 = cropped.header().affine();
+    for r in 0..3 {
+        let world: f64 = (0..3).map(|c| a1[r][c] * origin[c]).sum::<f64>() + a1[r][3];
+        assert_eq!(a2[r][3], world);
+    }
+    let coords = [bbox[0].start + 10, bbox[1].start + 20, bbox[2].start + 30];
+    assert_eq!(
+        cropped.volume().get_u8(&[10, 20, 30]).unwrap(),
+        obj.volume().get_u8(&coords).unwrap()
+    );
+
+    let padded = cropped.pad_to(&[96, 112, 96]).unwrap();
+    assert_eq!(padded.volume().dim(), &[96, 112, 96]);
+    assert_eq!(padded.volume().bounding_box(), {
+        let shift = |r: &std::ops::Range<u16>, d: u16| {
+            let b = (d - (r.end - r.start)) / 2;
+            b..b + r.end - r.start
+        };
+        let b = cropped.volume().bounding_box().unwrap();
+        Some(vec![shift(&b[0], 96), shift(&b[1], 112), shift(&b[2], 96)])
+    });
+    assert!(cropped.pad_to(&[10, 10, 10]).is_err());
+}
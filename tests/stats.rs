@@ -0,0 +1,71 @@
+extern crate nifti;
+
+use nifti::stats::{convert, pvalue, zscore_from_pvalue, StatOutput};
+use nifti::{InMemNiftiObject, Intent, NiftiObject, NiftiType, NiftiVolume};
+
+fn assert_close(got: f64, expected: f64, tol: f64) {
+    assert!(
+        (got - expected).abs() <= tol * expected.abs().max(1e-300),
+        "got {}, expected {}",
+        got,
+        expected
+    );
+}
+
+#[test]
+fn stat_pvalues() {
+    // reference values computed independently (closed forms or numerical integration)
+    assert_close(pvalue(1.96, Intent::Zscore, [0.; 3]).unwrap(), 0.024_997_895_148_220_435, 1e-9);
+    assert_close(pvalue(-1., Intent::Zscore, [0.; 3]).unwrap(), 0.841_344_746_068_542_9, 1e-9);
+    assert_close(pvalue(8., Intent::Zscore, [0.; 3]).unwrap(), 6.220_960_574_271_785e-16, 1e-6);
+    assert_close(pvalue(2., Intent::Ttest, [10., 0., 0.]).unwrap(), 0.036_694_017_385_370_16, 1e-9);
+    assert_close(pvalue(-2., Intent::Ttest, [10., 0., 0.]).unwrap(), 0.963_305_982_614_629_8, 1e-9);
+    assert_close(pvalue(3., Intent::Ftest, [2., 10., 0.]).unwrap(), 0.095_367_431_640_625, 1e-9);
+    assert_close(pvalue(3.84, Intent::Chisq, [1., 0., 0.]).unwrap(), 0.050_043_521_248_705_2, 1e-9);
+    assert_close(pvalue(0.5, Intent::Correl, [20., 0., 0.]).unwrap(), 0.008_903_279_303_931_541, 1e-6);
+    assert_close(pvalue(3., Intent::Log10pval, [0.; 3]).unwrap(), 1e-3, 1e-9);
+
+    assert!(pvalue(2., Intent::Ttest, [0., 0., 0.]).is_err());
+    assert!(pvalue(2., Intent::Label, [0., 0., 0.]).is_err());
+}
+
+#[test]
+fn stat_zscores() {
+    assert_close(zscore_from_pvalue(0.025), 1.959_963_984_540_054, 1e-9);
+    assert_close(zscore_from_pvalue(0.5).abs() + 1., 1., 1e-12);
+    assert_close(zscore_from_pvalue(1e-20), 9.262_340_089_798_408, 1e-9);
+    assert_close(zscore_from_pvalue(0.9), -1.281_551_565_544_600_5, 1e-9);
+
+    // a z-score round trip is the identity
+    let z = convert(3.5, Intent::Zscore, [0.; 3], StatOutput::Zscore).unwrap();
+    assert_eq!(z, 3.5);
+    let t = convert(2., Intent::Ttest, [10., 0., 0.], StatOutput::Zscore).unwrap();
+    assert_close(t, 1.790_409_932_268_829_2, 1e-8);
+    let logp = convert(2., Intent::Ttest, [10., 0., 0.], StatOutput::Log10Pvalue).unwrap();
+    assert_close(logp, 1.435_404_737_609_353_7, 1e-8);
+}
+
+#[test]
+fn zstat1_to_pvalue() {
+    const FILE_NAME: &str = "resources/zstat1.nii.gz";
+    let obj = InMemNiftiObject::from_file(FILE_NAME).unwrap();
+    let pmap = obj.convert_stat(StatOutput::Pvalue).unwrap();
+
+    assert_eq!(pmap.header().intent().unwrap(), Intent::Pval);
+    assert_eq!(pmap.header().data_type().unwrap(), NiftiType::Float32);
+    assert_eq!(pmap.volume().dim(), obj.volume().dim());
+    for coords in &[[0, 0, 0], [32, 32, 10], [20, 40, 5], [63, 63, 20]] {
+        let z = obj.volume().get_f64(coords).unwrap();
+        let p = pmap.volume().get_f64(coords).unwrap();
+        let expected = pvalue(z, Intent::Zscore, [0.; 3]).unwrap();
+        assert_close(p, expected, 1e-6);
+    }
+
+    // converting a p-value map to z-scores goes back to the original values
+    let zmap = pmap.convert_stat(StatOutput::Zscore).unwrap();
+    let z1 = obj.volume().get_f64(&[32, 32, 10]).unwrap();
+    let z2 = zmap.volume().get_f64(&[32, 32, 10]).unwrap();
+    assert!((z1 - z2).abs() < 1e-3);
+
+    assert!(zmap.convert_stat(StatOutput::Pvalue).is_ok());
+}
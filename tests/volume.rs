@@ -13,7 +13,10 @@ extern crate num_traits;
 #[cfg(feature = "ndarray_volumes")]
 extern crate safe_transmute;
 
-use nifti::{Endianness, InMemNiftiVolume, NiftiHeader, NiftiVolume};
+use nifti::volume::warp::warp;
+use nifti::{ComponentLayout, ComponentVolume, Endianness, InMemNiftiObject, InMemNiftiVolume,
+            Intent, Interpolation, NiftiError, NiftiHeader, NiftiObject, NiftiVolume,
+            WarpConvention, WarpMode, WarpSpace};
 
 #[test]
 fn minimal_img_gz() {
@@ -53,11 +56,190 @@ fn minimal_img_gz() {
     }
 }
 
+/// A 2x1x1 volume with 2 time points of 3x3 symmetric matrices, where the
+/// stored components at voxel `(x, 0, 0, t)` are `100 * t + 10 * x + c`.
+fn symmatrix_volume() -> (NiftiHeader, InMemNiftiVolume) {
+    let header = NiftiHeader {
+        dim: [5, 2, 1, 1, 2, 6, 1, 1],
+        datatype: 16,
+        bitpix: 32,
+        intent_code: Intent::Symmatrix as i16,
+        intent_p1: 3.,
+        endianness: Endianness::LE,
+        ..Default::default()
+    };
+    let mut raw_data = Vec::with_capacity(2 * 2 * 6 * 4);
+    for c in 0..6 {
+        for t in 0..2 {
+            for x in 0..2 {
+                let value = (100 * t + 10 * x + c) as f32;
+                raw_data.extend(&value.to_bits().to_le_bytes());
+            }
+        }
+    }
+    let volume = InMemNiftiVolume::from_raw_data(&header, raw_data).unwrap();
+    (header, volume)
+}
+
+#[test]
+fn symmatrix_components() {
+    let (mut header, volume) = symmatrix_volume();
+    let tensors = ComponentVolume::new(&volume, &header).unwrap();
+    assert_eq!(tensors.layout(), ComponentLayout::SymMatrix(3));
+    assert_eq!(tensors.dim(), &[2, 1, 1, 2]);
+
+    assert_eq!(
+        tensors.get_components(&[1, 0, 0, 1]).unwrap(),
+        vec![110., 111., 112., 113., 114., 115.]
+    );
+    assert_eq!(
+        tensors.get_matrix(&[1, 0, 0, 1]).unwrap(),
+        vec![
+            vec![110., 111., 113.],
+            vec![111., 112., 114.],
+            vec![113., 114., 115.],
+        ]
+    );
+    assert!(tensors.get_matrix(&[2, 0, 0, 0]).is_err());
+    assert!(tensors.get_matrix(&[0, 0, 0]).is_err());
+
+    // the same data read as 6-component vectors, or as 2x3 matrices
+    header.intent_code = Intent::Vector as i16;
+    let vectors = ComponentVolume::new(&volume, &header).unwrap();
+    assert_eq!(vectors.layout(), ComponentLayout::Vector(6));
+    assert_eq!(
+        vectors.get_matrix(&[0, 0, 0, 0]).unwrap(),
+        vec![vec![0.], vec![1.], vec![2.], vec![3.], vec![4.], vec![5.]]
+    );
+    let layout = ComponentLayout::Matrix { rows: 2, cols: 3 };
+    let matrices = ComponentVolume::with_layout(&volume, layout).unwrap();
+    assert_eq!(
+        matrices.get_matrix(&[0, 0, 0, 1]).unwrap(),
+        vec![vec![100., 101., 102.], vec![103., 104., 105.]]
+    );
+
+    // dim[5] must match the matrix size
+    header.intent_code = Intent::Symmatrix as i16;
+    header.intent_p1 = 2.;
+    assert!(ComponentVolume::new(&volume, &header).is_err());
+    header.intent_code = Intent::Estimate as i16;
+    assert!(ComponentVolume::new(&volume, &header).is_err());
+}
+
+/// A displacement field over the grid of `resources/minimal.nii`, holding
+/// the vector given by `f` at each voxel.
+fn displacement_field<F>(f: F) -> (NiftiHeader, InMemNiftiVolume)
+where
+    F: Fn([u16; 3]) -> [f32; 3],
+{
+    let header = NiftiHeader {
+        dim: [5, 64, 64, 10, 1, 3, 1, 1],
+        pixdim: [1., 3., 3., 3., 1., 1., 1., 1.],
+        datatype: 16,
+        bitpix: 32,
+        intent_code: Intent::Vector as i16,
+        endianness: Endianness::LE,
+        ..Default::default()
+    };
+    let mut raw_data = Vec::with_capacity(64 * 64 * 10 * 3 * 4);
+    for c in 0..3 {
+        for k in 0..10 {
+            for j in 0..64 {
+                for i in 0..64 {
+                    let value = f([i, j, k])[c];
+                    raw_data.extend(&value.to_bits().to_le_bytes());
+                }
+            }
+        }
+    }
+    let volume = InMemNiftiVolume::from_raw_data(&header, raw_data).unwrap();
+    (header, volume)
+}
+
+#[test]
+fn minimal_nii_warp() {
+    // voxel values are their `j` index, voxels are 3 mm wide
+    let obj = InMemNiftiObject::from_file("resources/minimal.nii").unwrap();
+    let apply = |field: &(NiftiHeader, InMemNiftiVolume), convention| {
+        warp(
+            obj.volume(),
+            obj.header(),
+            &field.1,
+            &field.0,
+            convention,
+            Interpolation::Nearest,
+        )
+        .unwrap()
+    };
+    let ras = WarpConvention {
+        space: WarpSpace::Ras,
+        mode: WarpMode::Relative,
+    };
+
+    let identity = displacement_field(|_| [0.; 3]);
+    assert_eq!(&apply(&identity, ras), obj.volume());
+
+    // one voxel forward along y, in all relative conventions
+    let shift = displacement_field(|_| [0., 3., 0.]);
+    let expected = |volume: &InMemNiftiVolume| {
+        assert_eq!(volume.dim(), &[64, 64, 10]);
+        assert_eq!(volume.get_u8(&[5, 20, 2]).unwrap(), 21);
+        assert_eq!(volume.get_u8(&[5, 63, 2]).unwrap(), 0);
+    };
+    expected(&apply(&shift, ras));
+    expected(&apply(&shift, WarpConvention::fsl(WarpMode::Relative)));
+    expected(&apply(&displacement_field(|_| [0., -3., 0.]), WarpConvention::itk()));
+    expected(&apply(
+        &displacement_field(|_| [0., 1., 0.]),
+        WarpConvention {
+            space: WarpSpace::Voxel,
+            mode: WarpMode::Relative,
+        },
+    ));
+    expected(&apply(
+        &displacement_field(|[i, j, k]| [f32::from(i), f32::from(j) + 1., f32::from(k)]),
+        WarpConvention {
+            space: WarpSpace::Voxel,
+            mode: WarpMode::Absolute,
+        },
+    ));
+    expected(&apply(
+        &displacement_field(|[i, j, k]| {
+            [3. * f32::from(i), 3. * f32::from(j) + 3., 3. * f32::from(k)]
+        }),
+        WarpConvention {
+            space: WarpSpace::Ras,
+            mode: WarpMode::Absolute,
+        },
+    ));
+
+    // FSL coordinates flip the x axis of images in neurological order
+    let shift_x = displacement_field(|_| [3., 0., 0.]);
+    let warped = apply(&shift_x, ras);
+    assert_eq!(warped.get_u8(&[0, 20, 2]).unwrap(), 20);
+    assert_eq!(warped.get_u8(&[63, 20, 2]).unwrap(), 0);
+    let warped = apply(&shift_x, WarpConvention::fsl(WarpMode::Relative));
+    assert_eq!(warped.get_u8(&[0, 20, 2]).unwrap(), 0);
+    assert_eq!(warped.get_u8(&[63, 20, 2]).unwrap(), 20);
+
+    // two components per voxel
+    let (mut header, _) = displacement_field(|_| [0.; 3]);
+    header.dim[5] = 2;
+    let raw_data = vec![0; 64 * 64 * 10 * 2 * 4];
+    let volume = InMemNiftiVolume::from_raw_data(&header, raw_data).unwrap();
+    match warp(obj.volume(), obj.header(), &volume, &header, ras, Interpolation::Nearest) {
+        Err(NiftiError::InvalidDisplacementField(dim)) => {
+            assert_eq!(dim, vec![64, 64, 10, 1, 2])
+        }
+        r => panic!("unexpected result {:?}", r),
+    }
+}
+
 #[cfg(feature = "ndarray_volumes")]
 mod ndarray_volumes {
     use std::fmt;
     use std::ops::{Add, Mul};
-    use nifti::{DataElement, Endianness, InMemNiftiObject, InMemNiftiVolume,
+    use nifti::{ComponentVolume, DataElement, Endianness, InMemNiftiObject, InMemNiftiVolume,
                 NiftiHeader, NiftiObject, NiftiVolume, NiftiType, IntoNdArray};
     use ndarray::{Array, Axis, IxDyn, ShapeBuilder};
     use num_traits::AsPrimitive;
@@ -242,6 +424,32 @@ mod ndarray_volumes {
         test_all(FILE_NAME, NiftiType::Float64);
     }
 
+    #[test]
+    fn symmatrix_ndarray() {
+        let (mut header, volume) = super::symmatrix_volume();
+        let tensors = ComponentVolume::new(volume.clone(), &header).unwrap();
+        let data = tensors.into_ndarray::<f32>().unwrap();
+        assert_eq!(data.shape(), &[2, 1, 1, 2, 3, 3]);
+        assert_eq!(data[[1, 0, 0, 1, 0, 2]], 113.);
+        assert_eq!(data[[1, 0, 0, 1, 2, 0]], 113.);
+        assert_eq!(data[[0, 0, 0, 0, 1, 1]], 2.);
+
+        // the time axis is kept with a single time point
+        let mut single = header.clone();
+        single.dim[4] = 1;
+        let first = volume.crop(&[0..2, 0..1, 0..1, 0..1, 0..6]).unwrap();
+        let tensors = ComponentVolume::new(first, &single).unwrap();
+        let data = tensors.into_ndarray::<f32>().unwrap();
+        assert_eq!(data.shape(), &[2, 1, 1, 1, 3, 3]);
+        assert_eq!(data[[0, 0, 0, 0, 1, 1]], 2.);
+
+        header.intent_code = nifti::Intent::Vector as i16;
+        let vectors = ComponentVolume::new(volume, &header).unwrap();
+        let data = vectors.into_ndarray::<f32>().unwrap();
+        assert_eq!(data.shape(), &[2, 1, 1, 2, 6]);
+        assert_eq!(data[[1, 0, 0, 1, 3]], 113.);
+    }
+
     fn test_all(path: &str, dtype: NiftiType)
     {
         test_types::<i8>(path, dtype);
@@ -20,8 +20,12 @@ mod tests {
     use nifti::{
         header::MAGIC_CODE_NIP1,
         object::NiftiObject,
-        writer::{write_nifti, write_rgb_nifti},
-        InMemNiftiObject, IntoNdArray, NiftiHeader, NiftiType,
+        writer::{
+            write_nifti, write_nifti_with_extensions, write_nifti_with_options, write_rgb_nifti,
+            WriterOptions,
+        },
+        Extension, ExtensionCode, ExtensionSequence, InMemNiftiObject, IntoNdArray, NiftiHeader,
+        NiftiType,
     };
 
     fn get_temporary_path(ext: &str) -> PathBuf {
@@ -122,6 +126,87 @@ mod tests {
         test_write_read(&arr, "test_non_contiguous.nii.gz");
     }
 
+    #[test]
+    fn test_compressed_writing() {
+        let arr = f_order_array();
+        let mut formats: Vec<(&str, &[u8])> = vec![("test.nii.gz", &[0x1f, 0x8b])];
+        if cfg!(feature = "zstd") {
+            formats.push(("test.nii.zst", &[0x28, 0xb5, 0x2f, 0xfd]));
+        }
+        if cfg!(feature = "bzip2") {
+            formats.push(("test.nii.bz2", b"BZh"));
+        }
+        if cfg!(feature = "xz2") {
+            formats.push(("test.nii.xz", b"\xfd7zXZ\0"));
+        }
+
+        for (name, magic) in formats {
+            let path = get_temporary_path(name);
+            write_nifti(&path, &arr, None).unwrap();
+            let mut bytes = vec![];
+            let _ = File::open(&path)
+                .unwrap()
+                .read_to_end(&mut bytes)
+                .unwrap();
+            assert!(bytes.starts_with(magic));
+            let read_nifti = read_2d_image(path);
+            assert!(read_nifti.all_close(&arr, 1e-10));
+        }
+    }
+
+    #[test]
+    fn test_gzip_options() {
+        // large enough for several rounds of parallel compression
+        let arr = Array::from_shape_fn((64, 64, 48), |(i, j, k)| ((i * j + k) % 251) as f32);
+        let raw_len = 352 + arr.len() * 4;
+        let mut sizes = vec![];
+        for &(gzip_level, gzip_threads) in &[(0, 1), (1, 1), (9, 1), (0, 3), (6, 3), (9, 4)] {
+            let path = get_temporary_path("test_options.nii.gz");
+            let options = WriterOptions {
+                gzip_level,
+                gzip_threads,
+            };
+            write_nifti_with_options(&path, &arr, None, &options).unwrap();
+            let size = path.metadata().unwrap().len() as usize;
+            sizes.push(size);
+
+            let obj = InMemNiftiObject::from_file(&path).unwrap();
+            let read = obj.into_volume().into_ndarray::<f32>().unwrap();
+            assert_eq!(read, arr.clone().into_dyn());
+        }
+
+        // stored blocks do not shrink the data
+        assert!(sizes[0] > raw_len);
+        assert!(sizes[3] > raw_len);
+        assert!(sizes[2] <= sizes[1]);
+        assert!(sizes[4] < raw_len);
+    }
+
+    #[test]
+    fn test_write_extensions() {
+        let arr = Array::from_shape_fn((4, 3, 2), |(i, j, k)| (i * 6 + j * 2 + k) as f32);
+        let mut extensions = ExtensionSequence::new();
+        extensions.push(Extension::from_data(6, b"written by nifti-rs".to_vec()));
+        extensions.push(Extension::from_data(4, b"<AFNI_attributes/>".to_vec()));
+        assert_eq!(extensions.iter().map(|e| e.size()).sum::<i32>(), 32 + 32);
+
+        for ext in &["extensions.nii", "extensions.nii.gz"] {
+            let path = get_temporary_path(ext);
+            let options = WriterOptions::default();
+            write_nifti_with_extensions(&path, &arr, None, &extensions, &options).unwrap();
+
+            let obj = InMemNiftiObject::from_file(&path).unwrap();
+            assert_eq!(obj.header().vox_offset, 352. + 64.);
+            assert!(obj.extensions().extender().has_extensions());
+            assert_eq!(obj.extensions(), &extensions);
+            assert_eq!(obj.extensions().comments(), vec!["written by nifti-rs"]);
+            assert_eq!(obj.extensions().afni_xml().unwrap(), "<AFNI_attributes/>");
+            assert!(obj.extensions().find(ExtensionCode::Cifti).is_none());
+            let read = obj.into_volume().into_ndarray::<f32>().unwrap();
+            assert_eq!(read, arr.clone().into_dyn());
+        }
+    }
+
     #[test]
     fn test_header_slope_inter() {
         let arr = f_order_array();